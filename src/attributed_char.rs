@@ -34,6 +34,37 @@ impl AttributedChar {
         AttributedChar { ch, attribute }
     }
 
+    /// Creates the right half cell of a double width character.
+    pub fn wide_continuation(mut attribute: TextAttribute) -> Self {
+        attribute.attr |= crate::attribute::WIDE_CONTINUATION;
+        AttributedChar { ch: ' ', attribute }
+    }
+
+    /// Is the character double width (CJK/emoji)? Only meaningful for unicode buffers.
+    pub fn is_wide(&self) -> bool {
+        !self.is_wide_continuation() && crate::get_char_width(self.ch) == 2
+    }
+
+    /// Gives back the text of the cell - a cell may hold a whole grapheme cluster (base char + combining marks).
+    pub fn get_text(&self) -> String {
+        crate::get_grapheme_cluster(self.ch).unwrap_or_else(|| self.ch.to_string())
+    }
+
+    /// Appends a combining char to the grapheme cluster of this cell.
+    /// Returns false if the cluster can't be stored, marks beyond [`crate::MAX_CLUSTER_CHARS`] are dropped.
+    pub fn push_to_cluster(&mut self, ch: char) -> bool {
+        if let Some(cluster) = crate::grapheme::push_to_grapheme_cluster(self.ch, ch) {
+            self.ch = cluster;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn is_wide_continuation(&self) -> bool {
+        (self.attribute.attr & crate::attribute::WIDE_CONTINUATION) != 0
+    }
+
     pub fn is_transparent(self) -> bool {
         (self.ch == '\0' || self.ch == ' ') && self.attribute.get_background() == 0
     }
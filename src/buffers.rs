@@ -10,8 +10,8 @@ use i18n_embed_fl::fl;
 
 use crate::paint::HalfBlock;
 use crate::{
-    parsers, EngineResult, Glyph, Layer, LoadingError, OutputFormat, Position, Rectangle, SauceData, Sixel, TerminalState, TextAttribute, TextPane,
-    UnicodeConverter, FORMATS,
+    parsers, Caret, EngineResult, Glyph, Layer, LineSize, LoadingError, OutputFormat, Position, Rectangle, SauceData, Scrollback, Sixel, TerminalState,
+    TextAttribute, TextPane, UnicodeConverter, FORMATS,
 };
 
 use super::{AttributedChar, BitFont, Palette, SaveOptions, Size};
@@ -159,6 +159,11 @@ pub struct Buffer {
     pub font_mode: FontMode,
 
     pub is_terminal_buffer: bool,
+    /// Lines that scrolled off the top of a terminal screen.
+    pub scrollback: Scrollback,
+    /// The screen plane that's currently not shown - the main screen while the alternate screen is active and vice versa.
+    inactive_screen: Option<ScreenPlane>,
+    alternate_screen_active: bool,
 
     sauce_data: Option<SauceData>,
 
@@ -174,6 +179,14 @@ pub struct Buffer {
                                                                                // pub redo_stack: Vec<Box<dyn UndoOperation>>,
 }
 
+/// Screen contents swapped out by switching between the main and the alternate screen.
+#[derive(Debug, Clone)]
+struct ScreenPlane {
+    layer_index: usize,
+    layer: Layer,
+    height: i32,
+}
+
 impl std::fmt::Debug for Buffer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Buffer")
@@ -356,6 +369,7 @@ impl Buffer {
         let mut font_table = HashMap::new();
         font_table.insert(0, BitFont::default());
         let size = size.into();
+        crate::grapheme::register_buffer();
         Buffer {
             file_name: None,
             size,
@@ -368,6 +382,9 @@ impl Buffer {
             font_mode: FontMode::Sauce,
 
             is_terminal_buffer: false,
+            scrollback: Scrollback::default(),
+            inactive_screen: None,
+            alternate_screen_active: false,
             palette: Palette::dos_default(),
 
             font_table,
@@ -400,26 +417,30 @@ impl Buffer {
             let sixel = result?;
 
             updated_sixel = true;
+            self.add_sixel(0, sixel);
+        }
+        Ok(updated_sixel)
+    }
 
-            let font_dims = self.get_font_dimensions();
-            let screen_rect = sixel.get_screen_rect(font_dims);
-
-            let vec = &mut self.layers[0].sixels;
-            let mut sixel_count = vec.len();
-            // remove old sixel that are shadowed by the new one
-            let mut i = 0;
-            while i < sixel_count {
-                let old_rect = vec[i].get_screen_rect(font_dims);
-                if screen_rect.contains_rect(&old_rect) {
-                    vec.remove(i);
-                    sixel_count -= 1;
-                } else {
-                    i += 1;
-                }
+    /// Adds a picture to a layer and removes the old pictures that are shadowed by it.
+    pub fn add_sixel(&mut self, layer: usize, sixel: Sixel) {
+        let font_dims = self.get_font_dimensions();
+        let screen_rect = sixel.get_screen_rect(font_dims);
+
+        let vec = &mut self.layers[layer].sixels;
+        let mut sixel_count = vec.len();
+        // remove old sixel that are shadowed by the new one
+        let mut i = 0;
+        while i < sixel_count {
+            let old_rect = vec[i].get_screen_rect(font_dims);
+            if screen_rect.contains_rect(&old_rect) {
+                vec.remove(i);
+                sixel_count -= 1;
+            } else {
+                i += 1;
             }
-            vec.push(sixel);
         }
-        Ok(updated_sixel)
+        vec.push(sixel);
     }
 
     pub fn clear_font_table(&mut self) {
@@ -506,6 +527,8 @@ impl Buffer {
     }
 
     pub fn reset_terminal(&mut self) {
+        self.restore_main_screen(false);
+        self.inactive_screen = None;
         self.terminal_state = TerminalState::from(self.terminal_state.get_size());
     }
 
@@ -545,6 +568,78 @@ impl Buffer {
         self.sixel_threads.clear();
     }
 
+    pub fn is_alternate_screen_active(&self) -> bool {
+        self.alternate_screen_active
+    }
+
+    /// Switches to the alternate screen (DECSET 47/1047/1049).
+    /// The alternate screen has the size of the terminal, doesn't grow and has no scrollback.
+    /// Its contents are kept when switching back, unless `clear` is set.
+    /// The caret keeps its screen position.
+    pub fn enter_alternate_screen(&mut self, layer: usize, caret: &mut Caret, clear: bool) {
+        if self.alternate_screen_active || layer >= self.layers.len() {
+            return;
+        }
+        let size = self.terminal_state.get_size();
+        caret.pos.y = (caret.pos.y - self.get_first_visible_line()).clamp(0, (size.height - 1).max(0));
+        let mut alternate = match self.inactive_screen.take() {
+            Some(plane) if plane.layer_index == layer => plane.layer,
+            _ => Layer::new(self.layers[layer].get_title().to_string(), size),
+        };
+        if clear {
+            alternate.clear();
+        }
+        let main = std::mem::replace(&mut self.layers[layer], alternate);
+        self.inactive_screen = Some(ScreenPlane {
+            layer_index: layer,
+            layer: main,
+            height: self.get_height(),
+        });
+        self.set_height(size.height);
+        self.alternate_screen_active = true;
+    }
+
+    /// Switches back to the main screen (DECRST 47/1047/1049), `clear` erases the alternate screen before leaving.
+    /// The caret keeps its screen position.
+    pub fn leave_alternate_screen(&mut self, caret: &mut Caret, clear: bool) {
+        if !self.alternate_screen_active {
+            return;
+        }
+        self.restore_main_screen(clear);
+        caret.pos.y = (caret.pos.y + self.get_first_visible_line()).min((self.get_height() - 1).max(0));
+    }
+
+    fn restore_main_screen(&mut self, clear: bool) {
+        if !self.alternate_screen_active {
+            return;
+        }
+        self.alternate_screen_active = false;
+        let Some(main) = self.inactive_screen.take() else {
+            return;
+        };
+        let mut alternate = std::mem::replace(&mut self.layers[main.layer_index], main.layer);
+        if clear {
+            alternate.clear();
+        }
+        self.inactive_screen = Some(ScreenPlane {
+            layer_index: main.layer_index,
+            layer: alternate,
+            height: self.terminal_state.get_height(),
+        });
+        self.set_height(main.height);
+    }
+
+    /// Gives back the line attribute of a terminal line.
+    pub fn get_line_size(&self, line: i32) -> LineSize {
+        if line < 0 {
+            return LineSize::Single;
+        }
+        self.layers
+            .first()
+            .and_then(|layer| layer.lines.get(line as usize))
+            .map_or(LineSize::Single, |line| line.line_size)
+    }
+
     /// terminal buffers have a viewport on the bottom of the buffer
     /// this function gives back the first visible line.
     #[must_use]
@@ -742,79 +837,18 @@ impl Buffer {
         y as f64 * font_dimensions.height as f64
     }
 
-    /// .
-    ///
-    /// # Panics
-    ///
-    /// Panics if .
     pub fn render_to_rgba(&self, rect: Rectangle) -> (Size, Vec<u8>) {
-        let font_size = self.get_font(0).unwrap().size;
-
-        let px_width = rect.get_width() * font_size.width;
-        let px_height = rect.get_height() * font_size.height;
-        let line_bytes = px_width * 4;
-        let mut pixels = vec![0; (line_bytes * px_height) as usize];
-
-        for y in 0..rect.get_height() {
-            for x in 0..rect.get_width() {
-                let ch = self.get_char((x + rect.start.x, y + rect.start.y));
-                let font = self.get_font(ch.get_font_page()).unwrap();
-
-                let fg = if ch.attribute.is_bold() && ch.attribute.get_foreground() < 8 {
-                    ch.attribute.get_foreground() + 8
-                } else {
-                    ch.attribute.get_foreground()
-                };
-
-                let (f_r, f_g, f_b) = self.palette.get_rgb(fg);
-                let (b_r, b_g, b_b) = self.palette.get_rgb(ch.attribute.get_background());
-
-                if let Some(glyph) = font.get_glyph(ch.ch) {
-                    let cur_font_size = font.size;
-                    for cy in 0..cur_font_size.height.min(font_size.height) {
-                        for cx in 0..cur_font_size.width.min(font_size.width) {
-                            let offset = ((x * font_size.width + cx) * 4 + (y * font_size.height + cy) * line_bytes) as usize;
-                            if glyph.data[cy as usize] & (128 >> cx) == 0 {
-                                pixels[offset] = b_r;
-                                pixels[offset + 1] = b_g;
-                                pixels[offset + 2] = b_b;
-                            } else {
-                                pixels[offset] = f_r;
-                                pixels[offset + 1] = f_g;
-                                pixels[offset + 2] = f_b;
-                            }
-                            pixels[offset + 3] = 0xFF;
-                        }
-                    }
-                }
-            }
-        }
+        self.render_with_options(rect, &crate::RenderOptions::default())
+    }
 
-        for layer in &self.layers {
-            for sixel in &layer.sixels {
-                let sx = layer.get_offset().x + sixel.position.x - rect.start.x;
-                let sx_px = sx * font_size.width;
-                let sy = layer.get_offset().y + sixel.position.y - rect.start.y;
-                let sy_pix = sy * font_size.height;
-                let sixel_line_bytes = (sixel.get_width() * 4) as usize;
-
-                let mut sixel_line = 0;
-                for y in sy_pix..(sy_pix + sixel.get_height()) {
-                    if y < 0 {
-                        continue;
-                    }
-                    let y = y as usize;
-                    let offset = y * line_bytes as usize + sx_px as usize * 4;
-                    let o = sixel_line * sixel_line_bytes;
-                    if offset + sixel_line_bytes > pixels.len() {
-                        break;
-                    }
-                    pixels[offset..(offset + sixel_line_bytes)].copy_from_slice(&sixel.picture_data[o..(o + sixel_line_bytes)]);
-                    sixel_line += 1;
-                }
-            }
-        }
-        (Size::new(px_width, px_height), pixels)
+    /// Renders the rectangle with blinking chars either shown or hidden.
+    /// Chars only blink if the ice mode allows it.
+    pub fn render_to_rgba_blink_phase(&self, rect: Rectangle, show_blinking: bool) -> (Size, Vec<u8>) {
+        let options = crate::RenderOptions {
+            show_blinking,
+            ..Default::default()
+        };
+        self.render_with_options(rect, &options)
     }
 
     pub fn use_letter_spacing(&self) -> bool {
@@ -863,6 +897,12 @@ impl Buffer {
     }
 }
 
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        crate::grapheme::release_buffer();
+    }
+}
+
 impl Default for Buffer {
     fn default() -> Self {
         Buffer::new((80, 25))
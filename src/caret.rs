@@ -1,5 +1,13 @@
 use super::{Position, TextAttribute};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaretShape {
+    #[default]
+    Block,
+    Underline,
+    Bar,
+}
+
 #[derive(Clone)]
 pub struct Caret {
     pub(super) pos: Position,
@@ -7,6 +15,7 @@ pub struct Caret {
     pub insert_mode: bool,
     is_visible: bool,
     pub is_blinking: bool,
+    pub shape: CaretShape,
     ice_mode: bool,
 }
 
@@ -73,6 +82,7 @@ impl Caret {
         self.insert_mode = false;
         self.is_visible = true;
         self.is_blinking = true;
+        self.shape = CaretShape::Block;
         self.ice_mode = false;
     }
 
@@ -84,10 +94,13 @@ impl Caret {
         self.attribute.set_font_page(page);
     }
 
+    /// Resets the attribute to the default colors, the font page and the DECSCA protection aren't affected.
     pub fn reset_color_attribute(&mut self) {
         let font_page = self.attribute.get_font_page();
+        let is_protected = self.attribute.is_protected();
         self.attribute = TextAttribute::default();
         self.attribute.set_font_page(font_page);
+        self.attribute.set_is_protected(is_protected);
     }
 
     pub fn ice_mode(&self) -> bool {
@@ -124,6 +137,7 @@ impl Default for Caret {
             insert_mode: false,
             is_visible: true,
             is_blinking: true,
+            shape: CaretShape::Block,
             ice_mode: false,
         }
     }
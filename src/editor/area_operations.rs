@@ -407,6 +407,10 @@ impl EditState {
 
 fn generate_flipy_table(font: &crate::BitFont) -> HashMap<char, char> {
     let mut flip_table = HashMap::new();
+    // the glyph variants are generated with one byte per row
+    if font.size.width > 8 {
+        return flip_table;
+    }
 
     for (ch, cur_glyph) in &font.glyphs {
         let flipped_glyhps = generate_flipy_variants(cur_glyph);
@@ -456,6 +460,10 @@ fn generate_flipx_table(font: &crate::BitFont) -> HashMap<char, char> {
 
     flip_table.insert('\\', '/');
     flip_table.insert('/', '\\');
+    // the glyph variants are generated with one byte per row
+    if font.size.width > 8 {
+        return flip_table;
+    }
 
     for (ch, cur_glyph) in &font.glyphs {
         let flipped_glyhps = generate_flipx_variants(cur_glyph, font.size.width);
@@ -641,6 +649,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_flip_tables_wide_font() {
+        let mut font = BitFont::default();
+        font.size = Size::new(10, 16);
+        for glyph in font.glyphs.values_mut() {
+            glyph.data = [0xF0, 0x00].repeat(16);
+        }
+        assert_eq!(HashMap::from([('\\', '/'), ('/', '\\')]), generate_flipx_table(&font));
+        assert!(generate_flipy_table(&font).is_empty());
+    }
+
     #[test]
     fn test_generate_flipy_table() {
         let table = generate_flipy_table(&BitFont::default());
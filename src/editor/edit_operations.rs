@@ -317,7 +317,7 @@ impl EditState {
 mod tests {
     use crate::{
         editor::{EditState, UndoState},
-        Size, TextPane,
+        AttributedChar, Rectangle, Size, TextAttribute, TextPane,
     };
 
     #[test]
@@ -331,4 +331,34 @@ mod tests {
         state.redo().unwrap();
         assert_eq!(Size::new(10, 10), state.buffer.get_size());
     }
+
+    #[test]
+    fn test_copy_wide_chars() {
+        let mut state = EditState::default();
+        state.get_buffer_mut().buffer_type = crate::BufferType::Unicode;
+        let layer = &mut state.get_buffer_mut().layers[0];
+        layer.set_char((0, 0), AttributedChar::new('中', TextAttribute::default()));
+        layer.set_char((1, 0), AttributedChar::wide_continuation(TextAttribute::default()));
+        layer.set_char((2, 0), AttributedChar::new('b', TextAttribute::default()));
+
+        state.set_selection(Rectangle::from(0, 0, 3, 0)).unwrap();
+        assert_eq!(Some("中b\n".to_string()), state.get_copy_text());
+
+        // starting the selection on the right half includes the whole character
+        state.set_selection(Rectangle::from(1, 0, 2, 0)).unwrap();
+        assert_eq!(Some("中b\n".to_string()), state.get_copy_text());
+    }
+
+    #[test]
+    fn test_copy_grapheme_cluster() {
+        let mut state = EditState::default();
+        state.get_buffer_mut().buffer_type = crate::BufferType::Unicode;
+        let cluster = crate::intern_grapheme_cluster("e\u{301}").unwrap();
+        let layer = &mut state.get_buffer_mut().layers[0];
+        layer.set_char((0, 0), AttributedChar::new(cluster, TextAttribute::default()));
+        layer.set_char((1, 0), AttributedChar::new('b', TextAttribute::default()));
+
+        state.set_selection(Rectangle::from(0, 0, 2, 0)).unwrap();
+        assert_eq!(Some("e\u{301}b\n".to_string()), state.get_copy_text());
+    }
 }
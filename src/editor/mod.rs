@@ -204,14 +204,34 @@ impl EditState {
             return None;
         };
 
+        let push_char = |res: &mut String, pos: Position| {
+            let ch = self.buffer.get_char(pos);
+            // the right half of a double width character is part of the glyph left of it
+            if ch.is_wide_continuation() {
+                return;
+            }
+            if let Some(cluster) = crate::get_grapheme_cluster(ch.ch) {
+                res.push_str(&cluster);
+            } else {
+                res.push(self.unicode_converter.convert_to_unicode(ch));
+            }
+        };
+        // a selection starting on the right half of a double width character includes the whole character
+        let first_column = |pos: Position| {
+            if self.buffer.get_char(pos).is_wide_continuation() {
+                pos.x - 1
+            } else {
+                pos.x
+            }
+        };
+
         let mut res = String::new();
         if matches!(selection.shape, Shape::Rectangle) {
             let start = selection.min();
             let end = selection.max();
             for y in start.y..=end.y {
-                for x in start.x..end.x {
-                    let ch = self.buffer.get_char((x, y));
-                    res.push(self.unicode_converter.convert_to_unicode(ch));
+                for x in first_column(Position::new(start.x, y))..end.x {
+                    push_char(&mut res, Position::new(x, y));
                 }
                 res.push('\n');
             }
@@ -222,26 +242,22 @@ impl EditState {
                 (selection.lead, selection.anchor)
             };
             if start.y == end.y {
-                for x in start.x..end.x {
-                    let ch = self.buffer.get_char(Position::new(x, start.y));
-                    res.push(self.unicode_converter.convert_to_unicode(ch));
+                for x in first_column(start)..end.x {
+                    push_char(&mut res, Position::new(x, start.y));
                 }
             } else {
-                for x in start.x..(self.buffer.get_line_length(start.y)) {
-                    let ch = self.buffer.get_char(Position::new(x, start.y));
-                    res.push(self.unicode_converter.convert_to_unicode(ch));
+                for x in first_column(start)..(self.buffer.get_line_length(start.y)) {
+                    push_char(&mut res, Position::new(x, start.y));
                 }
                 res.push('\n');
                 for y in start.y + 1..end.y {
                     for x in 0..(self.buffer.get_line_length(y)) {
-                        let ch = self.buffer.get_char(Position::new(x, y));
-                        res.push(self.unicode_converter.convert_to_unicode(ch));
+                        push_char(&mut res, Position::new(x, y));
                     }
                     res.push('\n');
                 }
                 for x in 0..end.x {
-                    let ch = self.buffer.get_char(Position::new(x, end.y));
-                    res.push(self.unicode_converter.convert_to_unicode(ch));
+                    push_char(&mut res, Position::new(x, end.y));
                 }
             }
         }
@@ -259,12 +275,13 @@ impl EditState {
         let selection = self.get_selected_rectangle();
 
         let mut data = Vec::new();
-        data.push(0);
+        data.push(2);
         data.extend(i32::to_le_bytes(selection.start.x));
         data.extend(i32::to_le_bytes(selection.start.y));
 
         data.extend(u32::to_le_bytes(selection.get_size().width as u32));
         data.extend(u32::to_le_bytes(selection.get_size().height as u32));
+        let mut clusters = Vec::new();
         for y in selection.y_range() {
             for x in selection.x_range() {
                 let pos = Position::new(x, y);
@@ -273,13 +290,26 @@ impl EditState {
                 } else {
                     AttributedChar::invisible()
                 };
-                data.extend(u16::to_le_bytes(ch.ch as u16));
+                if let Some(cluster) = crate::get_grapheme_cluster(ch.ch) {
+                    if !clusters.iter().any(|(cluster_ch, _)| *cluster_ch == ch.ch) {
+                        clusters.push((ch.ch, cluster));
+                    }
+                }
+                data.extend(u32::to_le_bytes(ch.ch as u32));
                 data.extend(u16::to_le_bytes(ch.attribute.attr));
                 data.extend(u16::to_le_bytes(ch.attribute.font_page as u16));
                 data.extend(u32::to_le_bytes(ch.attribute.background_color));
                 data.extend(u32::to_le_bytes(ch.attribute.foreground_color));
+                data.extend(u32::to_le_bytes(ch.attribute.get_underline_color().unwrap_or(crate::layer::NO_UNDERLINE_COLOR)));
             }
         }
+        // grapheme cluster ids are only valid inside this process, the cluster texts are stored after the chars
+        data.extend(u32::to_le_bytes(clusters.len() as u32));
+        for (ch, cluster) in clusters {
+            data.extend(u32::to_le_bytes(ch as u32));
+            data.extend(u32::to_le_bytes(cluster.len() as u32));
+            data.extend(cluster.as_bytes());
+        }
         Some(data)
     }
 
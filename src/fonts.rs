@@ -126,7 +126,7 @@ impl BitFont {
                 result.extend_from_slice(&glyph.data);
             } else {
                 log::error!("Glyph not found for char: {}", ch);
-                result.extend_from_slice(vec![0; self.size.height as usize].as_slice());
+                result.extend_from_slice(vec![0; (self.size.height * ((self.size.width + 7) / 8)) as usize].as_slice());
             }
         }
         result
@@ -241,7 +241,8 @@ impl BitFont {
             size: (width, height).into(),
             length,
             font_type: BitFontType::BuiltIn,
-            glyphs: glyphs_from_u8_data(height, &data[headersize..]),
+            // fonts wider than 8 pixels use more than one byte per glyph row
+            glyphs: glyphs_from_u8_data(charsize as usize, &data[headersize..]),
             checksum: 0,
         };
         r.calculate_checksum();
@@ -259,19 +260,25 @@ impl BitFont {
     /// This function will return an error if .
     pub fn to_psf2_bytes(&self) -> EngineResult<Vec<u8>> {
         let mut data = Vec::new();
+        let charsize = self.size.height * ((self.size.width + 7) / 8);
         // Write PSF2 header.
         data.extend(u32::to_le_bytes(BitFont::PSF2_MAGIC)); // magic
         data.extend(u32::to_le_bytes(0)); // version
         data.extend(u32::to_le_bytes(8 * 4)); // headersize
         data.extend(u32::to_le_bytes(0)); // flags
         data.extend(u32::to_le_bytes(self.length as u32)); // length
-        data.extend(u32::to_le_bytes(self.size.height as u32)); // charsize
+        data.extend(u32::to_le_bytes(charsize as u32)); // charsize
         data.extend(u32::to_le_bytes(self.size.height as u32)); // height
         data.extend(u32::to_le_bytes(self.size.width as u32)); // width
 
         // glyphs
         for i in 0..self.length {
-            data.extend(&self.get_glyph(unsafe { char::from_u32_unchecked(i as u32) }).unwrap().data);
+            let mut glyph_data = self
+                .get_glyph(unsafe { char::from_u32_unchecked(i as u32) })
+                .map(|glyph| glyph.data.clone())
+                .unwrap_or_default();
+            glyph_data.resize(charsize as usize, 0);
+            data.extend(glyph_data);
         }
 
         Ok(data)
@@ -500,6 +507,21 @@ macro_rules! sauce_fonts {
                     _ => Err(ParserError::UnsupportedSauceFont(sauce_name.to_string()).into()),
                 }
             }
+
+            /// Vertical stretch of a sauce font shown on a 4:3 display. The first table value is for
+            /// 9 pixel columns (letter spacing), the second one for 8 pixel columns - fonts without a
+            /// second value use the first one for both. Returns `None` for fonts without a sauce entry.
+            pub fn get_sauce_aspect_ratio_stretch(sauce_name: &str, use_letter_spacing: bool) -> Option<f32> {
+                let (stretch, stretch_lga): (f32, f32) = match sauce_name {
+                    $(
+                        $name => ($stretch as f32, $stretch_lga as f32),
+                    )*
+                    _ => return None,
+                };
+                let stretch = if use_letter_spacing || stretch_lga <= 0.0 { stretch } else { stretch_lga };
+                // square pixel fonts like the 640x480 VGA25G aren't stretched
+                Some(if stretch > 0.0 { stretch } else { 1.0 })
+            }
         }
 
         pub const SAUCE_FONT_NAMES: &[&str] = &[
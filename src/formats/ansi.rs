@@ -4,10 +4,10 @@ use std::path::Path;
 use crate::ansi::constants::COLOR_OFFSETS;
 use crate::ascii::CP437_TO_UNICODE;
 use crate::{
-    analyze_font_usage, parse_with_parser, parsers, BitFont, Buffer, BufferFeatures, OutputFormat, Rectangle, TextPane, ANSI_FONTS, DOS_DEFAULT_PALETTE,
-    XTERM_256_PALETTE,
+    analyze_font_usage, parse_with_parser, parsers, BitFont, Buffer, BufferFeatures, BufferType, OutputFormat, Rectangle, TextPane, ANSI_FONTS,
+    DOS_DEFAULT_PALETTE, XTERM_256_PALETTE,
 };
-use crate::{Color, TextAttribute};
+use crate::{Color, TextAttribute, UnderlineStyle};
 
 use super::SaveOptions;
 
@@ -67,6 +67,8 @@ struct CharCell {
     ch: char,
     sgr: Vec<u8>,
     sgr_tc: Vec<u8>,
+    /// SGR parameters with colon separated sub parameters (`4:3`), written as their own sequence.
+    sgr_sub: Vec<String>,
     font_page: usize,
     cur_state: AnsiState,
 }
@@ -89,6 +91,8 @@ struct AnsiState {
     pub is_italic: bool,
     pub is_underlined: bool,
     pub is_double_underlined: bool,
+    pub underline_style: UnderlineStyle,
+    pub underline_color: Option<(u8, u8, u8)>,
     pub is_crossed_out: bool,
     pub is_concealed: bool,
 
@@ -126,9 +130,10 @@ impl StringGenerator {
         }
     }
 
-    fn get_color(&self, buf: &Buffer, attr: TextAttribute, mut state: AnsiState) -> (AnsiState, Vec<u8>, Vec<u8>) {
+    fn get_color(&self, buf: &Buffer, attr: TextAttribute, mut state: AnsiState) -> (AnsiState, Vec<u8>, Vec<u8>, Vec<String>) {
         let mut sgr = Vec::new();
         let mut sgr_tc = Vec::new();
+        let mut sgr_sub = Vec::new();
 
         let fg = attr.get_foreground();
         let cur_fore_color = buf.palette.get_color(fg);
@@ -147,6 +152,13 @@ impl StringGenerator {
         let is_italic = attr.is_italic();
         let is_underlined = attr.is_underlined();
         let is_double_underlined = attr.is_double_underlined();
+        let underline_style = attr.get_underline_style();
+        // underline styles & colors are only understood by modern terminals
+        let underline_color = if self.options.modern_terminal_output {
+            attr.get_underline_color().map(|color| buf.palette.get_color(color).get_rgb())
+        } else {
+            None
+        };
         let is_crossed_out = attr.is_crossed_out();
         let is_concealed = attr.is_concealed();
 
@@ -205,6 +217,8 @@ impl StringGenerator {
             state.is_faint = false;
             state.is_underlined = false;
             state.is_double_underlined = false;
+            state.underline_style = UnderlineStyle::None;
+            state.underline_color = None;
             state.is_crossed_out = false;
 
             state.fg_idx = 7;
@@ -230,9 +244,14 @@ impl StringGenerator {
             sgr.push(3);
             state.is_italic = true;
         }
-        if is_underlined && !state.is_underlined {
-            sgr.push(4);
+        if is_underlined && (!state.is_underlined || state.underline_style != underline_style) {
+            if self.options.modern_terminal_output && underline_style != UnderlineStyle::Single {
+                sgr_sub.push(format!("4:{}", underline_style.to_sgr()));
+            } else {
+                sgr.push(4);
+            }
             state.is_underlined = true;
+            state.underline_style = underline_style;
         }
 
         if is_blink && !state.is_blink {
@@ -253,6 +272,16 @@ impl StringGenerator {
         if is_double_underlined && !state.is_double_underlined {
             sgr.push(21);
             state.is_double_underlined = true;
+            state.underline_style = underline_style;
+        }
+
+        if underline_color != state.underline_color {
+            if let Some((r, g, b)) = underline_color {
+                sgr.extend([58, 2, r, g, b]);
+            } else {
+                sgr.push(59);
+            }
+            state.underline_color = underline_color;
         }
 
         if cur_fore_rgb != state.fg.get_rgb() {
@@ -288,7 +317,7 @@ impl StringGenerator {
             }
             state.bg = cur_back_color;
         }
-        (state, sgr, sgr_tc)
+        (state, sgr, sgr_tc, sgr_sub)
     }
 
     fn generate_cells<T: TextPane>(&self, buf: &Buffer, layer: &T, area: Rectangle, font_map: &HashMap<usize, usize>) -> Vec<Vec<CharCell>> {
@@ -300,6 +329,8 @@ impl StringGenerator {
             is_faint: false,
             is_underlined: false,
             is_double_underlined: false,
+            underline_style: UnderlineStyle::None,
+            underline_color: None,
             is_crossed_out: false,
             is_concealed: false,
             fg_idx: 7,
@@ -348,13 +379,19 @@ impl StringGenerator {
             let mut x = 0;
             while x < len {
                 let ch = layer.get_char((x, y));
+                if ch.is_wide_continuation() {
+                    // the double width glyph left of it already covers this cell
+                    x += 1;
+                    continue;
+                }
                 if ch.is_visible() {
-                    let (new_state, sgr, sgr_tc) = self.get_color(buf, ch.attribute, state);
+                    let (new_state, sgr, sgr_tc, sgr_sub) = self.get_color(buf, ch.attribute, state);
                     state = new_state;
                     line.push(CharCell {
                         ch: ch.ch,
                         sgr,
                         sgr_tc,
+                        sgr_sub,
                         font_page: *font_map.get(&ch.get_font_page()).unwrap(),
                         cur_state: state.clone(),
                     });
@@ -363,6 +400,7 @@ impl StringGenerator {
                         ch: ' ',
                         sgr: Vec::new(),
                         sgr_tc: Vec::new(),
+                        sgr_sub: Vec::new(),
                         font_page: *font_map.get(&ch.get_font_page()).unwrap(),
                         cur_state: state.clone(),
                     });
@@ -484,6 +522,10 @@ impl StringGenerator {
                     result.push(b'm');
                     self.push_result(&mut result);
                 }
+                if !cell.sgr_sub.is_empty() {
+                    result.extend_from_slice(format!("\x1b[{}m", cell.sgr_sub.join(";")).as_bytes());
+                    self.push_result(&mut result);
+                }
                 let mut idx = 0;
                 while idx < cell.sgr_tc.len() {
                     result.extend_from_slice(b"\x1b[");
@@ -500,8 +542,10 @@ impl StringGenerator {
                 let cell_char = if self.options.modern_terminal_output {
                     if cell.ch == '\0' {
                         vec![b' ']
+                    } else if buf.buffer_type == BufferType::Unicode {
+                        crate::get_grapheme_cluster(cell.ch).unwrap_or_else(|| cell.ch.to_string()).into_bytes()
                     } else {
-                        let uni_ch = CP437_TO_UNICODE[cell.ch as usize].to_string();
+                        let uni_ch = CP437_TO_UNICODE.get(cell.ch as usize).unwrap_or(&cell.ch).to_string();
                         uni_ch.as_bytes().to_vec()
                     }
                 } else if StringGenerator::CONTROL_CHARS.contains(cell.ch) {
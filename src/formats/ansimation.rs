@@ -0,0 +1,244 @@
+use std::{collections::VecDeque, path::Path, time::Duration};
+
+use regex::Regex;
+
+use crate::{ansi::BaudEmulation, parsers, Buffer, BufferParser, CallbackAction, Caret};
+
+/// A screen snapshot taken during playback, it has the size of the terminal screen.
+pub struct AnimationFrame {
+    pub buffer: Buffer,
+    /// Time since the start of the playback at the emulated baud rate.
+    pub timestamp: Duration,
+}
+
+/// Plays back ANSI animations by feeding the parser incrementally at an emulated baud rate.
+///
+/// A frame is taken before the screen gets cleared or the cursor is homed and whenever a time slice passed.
+/// Without baud emulation the time doesn't advance and frames are only taken on clear screen/home.
+pub struct AnsimationPlayer {
+    parser: parsers::ansi::Parser,
+    buffer: Buffer,
+    caret: Caret,
+    text: String,
+    pos: usize,
+    /// Byte offsets in `text` where a clear screen or cursor home sequence starts.
+    events: VecDeque<usize>,
+
+    baud_emulation: BaudEmulation,
+    frame_interval: Option<Duration>,
+    /// Playback time at the last baud rate change or pause.
+    time_base: Duration,
+    chars_at_rate: u64,
+    time: Duration,
+    last_frame_time: Duration,
+    is_dirty: bool,
+}
+
+lazy_static::lazy_static! {
+    static ref CLEAR_OR_HOME: Regex = Regex::new(r"\x1B\[2J|\x1B\[(?:[01]?(?:;[01]?)?)?[Hf]|\x0C").unwrap();
+}
+
+impl AnsimationPlayer {
+    pub fn new(file_name: &Path, data: &[u8], sauce_opt: Option<crate::SauceData>, baud_emulation: BaudEmulation) -> Self {
+        let mut buffer = Buffer::new((80, 25));
+        buffer.is_terminal_buffer = true;
+        buffer.file_name = Some(file_name.into());
+        buffer.set_sauce(sauce_opt, true);
+        buffer.terminal_state.set_baud_rate(baud_emulation);
+
+        let mut parser = parsers::ansi::Parser::default();
+        parser.bs_is_ctrl_char = false;
+        let (text, is_unicode) = crate::convert_ansi_to_utf8(data);
+        if is_unicode {
+            buffer.buffer_type = crate::BufferType::Unicode;
+        }
+        let mut caret = Caret::default();
+        if let Some(sauce) = &buffer.get_sauce() {
+            caret.set_ice_mode(sauce.use_ice);
+        }
+        let events = CLEAR_OR_HOME.find_iter(&text).map(|m| m.start()).collect();
+
+        Self {
+            parser,
+            buffer,
+            caret,
+            text,
+            pos: 0,
+            events,
+            baud_emulation,
+            frame_interval: Some(Duration::from_millis(100)),
+            time_base: Duration::ZERO,
+            chars_at_rate: 0,
+            time: Duration::ZERO,
+            last_frame_time: Duration::ZERO,
+            is_dirty: false,
+        }
+    }
+
+    /// Sets the time slice after which a frame is taken, `None` only takes frames on clear screen/home.
+    pub fn set_frame_interval(&mut self, frame_interval: Option<Duration>) {
+        self.frame_interval = frame_interval;
+    }
+
+    pub fn get_frame_interval(&self) -> Option<Duration> {
+        self.frame_interval
+    }
+
+    /// The current state of the screen.
+    pub fn get_buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    /// The playback time of the data fed so far.
+    pub fn get_time(&self) -> Duration {
+        self.time
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pos >= self.text.len()
+    }
+
+    /// Feeds the parser until the next frame is due. Gives back `None` when the playback is finished.
+    pub fn next_frame(&mut self) -> Option<AnimationFrame> {
+        while let Some(ch) = self.text[self.pos..].chars().next() {
+            if self.events.front() == Some(&self.pos) {
+                self.events.pop_front();
+                if self.is_dirty {
+                    return Some(self.take_frame());
+                }
+            }
+            self.pos += ch.len_utf8();
+            self.feed(ch);
+            if let Some(interval) = self.frame_interval {
+                if self.is_dirty && self.time.saturating_sub(self.last_frame_time) >= interval {
+                    return Some(self.take_frame());
+                }
+            }
+        }
+        if self.is_dirty {
+            return Some(self.take_frame());
+        }
+        None
+    }
+
+    fn feed(&mut self, ch: char) {
+        self.is_dirty = true;
+        let baud_rate = self.baud_emulation.get_baud_rate();
+        if baud_rate > 0 {
+            // 8N1 - 10 bits per char, calculated from the last rate change to avoid rounding errors
+            self.chars_at_rate += 1;
+            self.time = self.time_base + Duration::from_nanos(self.chars_at_rate * 10_000_000_000 / u64::from(baud_rate));
+        }
+        match self.parser.print_char(&mut self.buffer, 0, &mut self.caret, ch) {
+            Ok(CallbackAction::ChangeBaudEmulation(baud_emulation)) => {
+                self.baud_emulation = baud_emulation;
+                self.time_base = self.time;
+                self.chars_at_rate = 0;
+            }
+            Ok(CallbackAction::Pause(ms)) => {
+                self.time += Duration::from_millis(u64::from(ms));
+                self.time_base = self.time;
+                self.chars_at_rate = 0;
+            }
+            Ok(_) => {}
+            Err(err) => log::error!("Error during ansimation playback: {err}"),
+        }
+    }
+
+    fn take_frame(&mut self) -> AnimationFrame {
+        self.is_dirty = false;
+        self.last_frame_time = self.time;
+        AnimationFrame {
+            buffer: self.take_screen(),
+            timestamp: self.time,
+        }
+    }
+
+    /// Copies the visible screen - the terminal buffer grows when the output scrolls.
+    fn take_screen(&self) -> Buffer {
+        let mut screen = self.buffer.flat_clone(true);
+        let first = self.buffer.get_first_visible_line();
+        let size = self.buffer.terminal_state.get_size();
+        for layer in &mut screen.layers {
+            layer.lines.drain(..(first as usize).min(layer.lines.len()));
+            layer.lines.truncate(size.height as usize);
+            layer.sixels.retain_mut(|sixel| {
+                sixel.position.y -= first;
+                sixel.position.y >= 0
+            });
+            layer.hyperlinks.retain_mut(|link| {
+                link.position.y -= first;
+                link.position.y >= 0
+            });
+            layer.set_size(size);
+        }
+        screen.set_size(size);
+        screen
+    }
+}
+
+impl Iterator for AnsimationPlayer {
+    type Item = AnimationFrame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use super::AnsimationPlayer;
+    use crate::{ansi::BaudEmulation, TextPane};
+
+    #[test]
+    fn test_frame_on_home() {
+        let mut player = AnsimationPlayer::new(Path::new("test.ans"), b"\x1B[2JA\x1B[HB\x1B[1;1HC", None, BaudEmulation::Off);
+        player.set_frame_interval(None);
+        let frames: Vec<_> = player.collect();
+        assert_eq!(3, frames.len());
+        assert_eq!('A', frames[0].buffer.get_char((0, 0)).ch);
+        assert_eq!('B', frames[1].buffer.get_char((0, 0)).ch);
+        assert_eq!('C', frames[2].buffer.get_char((0, 0)).ch);
+        assert_eq!(Duration::ZERO, frames[2].timestamp);
+    }
+
+    #[test]
+    fn test_frame_on_clear_screen() {
+        let frames: Vec<_> = AnsimationPlayer::new(Path::new("test.ans"), b"AB\x1B[2JCD", None, BaudEmulation::Off).collect();
+        assert_eq!(2, frames.len());
+        assert_eq!('A', frames[0].buffer.get_char((0, 0)).ch);
+        assert_eq!('C', frames[1].buffer.get_char((0, 0)).ch);
+        assert_eq!(' ', frames[1].buffer.get_char((2, 0)).ch);
+    }
+
+    #[test]
+    fn test_baud_timing() {
+        // 300 baud = 30 chars per second
+        let data = vec![b'A'; 30];
+        let mut player = AnsimationPlayer::new(Path::new("test.ans"), &data, None, BaudEmulation::Rate(300));
+        player.set_frame_interval(Some(Duration::from_millis(500)));
+        let frames: Vec<_> = player.collect();
+        assert_eq!(2, frames.len());
+        assert_eq!(Duration::from_millis(500), frames[0].timestamp);
+        assert_eq!(Duration::from_secs(1), frames[1].timestamp);
+        assert_eq!('A', frames[0].buffer.get_char((14, 0)).ch);
+        assert_eq!(' ', frames[0].buffer.get_char((15, 0)).ch);
+    }
+
+    #[test]
+    fn test_scrolling_frames() {
+        let data: Vec<u8> = (0..30).flat_map(|i| [b'0' + i % 10, b'\r', b'\n']).collect();
+        let mut player = AnsimationPlayer::new(Path::new("test.ans"), &data, None, BaudEmulation::Rate(9600));
+        player.set_frame_interval(Some(Duration::from_millis(10)));
+        let frames: Vec<_> = player.collect();
+        assert!(frames.len() > 1);
+        assert!(frames.iter().all(|frame| frame.buffer.get_size() == crate::Size::new(80, 25)));
+        // the last frame shows lines 6 to 29 and the empty line after them
+        let last = &frames.last().unwrap().buffer;
+        assert_eq!('6', last.get_char((0, 0)).ch);
+        assert_eq!('9', last.get_char((0, 23)).ch);
+        assert_eq!(' ', last.get_char((0, 24)).ch);
+    }
+}
@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use crate::{AnimationFrame, Buffer, EngineResult, IceMode, Rectangle, Size, TextPane};
+
+/// A rendered RGBA image that is shown for `delay`.
+pub struct RenderedFrame {
+    pub size: Size,
+    pub pixels: Vec<u8>,
+    pub delay: Duration,
+}
+
+/// Encodes the frames as animated png. All frames need to have the same size.
+/// `num_plays` is the number of loops, 0 loops forever.
+///
+/// # Errors
+///
+/// This function will return an error if there are no frames, the frame sizes differ or the png encoding fails.
+pub fn encode_apng(frames: &[RenderedFrame], num_plays: u32) -> EngineResult<Vec<u8>> {
+    let Some(first) = frames.first() else {
+        return Err(anyhow::anyhow!("No frames to encode."));
+    };
+    let size = first.size;
+    if frames.iter().any(|frame| frame.size != size) {
+        return Err(anyhow::anyhow!("All frames of an animation need to have the same size."));
+    }
+
+    let mut result = Vec::new();
+    let mut encoder = png::Encoder::new(&mut result, size.width as u32, size.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, num_plays)?;
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        // the delay is stored as fraction, milliseconds fit for up to ~65 seconds
+        let delay = frame.delay.as_millis().min(u16::MAX as u128) as u16;
+        writer.set_frame_delay(delay, 1000)?;
+        writer.write_image_data(&frame.pixels)?;
+    }
+    writer.finish()?;
+    Ok(result)
+}
+
+impl Buffer {
+    /// Renders the blink phases of the buffer - one frame if nothing blinks, otherwise the visible and the hidden phase.
+    pub fn render_blink_frames(&self, rect: Rectangle, delay: Duration) -> Vec<RenderedFrame> {
+        let (size, pixels) = self.render_to_rgba_blink_phase(rect, true);
+        let mut result = vec![RenderedFrame { size, pixels, delay }];
+        if self.has_blinking_chars(rect) {
+            let (size, pixels) = self.render_to_rgba_blink_phase(rect, false);
+            result.push(RenderedFrame { size, pixels, delay });
+        }
+        result
+    }
+
+    fn has_blinking_chars(&self, rect: Rectangle) -> bool {
+        if self.ice_mode == IceMode::Ice {
+            return false;
+        }
+        (rect.start.y..rect.start.y + rect.get_height())
+            .any(|y| (rect.start.x..rect.start.x + rect.get_width()).any(|x| self.get_char((x, y)).attribute.is_blinking()))
+    }
+
+    /// Exports the whole buffer as animated png alternating the blink phases every `delay`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the png encoding fails.
+    pub fn to_blink_apng(&self, delay: Duration) -> EngineResult<Vec<u8>> {
+        let rect = Rectangle::from(0, 0, self.get_width(), self.get_height());
+        encode_apng(&self.render_blink_frames(rect, delay), 0)
+    }
+}
+
+/// Renders captured animation frames, each frame is shown until the timestamp of the next one.
+/// The last frame is shown for `last_frame_delay`.
+///
+/// Every frame is kept as full RGBA image, so frames that look like the previous one are merged into it
+/// and rendering stops after `max_frames` frames.
+pub fn render_animation_frames(frames: impl IntoIterator<Item = AnimationFrame>, last_frame_delay: Duration, max_frames: usize) -> Vec<RenderedFrame> {
+    let mut result: Vec<RenderedFrame> = Vec::new();
+    let mut last_timestamp = Duration::ZERO;
+    for frame in frames {
+        // the terminal screen, so all frames have the same size
+        let buf = &frame.buffer;
+        let screen = buf.terminal_state.get_size();
+        let (size, pixels) = buf.render_to_rgba(Rectangle::from(0, buf.get_first_visible_line(), screen.width, screen.height));
+        if result.last().is_some_and(|prev| prev.size == size && prev.pixels == pixels) {
+            continue;
+        }
+        if result.len() >= max_frames {
+            break;
+        }
+        if let Some(prev) = result.last_mut() {
+            prev.delay = frame.timestamp.saturating_sub(last_timestamp);
+        }
+        last_timestamp = frame.timestamp;
+        result.push(RenderedFrame {
+            size,
+            pixels,
+            delay: last_frame_delay,
+        });
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{ansi::BaudEmulation, AnsimationPlayer, AttributedChar, Buffer, IceMode, Rectangle, TextAttribute};
+
+    fn decode_frame_count(data: &[u8]) -> u32 {
+        let decoder = png::Decoder::new(data);
+        let reader = decoder.read_info().unwrap();
+        reader.info().animation_control().unwrap().num_frames
+    }
+
+    #[test]
+    fn test_blink_frames() {
+        let mut buf = Buffer::new((2, 1));
+        buf.ice_mode = IceMode::Blink;
+        let mut attr = TextAttribute::default();
+        attr.set_is_blinking(true);
+        buf.layers[0].set_char((0, 0), AttributedChar::new('A', attr));
+        let frames = buf.render_blink_frames(Rectangle::from(0, 0, 2, 1), Duration::from_millis(500));
+        assert_eq!(2, frames.len());
+        assert_ne!(frames[0].pixels, frames[1].pixels);
+        assert_eq!(2, decode_frame_count(&buf.to_blink_apng(Duration::from_millis(500)).unwrap()));
+
+        buf.ice_mode = IceMode::Ice;
+        assert_eq!(1, buf.render_blink_frames(Rectangle::from(0, 0, 2, 1), Duration::from_millis(500)).len());
+    }
+
+    #[test]
+    fn test_animation_frames() {
+        let mut player = AnsimationPlayer::new(std::path::Path::new("test.ans"), b"A\x1B[HB\x1B[HC", None, BaudEmulation::Rate(300));
+        player.set_frame_interval(None);
+        let frames = super::render_animation_frames(player, Duration::from_secs(1), 100);
+        assert_eq!(3, frames.len());
+        // 4 chars (ESC [ H B) at 300 baud
+        assert_eq!(Duration::from_nanos(133_333_334), frames[1].delay);
+        assert_eq!(Duration::from_secs(1), frames[2].delay);
+        assert_eq!(3, decode_frame_count(&super::encode_apng(&frames, 1).unwrap()));
+    }
+
+    #[test]
+    fn test_scrolling_animation_export() {
+        let data: Vec<u8> = (0..40).flat_map(|i| [b'0' + i % 10, b'\r', b'\n']).collect();
+        let mut player = AnsimationPlayer::new(std::path::Path::new("test.ans"), &data, None, BaudEmulation::Rate(9600));
+        player.set_frame_interval(Some(Duration::from_millis(10)));
+        let frames = super::render_animation_frames(player, Duration::from_secs(1), 100);
+        assert!(frames.len() > 1);
+        let apng = super::encode_apng(&frames, 1).unwrap();
+        assert_eq!(frames.len() as u32, decode_frame_count(&apng));
+    }
+
+    #[test]
+    fn test_animation_frame_limits() {
+        let player = |data: &[u8]| {
+            let mut player = AnsimationPlayer::new(std::path::Path::new("test.ans"), data, None, BaudEmulation::Rate(300));
+            player.set_frame_interval(None);
+            player
+        };
+        // the unchanged frame is shown until C is printed
+        let frames = super::render_animation_frames(player(b"A\x1B[HA\x1B[HC"), Duration::from_secs(1), 100);
+        assert_eq!(2, frames.len());
+        assert_eq!(Duration::from_nanos(266_666_667), frames[0].delay);
+        assert_eq!(Duration::from_secs(1), frames[1].delay);
+
+        let frames = super::render_animation_frames(player(b"A\x1B[HB\x1B[HC"), Duration::from_secs(1), 2);
+        assert_eq!(2, frames.len());
+        assert_eq!(Duration::from_secs(1), frames[1].delay);
+    }
+}
@@ -1,4 +1,8 @@
-use std::{error::Error, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    path::Path,
+};
 
 use base64::{engine::general_purpose, Engine};
 use regex::Regex;
@@ -308,6 +312,53 @@ impl OutputFormat for IcyDraw {
             }
         }
 
+        // underline colors don't fit into the cell data, they're stored as runs of (layer, line, column, length, color)
+        let mut underline_data = Vec::new();
+        for (i, layer) in buf.layers.iter().enumerate() {
+            for (y, line) in layer.lines.iter().enumerate() {
+                let mut x = 0;
+                while x < line.chars.len() {
+                    let Some(color) = line.chars[x].attribute.get_underline_color() else {
+                        x += 1;
+                        continue;
+                    };
+                    let start = x;
+                    while x < line.chars.len() && line.chars[x].attribute.get_underline_color() == Some(color) {
+                        x += 1;
+                    }
+                    if underline_data.len() as u64 + 20 > MAX {
+                        if let Err(err) = encoder.add_ztxt_chunk("UNDERLINE_COLORS".to_string(), general_purpose::STANDARD.encode(&underline_data)) {
+                            return Err(IcedError::ErrorEncodingZText(format!("{err}")).into());
+                        }
+                        underline_data.clear();
+                    }
+                    for value in [i, y, start, x - start] {
+                        underline_data.extend(u32::to_le_bytes(value as u32));
+                    }
+                    underline_data.extend(u32::to_le_bytes(color));
+                }
+            }
+        }
+        if !underline_data.is_empty() {
+            if let Err(err) = encoder.add_ztxt_chunk("UNDERLINE_COLORS".to_string(), general_purpose::STANDARD.encode(&underline_data)) {
+                return Err(IcedError::ErrorEncodingZText(format!("{err}")).into());
+            }
+        }
+
+        // grapheme cluster ids are only valid inside this process, the cluster texts are stored for all ids used in the layers
+        let clusters = get_grapheme_clusters(buf);
+        if !clusters.is_empty() {
+            let mut result = Vec::new();
+            result.extend(u32::to_le_bytes(clusters.len() as u32));
+            for (ch, cluster) in clusters {
+                result.extend(u32::to_le_bytes(ch as u32));
+                write_utf8_encoded_string(&mut result, &cluster);
+            }
+            if let Err(err) = encoder.add_ztxt_chunk("CLUSTERS".to_string(), general_purpose::STANDARD.encode(&result)) {
+                return Err(IcedError::ErrorEncodingZText(format!("{err}")).into());
+            }
+        }
+
         if let Err(err) = encoder.add_ztxt_chunk("END".to_string(), String::new()) {
             return Err(IcedError::ErrorEncodingZText(format!("{err}")).into());
         }
@@ -334,6 +385,8 @@ impl OutputFormat for IcyDraw {
         result.file_name = Some(file_name.into());
         result.layers.clear();
 
+        let mut clusters = HashMap::new();
+        let mut underline_colors = Vec::new();
         let mut decoder = png::StreamingDecoder::new();
         let mut len = 0;
         let mut last_info = 0;
@@ -403,12 +456,24 @@ impl OutputFormat for IcyDraw {
                                         result.set_sauce(Some(sauce), false);
                                     }
                                 }
+
+                                "UNDERLINE_COLORS" => {
+                                    underline_colors.extend(
+                                        bytes
+                                            .chunks_exact(20)
+                                            .map(|run| std::array::from_fn::<u32, 5, _>(|i| u32::from_le_bytes(run[i * 4..i * 4 + 4].try_into().unwrap()))),
+                                    );
+                                }
+
+                                "CLUSTERS" => {
+                                    read_clusters(&bytes, &mut clusters)?;
+                                }
                                 text => {
                                     if let Some(font_slot) = text.strip_prefix("FONT_") {
                                         match font_slot.parse() {
                                             Ok(font_slot) => {
                                                 let mut o: usize = 0;
-                                                let (font_name, size) = read_utf8_encoded_string(&bytes[o..]);
+                                                let (font_name, size) = read_utf8_encoded_string(&bytes[o..])?;
                                                 o += size;
                                                 let font = BitFont::from_bytes(font_name, &bytes[o..])?;
                                                 result.set_font(font_slot, font);
@@ -486,6 +551,7 @@ impl OutputFormat for IcyDraw {
                                                                     background_color: bg,
                                                                     font_page: font_page as usize,
                                                                     attr,
+                                                                    underline_color: None,
                                                                 },
                                                             },
                                                         );
@@ -503,7 +569,7 @@ impl OutputFormat for IcyDraw {
                                     }
                                     let mut o: usize = 0;
 
-                                    let (title, size) = read_utf8_encoded_string(&bytes[o..]);
+                                    let (title, size) = read_utf8_encoded_string(&bytes[o..])?;
                                     let mut layer = Layer::new(title, (0, 0));
 
                                     o += size;
@@ -652,6 +718,7 @@ impl OutputFormat for IcyDraw {
                                                             background_color: bg,
                                                             font_page: font_page as usize,
                                                             attr,
+                                                            underline_color: None,
                                                         },
                                                     },
                                                 );
@@ -681,11 +748,69 @@ impl OutputFormat for IcyDraw {
                 }
             }
         }
+        intern_grapheme_clusters(&mut result, &clusters);
+        for [layer, y, x, length, color] in underline_colors {
+            let Some(line) = result.layers.get_mut(layer as usize).and_then(|layer| layer.lines.get_mut(y as usize)) else {
+                continue;
+            };
+            for ch in line.chars.iter_mut().skip(x as usize).take(length as usize) {
+                ch.attribute.set_underline_color(Some(color));
+            }
+        }
 
         Ok(result)
     }
 }
 
+/// Collects the texts of all grapheme clusters used in the layers.
+fn get_grapheme_clusters(buf: &Buffer) -> BTreeMap<char, String> {
+    let mut clusters = BTreeMap::new();
+    for layer in &buf.layers {
+        for line in &layer.lines {
+            for ch in &line.chars {
+                if let Some(cluster) = crate::get_grapheme_cluster(ch.ch) {
+                    clusters.entry(ch.ch).or_insert(cluster);
+                }
+            }
+        }
+    }
+    clusters
+}
+
+/// Reads the cluster texts of the CLUSTERS chunk: count, then id & utf8 text per cluster.
+fn read_clusters(bytes: &[u8], clusters: &mut HashMap<u32, String>) -> EngineResult<()> {
+    let count = read_u32(bytes, 0)?;
+    let mut o: usize = 4;
+    for _ in 0..count {
+        let ch = read_u32(bytes, o)?;
+        o += 4;
+        let (mut cluster, size) = read_utf8_encoded_string(&bytes[o..])?;
+        o += size;
+        if let Some((end, _)) = cluster.char_indices().nth(crate::MAX_CLUSTER_CHARS) {
+            cluster.truncate(end);
+        }
+        clusters.insert(ch, cluster);
+    }
+    Ok(())
+}
+
+/// Maps the cluster ids of the file to the ids of this process. Ids without cluster text are private use chars.
+fn intern_grapheme_clusters(buf: &mut Buffer, clusters: &HashMap<u32, String>) {
+    for layer in &mut buf.layers {
+        for line in &mut layer.lines {
+            for ch in &mut line.chars {
+                if !crate::is_cluster_id(ch.ch) {
+                    continue;
+                }
+                ch.ch = match clusters.get(&(ch.ch as u32)) {
+                    Some(cluster) => crate::intern_grapheme_cluster(cluster).or_else(|| cluster.chars().next()).unwrap_or(' '),
+                    None => crate::get_cell_char(ch.ch),
+                };
+            }
+        }
+    }
+}
+
 fn get_invisible_line_length(layer: &Layer, y: i32) -> i32 {
     let mut length = layer.get_width();
     while length > 0 && !layer.get_char((length - 1, y)).is_visible() {
@@ -694,9 +819,22 @@ fn get_invisible_line_length(layer: &Layer, y: i32) -> i32 {
     length
 }
 
-fn read_utf8_encoded_string(data: &[u8]) -> (String, usize) {
-    let size = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
-    (unsafe { String::from_utf8_unchecked(data[4..(4 + size)].to_vec()) }, size + 4)
+fn read_utf8_encoded_string(data: &[u8]) -> EngineResult<(String, usize)> {
+    let size = read_u32(data, 0)? as usize;
+    let Some(bytes) = data.get(4..).and_then(|data| data.get(..size)) else {
+        return Err(LoadingError::OutOfBounds.into());
+    };
+    match String::from_utf8(bytes.to_vec()) {
+        Ok(s) => Ok((s, size + 4)),
+        Err(err) => Err(LoadingError::Error(format!("{err}")).into()),
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> EngineResult<u32> {
+    match data.get(offset..).and_then(|data| data.get(..4)) {
+        Some(bytes) => Ok(u32::from_le_bytes(bytes.try_into().unwrap())),
+        None => Err(LoadingError::OutOfBounds.into()),
+    }
 }
 
 fn write_utf8_encoded_string(data: &mut Vec<u8>, s: &str) {
@@ -752,7 +890,7 @@ impl Error for IcedError {
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use std::{collections::HashMap, path::Path};
 
     use crate::{compare_buffers, AttributedChar, Buffer, Color, Layer, OutputFormat, SaveOptions, TextAttribute, TextPane};
 
@@ -932,6 +1070,69 @@ mod tests {
         compare_buffers(&buf, &buf2, crate::CompareOptions::ALL);
     }
 
+    #[test]
+    fn test_grapheme_clusters() {
+        let mut buf = Buffer::new((3, 1));
+        buf.buffer_type = crate::BufferType::Unicode;
+        let cluster = crate::intern_grapheme_cluster("e\u{301}").unwrap();
+        buf.layers[0].set_char((0, 0), AttributedChar::new(cluster, TextAttribute::default()));
+        // a cell char in the cluster id range without cluster text
+        buf.layers[0].set_char((1, 0), AttributedChar::new('\u{10FFF0}', TextAttribute::default()));
+
+        let draw = IcyDraw::default();
+        let bytes = draw.to_bytes(&buf, &SaveOptions::default()).unwrap();
+        let buf2 = draw.load_buffer(Path::new("test.icy"), &bytes, None).unwrap();
+        assert_eq!("e\u{301}", buf2.get_char((0, 0)).get_text());
+        assert_eq!("\u{10FFF0}", buf2.get_char((1, 0)).get_text());
+    }
+
+    #[test]
+    fn test_invalid_clusters_chunk() {
+        let mut bytes = Vec::new();
+        bytes.extend(u32::to_le_bytes(1));
+        bytes.extend(u32::to_le_bytes(0x10_0000));
+        super::write_utf8_encoded_string(&mut bytes, &format!("e{}", "\u{301}".repeat(100)));
+
+        let mut clusters = HashMap::new();
+        super::read_clusters(&bytes, &mut clusters).unwrap();
+        assert_eq!(crate::MAX_CLUSTER_CHARS, clusters[&0x10_0000].chars().count());
+
+        for len in 0..bytes.len() {
+            assert!(super::read_clusters(&bytes[..len], &mut HashMap::new()).is_err());
+        }
+        // a huge count with missing clusters
+        bytes[0..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(super::read_clusters(&bytes, &mut HashMap::new()).is_err());
+        // invalid utf8
+        let len = bytes.len();
+        bytes[0..4].copy_from_slice(&1u32.to_le_bytes());
+        bytes[len - 1] = 0xFF;
+        assert!(super::read_clusters(&bytes, &mut HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_underline_color() {
+        let mut buf = Buffer::new((4, 2));
+        let mut attribute = TextAttribute::default();
+        attribute.set_underline_style(crate::UnderlineStyle::Curly);
+        attribute.set_underline_color(Some(2));
+        buf.layers[0].set_char((1, 0), AttributedChar::new('A', attribute));
+        buf.layers[0].set_char((2, 0), AttributedChar::new('B', attribute));
+        attribute.set_underline_color(Some(4));
+        buf.layers[0].set_char((0, 1), AttributedChar::new('C', attribute));
+
+        let draw = IcyDraw::default();
+        let bytes = draw.to_bytes(&buf, &SaveOptions::default()).unwrap();
+        let buf2 = draw.load_buffer(Path::new("test.icy"), &bytes, None).unwrap();
+        assert_eq!(None, buf2.get_char((0, 0)).attribute.get_underline_color());
+        assert_eq!(Some(2), buf2.get_char((1, 0)).attribute.get_underline_color());
+        assert_eq!(Some(2), buf2.get_char((2, 0)).attribute.get_underline_color());
+        assert_eq!(None, buf2.get_char((3, 0)).attribute.get_underline_color());
+        assert_eq!(Some(4), buf2.get_char((0, 1)).attribute.get_underline_color());
+        assert_eq!(crate::UnderlineStyle::Curly, buf2.get_char((0, 1)).attribute.get_underline_style());
+        compare_buffers(&buf, &buf2, crate::CompareOptions::ALL);
+    }
+
     #[test]
     fn test_fg_switch() {
         // was a bug in compare_buffers, but having more test doesn't hurt.
@@ -32,11 +32,19 @@ pub use tundra::*;
 
 mod color_optimization;
 pub use color_optimization::*;
+
+mod ansimation;
+pub use ansimation::*;
+
+mod apng;
+pub use apng::*;
+
 mod atascii;
 mod ctrla;
 mod icy_draw;
 mod renegade;
 mod seq;
+mod teletext;
 
 use crate::{BitFont, Buffer, BufferFeatures, BufferParser, Caret, EngineResult, Layer, Role, Size, TextPane, ANSI_FONTS, SAUCE_FONT_NAMES};
 
@@ -156,7 +164,7 @@ pub trait OutputFormat: Send + Sync {
 }
 
 lazy_static::lazy_static! {
-    pub static ref FORMATS: [Box<dyn OutputFormat>; 14] = [
+    pub static ref FORMATS: [Box<dyn OutputFormat>; 17] = [
         Box::<ansi::Ansi>::default(),
         Box::<icy_draw::IcyDraw>::default(),
         Box::<IceDraw>::default(),
@@ -171,6 +179,9 @@ lazy_static::lazy_static! {
         Box::<renegade::Renegade>::default(),
         Box::<seq::Seq>::default(),
         Box::<atascii::Atascii>::default(),
+        Box::<teletext::Tti>::default(),
+        Box::<teletext::T42>::default(),
+        Box::<teletext::TeletextFrame>::default(),
         ];
 }
 
@@ -427,6 +438,7 @@ mod tests {
                     foreground_color: 15,
                     background_color: 0,
                     attr: 0,
+                    underline_color: None,
                 },
             },
         );
@@ -6,6 +6,20 @@ use crate::{
     C64_LOWER, C64_UPPER,
 };
 
+const CLEAR_SCREEN: u8 = 0x93;
+const SHIFT_MODE_OFF: u8 = 0x0E;
+const SHIFT_MODE_ON: u8 = 0x8E;
+const REVERSE_ON: u8 = 0x12;
+const REVERSE_OFF: u8 = 0x92;
+const CURSOR_RIGHT: u8 = 0x1D;
+const RETURN: u8 = 0x0D;
+
+const DEFAULT_FOREGROUND: usize = 14;
+const DEFAULT_BACKGROUND: u32 = 6;
+
+/// PETSCII color control codes indexed by the C64 palette color.
+const COLOR_CODES: [u8; 16] = [0x90, 0x05, 0x1C, 0x9F, 0x9C, 0x1E, 0x1F, 0x9E, 0x81, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0x9B];
+
 #[derive(Default)]
 pub(super) struct Seq {}
 
@@ -27,7 +41,61 @@ impl OutputFormat for Seq {
             return Err(anyhow::anyhow!("Buffer is not a Petscii buffer!"));
         }
 
-        Err(anyhow::anyhow!("not implemented!"))
+        let mut result = Vec::new();
+        // the loader pre fills the screen, cells in the default state can only come from a clear screen
+        let cleared = (0..buf.get_height()).any(|y| (0..buf.get_width()).any(|x| buf.get_char((x, y)) == AttributedChar::default()));
+        let blank = if cleared {
+            AttributedChar::default()
+        } else {
+            let mut ch = AttributedChar::default();
+            ch.attribute.set_foreground(DEFAULT_FOREGROUND as u32);
+            ch.attribute.set_background(DEFAULT_BACKGROUND);
+            ch
+        };
+        if cleared {
+            result.push(CLEAR_SCREEN);
+        }
+        let font_page = first_font_page(buf, blank);
+        result.push(if font_page == 1 { SHIFT_MODE_ON } else { SHIFT_MODE_OFF });
+
+        let mut foreground = DEFAULT_FOREGROUND;
+        let mut reverse = false;
+        let last_line = (0..buf.get_height()).rev().find(|y| !is_blank_line(buf, *y, blank));
+        let Some(last_line) = last_line else {
+            return Ok(result);
+        };
+
+        for y in 0..=last_line {
+            let line_len = (0..buf.get_width())
+                .rev()
+                .find(|x| !is_blank(buf.get_char((*x, y)), blank))
+                .map_or(0, |x| x + 1);
+            for x in 0..line_len {
+                let ch = buf.get_char((x, y));
+                if is_blank(ch, blank) {
+                    result.push(CURSOR_RIGHT);
+                    continue;
+                }
+                let fg = ch.attribute.get_foreground() as usize;
+                if fg != foreground && fg < COLOR_CODES.len() {
+                    result.push(COLOR_CODES[fg]);
+                    foreground = fg;
+                }
+                let screen_code = ch.ch as u8;
+                let is_reverse = screen_code >= 0x80;
+                if is_reverse != reverse {
+                    result.push(if is_reverse { REVERSE_ON } else { REVERSE_OFF });
+                    reverse = is_reverse;
+                }
+                result.push(screen_code_to_petscii(screen_code & 0x7F));
+            }
+            // a full line wraps by itself, everything else needs a return - which also ends reverse mode
+            if line_len < buf.get_width() && y < last_line {
+                result.push(RETURN);
+                reverse = false;
+            }
+        }
+        Ok(result)
     }
 
     fn load_buffer(&self, file_name: &Path, data: &[u8], sauce_opt: Option<crate::SauceData>) -> EngineResult<crate::Buffer> {
@@ -60,3 +128,75 @@ impl OutputFormat for Seq {
         Ok(result)
     }
 }
+
+/// Maps a screen code (without the reverse bit) back to the PETSCII byte the parser translates it from.
+fn screen_code_to_petscii(screen_code: u8) -> u8 {
+    match screen_code {
+        0x20..=0x3F => screen_code,
+        0x40..=0x5F => screen_code + 0x20,
+        // 0x00..=0x1F and 0x60..=0x7F
+        _ => screen_code + 0x40,
+    }
+}
+
+/// A cell that is still in the empty screen state - the font page is ignored, the shift mode changes it for every cell.
+fn is_blank(ch: AttributedChar, blank: AttributedChar) -> bool {
+    ch.ch == blank.ch && ch.attribute.get_foreground() == blank.attribute.get_foreground() && ch.attribute.get_background() == blank.attribute.get_background()
+}
+
+fn is_blank_line(buf: &Buffer, y: i32, blank: AttributedChar) -> bool {
+    (0..buf.get_width()).all(|x| is_blank(buf.get_char((x, y)), blank))
+}
+
+/// Petscii switches the charset for the whole screen, so the first used font page wins.
+fn first_font_page(buf: &Buffer, blank: AttributedChar) -> usize {
+    for y in 0..buf.get_height() {
+        for x in 0..buf.get_width() {
+            let ch = buf.get_char((x, y));
+            if !is_blank(ch, blank) {
+                return ch.get_font_page();
+            }
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compare_buffers, OutputFormat, SaveOptions, TextPane};
+
+    fn roundtrip(data: &[u8]) {
+        let seq = super::Seq::default();
+        let buf = seq.load_buffer(std::path::Path::new("test.seq"), data, None).unwrap();
+        let bytes = seq.to_bytes(&buf, &SaveOptions::default()).unwrap();
+        let buf2 = seq.load_buffer(std::path::Path::new("test.seq"), &bytes, None).unwrap();
+        compare_buffers(&buf, &buf2, crate::CompareOptions::ALL);
+    }
+
+    #[test]
+    fn test_roundtrip_colors() {
+        roundtrip(b"\x93\x05HELLO\x1c WORLD\x0d\x0d\x9eYELLOW\x1d\x1d\x81ORANGE");
+    }
+
+    #[test]
+    fn test_roundtrip_reverse() {
+        roundtrip(b"\x93\x12\x1e  \x92AB\x12\xa0\xa0\x0dNORMAL");
+    }
+
+    #[test]
+    fn test_roundtrip_shift_mode() {
+        let seq = super::Seq::default();
+        let buf = seq.load_buffer(std::path::Path::new("test.seq"), b"\x8eHello\xc1", None).unwrap();
+        assert_eq!(1, buf.get_char((0, 0)).get_font_page());
+        roundtrip(b"\x8eHello\xc1");
+    }
+
+    #[test]
+    fn test_roundtrip_full_lines() {
+        let mut data = vec![0x93];
+        for i in 0..40 * 25 {
+            data.push(0x41 + (i % 26) as u8);
+        }
+        roundtrip(&data);
+    }
+}
@@ -0,0 +1,494 @@
+use std::path::Path;
+
+use super::{LoadingError, SaveOptions};
+use crate::{
+    viewdata, AttributedChar, BitFont, Buffer, BufferFeatures, BufferParser, BufferType, Caret, EngineResult, OutputFormat, Palette, Position, TextPane,
+    VIEWDATA, VIEWDATA_PALETTE,
+};
+
+const ROW_LENGTH: usize = 40;
+const TELETEXT_ROWS: usize = 25;
+const VIEWDATA_ROWS: usize = 24;
+const T42_PACKET_LENGTH: usize = 42;
+
+const ESC: u8 = 0x1B;
+
+/// MRG .tti page files as used by wxTED and most teletext inserters.
+#[derive(Default)]
+pub(super) struct Tti {}
+
+impl OutputFormat for Tti {
+    fn get_file_extension(&self) -> &str {
+        "tti"
+    }
+
+    fn get_alt_extensions(&self) -> Vec<String> {
+        vec!["ttix".to_string()]
+    }
+
+    fn get_name(&self) -> &str {
+        "Teletext TTI"
+    }
+
+    fn analyze_features(&self, _features: &BufferFeatures) -> String {
+        String::new()
+    }
+
+    fn to_bytes(&self, buf: &crate::Buffer, _options: &SaveOptions) -> EngineResult<Vec<u8>> {
+        let rows = buffer_to_rows(buf)?;
+        let mut result = Vec::new();
+        result.extend_from_slice(b"DE,");
+        if let Some(sauce) = buf.get_sauce() {
+            result.extend_from_slice(sauce.title.to_string().as_bytes());
+        }
+        result.extend_from_slice(b"\r\nPN,10000\r\nSC,0000\r\nPS,8000\r\n");
+
+        for (y, row) in rows.iter().enumerate() {
+            let len = row.iter().rposition(|b| *b != b' ').map_or(0, |x| x + 1);
+            if len == 0 {
+                continue;
+            }
+            result.extend_from_slice(format!("OL,{y},").as_bytes());
+            for b in &row[..len] {
+                if *b < 0x20 {
+                    result.push(ESC);
+                    result.push(*b + 0x40);
+                } else {
+                    result.push(*b);
+                }
+            }
+            result.extend_from_slice(b"\r\n");
+        }
+        Ok(result)
+    }
+
+    fn load_buffer(&self, file_name: &Path, data: &[u8], sauce_opt: Option<crate::SauceData>) -> EngineResult<crate::Buffer> {
+        let mut rows = vec![[b' '; ROW_LENGTH]; TELETEXT_ROWS];
+        let mut pages = 0;
+        for line in data.split(|b| *b == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.starts_with(b"PN,") {
+                pages += 1;
+                // only the first (sub)page is loaded
+                if pages > 1 {
+                    break;
+                }
+                continue;
+            }
+            let Some(line) = line.strip_prefix(b"OL,") else {
+                continue;
+            };
+            let Some(comma) = line.iter().position(|b| *b == b',') else {
+                continue;
+            };
+            let Ok(row) = String::from_utf8_lossy(&line[..comma]).parse::<usize>() else {
+                continue;
+            };
+            if row >= TELETEXT_ROWS {
+                continue;
+            }
+            let mut x = 0;
+            let mut bytes = line[comma + 1..].iter();
+            while let Some(b) = bytes.next() {
+                if x >= ROW_LENGTH {
+                    break;
+                }
+                rows[row][x] = if *b == ESC {
+                    match bytes.next() {
+                        Some(b) => *b & 0x1F,
+                        None => break,
+                    }
+                } else {
+                    *b & 0x7F
+                };
+                x += 1;
+            }
+        }
+        Ok(rows_to_buffer(file_name, &rows, sauce_opt))
+    }
+}
+
+/// Teletext packet streams, 42 bytes per packet: hamming 8/4 coded magazine and row address followed by 40 odd parity bytes.
+#[derive(Default)]
+pub(super) struct T42 {}
+
+impl OutputFormat for T42 {
+    fn get_file_extension(&self) -> &str {
+        "t42"
+    }
+
+    fn get_name(&self) -> &str {
+        "Teletext T42"
+    }
+
+    fn analyze_features(&self, _features: &BufferFeatures) -> String {
+        String::new()
+    }
+
+    fn to_bytes(&self, buf: &crate::Buffer, _options: &SaveOptions) -> EngineResult<Vec<u8>> {
+        let rows = buffer_to_rows(buf)?;
+        let mut result = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            // magazine 1, the row address bits are split over both address bytes
+            let address = 1 | (y as u8) << 3;
+            result.push(hamming_encode(address & 0x0F));
+            result.push(hamming_encode(address >> 4));
+            if y == 0 {
+                // page 00, subcode 0000 and no control bits - the page header shows the row from column 8 on
+                result.extend_from_slice(&[hamming_encode(0); 8]);
+                result.extend(row[8..].iter().map(|b| odd_parity(*b)));
+            } else {
+                result.extend(row.iter().map(|b| odd_parity(*b)));
+            }
+        }
+        Ok(result)
+    }
+
+    fn load_buffer(&self, file_name: &Path, data: &[u8], sauce_opt: Option<crate::SauceData>) -> EngineResult<crate::Buffer> {
+        if data.len() < T42_PACKET_LENGTH {
+            return Err(LoadingError::FileTooShort.into());
+        }
+        let mut rows = vec![[b' '; ROW_LENGTH]; TELETEXT_ROWS];
+        let mut got_header = false;
+        for packet in data.chunks_exact(T42_PACKET_LENGTH) {
+            let address = hamming_decode(packet[0]) | hamming_decode(packet[1]) << 4;
+            let row = (address >> 3) as usize;
+            if row == 0 {
+                // the next page header ends the page
+                if got_header {
+                    break;
+                }
+                got_header = true;
+                for (x, b) in packet[10..].iter().enumerate() {
+                    rows[0][8 + x] = *b & 0x7F;
+                }
+            } else if row < TELETEXT_ROWS {
+                for (x, b) in packet[2..].iter().enumerate() {
+                    rows[row][x] = *b & 0x7F;
+                }
+            }
+        }
+        Ok(rows_to_buffer(file_name, &rows, sauce_opt))
+    }
+}
+
+/// Raw frames - 40 bytes per row, 1000 bytes for a BBC Mode 7 screen and 960 bytes for a Viewdata frame.
+#[derive(Default)]
+pub(super) struct TeletextFrame {}
+
+impl OutputFormat for TeletextFrame {
+    fn get_file_extension(&self) -> &str {
+        "m7"
+    }
+
+    fn get_alt_extensions(&self) -> Vec<String> {
+        vec!["vdt".to_string()]
+    }
+
+    fn get_name(&self) -> &str {
+        "Teletext Frame"
+    }
+
+    fn analyze_features(&self, _features: &BufferFeatures) -> String {
+        String::new()
+    }
+
+    fn to_bytes(&self, buf: &crate::Buffer, _options: &SaveOptions) -> EngineResult<Vec<u8>> {
+        let rows = buffer_to_rows(buf)?;
+        let mut result = Vec::with_capacity(rows.len() * ROW_LENGTH);
+        for row in &rows {
+            // control codes are stored with the top bit set, like in the Mode 7 screen memory
+            result.extend(row.iter().map(|b| if *b < 0x20 { *b | 0x80 } else { *b }));
+        }
+        Ok(result)
+    }
+
+    fn load_buffer(&self, file_name: &Path, data: &[u8], sauce_opt: Option<crate::SauceData>) -> EngineResult<crate::Buffer> {
+        if data.len() < ROW_LENGTH * VIEWDATA_ROWS {
+            return Err(LoadingError::FileTooShort.into());
+        }
+        let height = if data.len() >= ROW_LENGTH * TELETEXT_ROWS {
+            TELETEXT_ROWS
+        } else {
+            VIEWDATA_ROWS
+        };
+        let mut rows = vec![[b' '; ROW_LENGTH]; height];
+        for (row, chunk) in rows.iter_mut().zip(data.chunks_exact(ROW_LENGTH)) {
+            for (dst, b) in row.iter_mut().zip(chunk) {
+                *dst = *b & 0x7F;
+            }
+        }
+        Ok(rows_to_buffer(file_name, &rows, sauce_opt))
+    }
+}
+
+fn hamming_encode(nibble: u8) -> u8 {
+    const HAMMING_8_4: [u8; 16] = [0x15, 0x02, 0x49, 0x5E, 0x64, 0x73, 0x38, 0x2F, 0xD0, 0xC7, 0x8C, 0x9B, 0xA1, 0xB6, 0xFD, 0xEA];
+    HAMMING_8_4[(nibble & 0x0F) as usize]
+}
+
+/// Extracts the data bits, the protection bits aren't checked.
+fn hamming_decode(b: u8) -> u8 {
+    (b >> 1) & 1 | ((b >> 3) & 1) << 1 | ((b >> 5) & 1) << 2 | ((b >> 7) & 1) << 3
+}
+
+fn odd_parity(b: u8) -> u8 {
+    let b = b & 0x7F;
+    if b.count_ones() & 1 == 0 {
+        b | 0x80
+    } else {
+        b
+    }
+}
+
+/// Creates a viewdata buffer from 7 bit teletext rows, control codes are the bytes below 0x20.
+fn rows_to_buffer(file_name: &Path, rows: &[[u8; ROW_LENGTH]], sauce_opt: Option<crate::SauceData>) -> Buffer {
+    let mut result = Buffer::new((ROW_LENGTH as i32, rows.len() as i32));
+    result.clear_font_table();
+    result.set_font(0, BitFont::from_bytes("", VIEWDATA).unwrap());
+    result.palette = Palette::from_slice(&VIEWDATA_PALETTE);
+    result.buffer_type = BufferType::Viewdata;
+    result.is_terminal_buffer = false;
+    result.file_name = Some(file_name.into());
+    result.set_sauce(sauce_opt, false);
+
+    for (y, row) in rows.iter().enumerate() {
+        // serial attributes are reset at the start of each row
+        let mut parser = viewdata::Parser::default();
+        let mut caret = Caret::default();
+        caret.pos = Position::new(0, y as i32);
+        for b in row {
+            if *b < 0x20 {
+                let _ = parser.print_char(&mut result, 0, &mut caret, ESC as char);
+                let _ = parser.print_char(&mut result, 0, &mut caret, (*b + 0x40) as char);
+            } else {
+                let _ = parser.print_char(&mut result, 0, &mut caret, *b as char);
+            }
+        }
+    }
+    result
+}
+
+/// The serial attribute state while encoding a row.
+struct RowState {
+    foreground: u32,
+    background: u32,
+    is_graphics: bool,
+    is_contiguous: bool,
+    is_blinking: bool,
+    is_double_height: bool,
+    is_concealed: bool,
+}
+
+impl Default for RowState {
+    fn default() -> Self {
+        Self {
+            foreground: 7,
+            background: 0,
+            is_graphics: false,
+            is_contiguous: true,
+            is_blinking: false,
+            is_double_height: false,
+            is_concealed: false,
+        }
+    }
+}
+
+fn is_mosaic(ch: AttributedChar) -> bool {
+    ch.ch as u32 >= 0x80
+}
+
+/// Converts a viewdata buffer back to 7 bit teletext rows.
+/// Attribute changes are placed in the spaces before the changed cells - changes without a free space can't be represented and get lost.
+fn buffer_to_rows(buf: &Buffer) -> EngineResult<Vec<[u8; ROW_LENGTH]>> {
+    if buf.buffer_type != BufferType::Viewdata {
+        return Err(anyhow::anyhow!("Buffer is not a Viewdata buffer!"));
+    }
+    let height = if buf.get_height() as usize > VIEWDATA_ROWS {
+        TELETEXT_ROWS
+    } else {
+        VIEWDATA_ROWS
+    };
+    let mut rows = vec![[b' '; ROW_LENGTH]; height];
+    for (y, row) in rows.iter_mut().enumerate() {
+        let mut state = RowState::default();
+        for (x, b) in row.iter_mut().enumerate() {
+            let ch = buf.get_char((x as i32, y as i32));
+            if ch.ch != ' ' {
+                *b = encode_char(ch);
+                continue;
+            }
+            let next = if x + 1 < ROW_LENGTH {
+                Some(buf.get_char((x as i32 + 1, y as i32)))
+            } else {
+                None
+            };
+            *b = encode_space(&mut state, ch, next);
+        }
+    }
+    Ok(rows)
+}
+
+fn encode_char(ch: AttributedChar) -> u8 {
+    if !is_mosaic(ch) {
+        return ch.ch as u8 & 0x7F;
+    }
+    let sixel = ch.ch as u8 & 0x3F;
+    if sixel < 0x20 {
+        sixel + 0x20
+    } else {
+        sixel + 0x40
+    }
+}
+
+/// A space can hold a control code: set-at codes change the space itself, set-after codes the cells following it.
+fn encode_space(state: &mut RowState, ch: AttributedChar, next: Option<AttributedChar>) -> u8 {
+    let attr = ch.attribute;
+    if attr.get_background() != state.background {
+        if attr.get_background() == 0 {
+            state.background = 0;
+            state.is_concealed = false;
+            return 0x1C;
+        }
+        if attr.get_background() == state.foreground {
+            state.background = state.foreground;
+            return 0x1D;
+        }
+    }
+    if !attr.is_blinking() && state.is_blinking {
+        state.is_blinking = false;
+        return 0x09;
+    }
+    if !attr.is_double_height() && state.is_double_height {
+        state.is_double_height = false;
+        return 0x0C;
+    }
+    if attr.is_concealed() && !state.is_concealed && !state.is_graphics {
+        state.is_concealed = true;
+        return 0x18;
+    }
+
+    if let Some(next) = next {
+        let next_attr = next.attribute;
+        let needs_graphics = if next.ch == ' ' { state.is_graphics } else { is_mosaic(next) };
+        let fg = next_attr.get_foreground();
+        if (fg != state.foreground || needs_graphics != state.is_graphics) && (1..=7).contains(&fg) {
+            state.foreground = fg;
+            state.is_graphics = needs_graphics;
+            state.is_concealed = false;
+            return if needs_graphics { 0x10 } else { 0x00 } + fg as u8;
+        }
+        if state.is_graphics && is_mosaic(next) {
+            let is_contiguous = (next.ch as u32) < 0xC0;
+            if is_contiguous != state.is_contiguous {
+                state.is_contiguous = is_contiguous;
+                return if is_contiguous { 0x19 } else { 0x1A };
+            }
+        }
+        if next_attr.is_blinking() && !state.is_blinking {
+            state.is_blinking = true;
+            return 0x08;
+        }
+        if next_attr.is_double_height() && !state.is_double_height {
+            state.is_double_height = true;
+            return 0x0D;
+        }
+    }
+
+    // in graphics mode a space would become a blank mosaic, a NUL is displayed as space
+    if state.is_graphics {
+        0x00
+    } else {
+        b' '
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compare_buffers, Buffer, OutputFormat, SaveOptions, TextPane};
+
+    fn test_frame() -> Vec<u8> {
+        let mut data = Vec::new();
+        let rows: [&[u8]; 4] = [
+            // the first 8 header columns aren't part of a t42 header packet
+            b"        \x01Red \x02Green \x07White",
+            b"\x11\x7f\x7f\x1a\x7f\x13\x23\x07Mosaic",
+            b"\x04\x1d\x07Blue background\x1c Black",
+            b"\x08Flash\x09 Steady \x0dDouble\x0c Normal",
+        ];
+        for row in rows {
+            let mut row = row.to_vec();
+            row.resize(40, b' ');
+            data.extend_from_slice(&row);
+        }
+        data.resize(1000, b' ');
+        data
+    }
+
+    fn roundtrip(format: &dyn OutputFormat, buf: &Buffer) {
+        let bytes = format.to_bytes(buf, &SaveOptions::default()).unwrap();
+        let buf2 = format.load_buffer(std::path::Path::new("test"), &bytes, None).unwrap();
+        compare_buffers(buf, &buf2, crate::CompareOptions::ALL);
+    }
+
+    fn load_frame() -> Buffer {
+        super::TeletextFrame::default()
+            .load_buffer(std::path::Path::new("test.m7"), &test_frame(), None)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_load_frame() {
+        let buf = load_frame();
+        assert_eq!(25, buf.get_height());
+        assert_eq!(crate::BufferType::Viewdata, buf.buffer_type);
+        assert_eq!('R', buf.get_char((9, 0)).ch);
+        assert_eq!(1, buf.get_char((9, 0)).attribute.get_foreground());
+        assert_eq!(7, buf.get_char((21, 0)).attribute.get_foreground());
+        assert_eq!(0xBF, buf.get_char((1, 1)).ch as u32);
+        assert_eq!(0xFF, buf.get_char((4, 1)).ch as u32);
+        assert_eq!(4, buf.get_char((3, 2)).attribute.get_background());
+    }
+
+    #[test]
+    fn test_viewdata_frame() {
+        let mut data = test_frame();
+        data.truncate(960);
+        let frame = super::TeletextFrame::default();
+        let buf = frame.load_buffer(std::path::Path::new("test.vdt"), &data, None).unwrap();
+        assert_eq!(24, buf.get_height());
+        assert_eq!(960, frame.to_bytes(&buf, &SaveOptions::default()).unwrap().len());
+    }
+
+    #[test]
+    fn test_frame_roundtrip() {
+        roundtrip(&super::TeletextFrame::default(), &load_frame());
+    }
+
+    #[test]
+    fn test_tti_roundtrip() {
+        roundtrip(&super::Tti::default(), &load_frame());
+    }
+
+    #[test]
+    fn test_t42_roundtrip() {
+        roundtrip(&super::T42::default(), &load_frame());
+    }
+
+    #[test]
+    fn test_load_tti() {
+        let data = b"DE,test\r\nPN,10000\r\nSC,0000\r\nOL,1,\x1bAHello\r\nPN,10001\r\nOL,1,Second\r\n";
+        let buf = super::Tti::default().load_buffer(std::path::Path::new("test.tti"), data, None).unwrap();
+        assert_eq!('H', buf.get_char((1, 1)).ch);
+        assert_eq!(1, buf.get_char((1, 1)).attribute.get_foreground());
+    }
+
+    #[test]
+    fn test_hamming() {
+        for i in 0..16 {
+            assert_eq!(i, super::hamming_decode(super::hamming_encode(i)));
+        }
+        assert_eq!(0x80, super::odd_parity(0x00));
+        assert_eq!(0x01, super::odd_parity(0x01));
+    }
+}
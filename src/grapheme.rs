@@ -0,0 +1,308 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        RwLock,
+    },
+};
+
+use unicode_width::UnicodeWidthChar;
+
+/// Grapheme clusters (base char + combining marks, ZWJ sequences) are interned and stored in a cell
+/// as a char of the supplementary private use area B. The ids are only valid inside this process,
+/// files and the clipboard store the cluster texts.
+///
+/// The table is shared by all buffers and only shrinks on [`clear_grapheme_clusters`]. Once it is full new
+/// clusters can't be stored and combining marks are dropped - the cell keeps its base char.
+/// A cluster holds at most [`MAX_CLUSTER_CHARS`] chars, further marks are dropped as well.
+/// Received chars of the private use area B are stored as single char clusters so they can't be taken for an id.
+const CLUSTER_BASE: u32 = 0x10_0000;
+const MAX_CLUSTERS: usize = 0xFFFE;
+
+/// Maximum number of chars (base char + marks) of a grapheme cluster.
+pub const MAX_CLUSTER_CHARS: usize = 32;
+
+/// Number of buffers alive - their cells may hold cluster ids.
+static LIVE_BUFFERS: AtomicUsize = AtomicUsize::new(0);
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+struct ClusterTable {
+    clusters: Vec<String>,
+    lookup: BTreeMap<String, char>,
+    max_clusters: usize,
+    /// The cluster that got its last mark from [`ClusterTable::push`] and isn't referenced by another cell,
+    /// it's extended in place instead of storing every prefix.
+    growing: Option<char>,
+}
+
+impl ClusterTable {
+    const fn new(max_clusters: usize) -> Self {
+        Self {
+            clusters: Vec::new(),
+            lookup: BTreeMap::new(),
+            max_clusters,
+            growing: None,
+        }
+    }
+
+    fn intern(&mut self, cluster: &str) -> Option<char> {
+        if let Some(ch) = self.lookup.get(cluster).copied() {
+            self.seal(ch);
+            return Some(ch);
+        }
+        if self.clusters.len() >= self.max_clusters {
+            return None;
+        }
+        let ch = char::from_u32(CLUSTER_BASE + self.clusters.len() as u32)?;
+        self.clusters.push(cluster.to_string());
+        self.lookup.insert(cluster.to_string(), ch);
+        if self.clusters.len() == self.max_clusters {
+            log::warn!("grapheme cluster table is full, combining chars of new clusters are dropped.");
+        }
+        Some(ch)
+    }
+
+    /// Appends `ch` to the cluster `id` stands for (or to the plain char `id`).
+    fn push(&mut self, id: char, ch: char) -> Option<char> {
+        let mut text = match self.get(id) {
+            Some(cluster) => cluster.clone(),
+            None => id.to_string(),
+        };
+        if text.chars().count() >= MAX_CLUSTER_CHARS {
+            return Some(id);
+        }
+        text.push(ch);
+        if let Some(existing) = self.lookup.get(&text).copied() {
+            self.seal(existing);
+            return Some(existing);
+        }
+        if self.growing == Some(id) {
+            let index = (id as u32 - CLUSTER_BASE) as usize;
+            let old = std::mem::replace(&mut self.clusters[index], text.clone());
+            self.lookup.remove(&old);
+            self.lookup.insert(text, id);
+            return Some(id);
+        }
+        let cluster = self.intern(&text)?;
+        self.growing = Some(cluster);
+        Some(cluster)
+    }
+
+    /// Another cell refers to `id` - it can't be extended in place anymore.
+    fn seal(&mut self, id: char) {
+        if self.growing == Some(id) {
+            self.growing = None;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.clusters.clear();
+        self.lookup.clear();
+        self.growing = None;
+    }
+
+    fn get(&self, ch: char) -> Option<&String> {
+        let id = (ch as u32).checked_sub(CLUSTER_BASE)? as usize;
+        self.clusters.get(id)
+    }
+}
+
+static CLUSTERS: RwLock<ClusterTable> = RwLock::new(ClusterTable::new(MAX_CLUSTERS));
+
+/// Gives back the char that represents the cluster in a cell.
+/// Single chars are returned as they are (except for chars of the private use area B),
+/// `None` is returned if the cluster table is full.
+///
+/// Clusters longer than [`MAX_CLUSTER_CHARS`] are cut.
+///
+/// Known limit: the table is global to the process and holds at most 65534 clusters. A host sending that many
+/// distinct clusters fills it and combining marks are dropped in all buffers until [`clear_grapheme_clusters`] is called.
+///
+/// # Panics
+///
+/// Panics if the cluster table lock is poisoned.
+pub fn intern_grapheme_cluster(cluster: &str) -> Option<char> {
+    let cluster = match cluster.char_indices().nth(MAX_CLUSTER_CHARS) {
+        Some((end, _)) => &cluster[..end],
+        None => cluster,
+    };
+    let mut chars = cluster.chars();
+    let first = chars.next()?;
+    if chars.next().is_none() && !is_cluster_id(first) {
+        return Some(first);
+    }
+    {
+        let table = CLUSTERS.read().unwrap();
+        if let Some(ch) = table.lookup.get(cluster) {
+            if table.growing != Some(*ch) {
+                return Some(*ch);
+            }
+        }
+    }
+    CLUSTERS.write().unwrap().intern(cluster)
+}
+
+/// Appends a combining char to the cell char `id`, gives back the char of the new cluster.
+/// Marks beyond [`MAX_CLUSTER_CHARS`] are dropped, `None` is returned if the cluster table is full.
+///
+/// # Panics
+///
+/// Panics if the cluster table lock is poisoned.
+pub(crate) fn push_to_grapheme_cluster(id: char, ch: char) -> Option<char> {
+    CLUSTERS.write().unwrap().push(id, ch)
+}
+
+/// Called when a cluster id is copied to another cell, the cluster can't be extended in place anymore.
+pub(crate) fn seal_grapheme_cluster(id: char) {
+    if !is_cluster_id(id) {
+        return;
+    }
+    CLUSTERS.write().unwrap().seal(id);
+}
+
+/// Gives back the grapheme cluster a cell char stands for, `None` if it's a plain char.
+///
+/// # Panics
+///
+/// Panics if the cluster table lock is poisoned.
+pub fn get_grapheme_cluster(ch: char) -> Option<String> {
+    if !is_cluster_id(ch) {
+        return None;
+    }
+    CLUSTERS.read().unwrap().get(ch).cloned()
+}
+
+/// Empties the grapheme cluster table once all buffers are dropped - their cells would refer to other clusters
+/// afterwards. Gives back false and keeps the table if a buffer is still alive.
+///
+/// # Panics
+///
+/// Panics if the cluster table lock is poisoned.
+pub fn clear_grapheme_clusters() -> bool {
+    let mut table = CLUSTERS.write().unwrap();
+    if LIVE_BUFFERS.load(Ordering::SeqCst) > 0 {
+        return false;
+    }
+    table.clear();
+    true
+}
+
+pub(crate) fn register_buffer() {
+    LIVE_BUFFERS.fetch_add(1, Ordering::SeqCst);
+}
+
+pub(crate) fn release_buffer() {
+    LIVE_BUFFERS.fetch_sub(1, Ordering::SeqCst);
+}
+
+/// Cell chars in the private use area B are cluster ids.
+pub(crate) fn is_cluster_id(ch: char) -> bool {
+    ch as u32 >= CLUSTER_BASE
+}
+
+/// Converts a received char to the char stored in the cell - chars that would be taken for a cluster id
+/// are interned. Gives back the replacement char if the cluster table is full.
+pub(crate) fn get_cell_char(ch: char) -> char {
+    if is_cluster_id(ch) {
+        intern_grapheme_cluster(ch.encode_utf8(&mut [0; 4])).unwrap_or(char::REPLACEMENT_CHARACTER)
+    } else {
+        ch
+    }
+}
+
+/// Checks if `ch` belongs to the cluster before it instead of starting a new cell.
+pub(crate) fn extends_grapheme_cluster(previous: char, ch: char) -> bool {
+    if ch.width() == Some(0) && !ch.is_control() {
+        // combining marks, variation selectors & ZWJ
+        return true;
+    }
+    get_grapheme_cluster(previous).is_some_and(|cluster| cluster.ends_with(ZERO_WIDTH_JOINER))
+}
+
+/// Width of a cell char in columns - a cluster is as wide as its base char.
+pub(crate) fn get_char_width(ch: char) -> usize {
+    if let Some(cluster) = get_grapheme_cluster(ch) {
+        return cluster.chars().next().and_then(UnicodeWidthChar::width).unwrap_or(1);
+    }
+    ch.width().unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{get_grapheme_cluster, intern_grapheme_cluster};
+
+    #[test]
+    fn test_intern_cluster() {
+        assert_eq!(Some('a'), intern_grapheme_cluster("a"));
+        let ch = intern_grapheme_cluster("e\u{301}").unwrap();
+        assert_eq!(Some(ch), intern_grapheme_cluster("e\u{301}"));
+        assert_eq!(Some("e\u{301}".to_string()), get_grapheme_cluster(ch));
+        assert_eq!(None, get_grapheme_cluster('e'));
+    }
+
+    #[test]
+    fn test_private_use_chars() {
+        // a received char of the private use area B doesn't collide with the cluster ids
+        let cluster = intern_grapheme_cluster("a\u{301}").unwrap();
+        let cell_char = super::get_cell_char(cluster);
+        assert_ne!(cluster, cell_char);
+        assert_eq!(Some(cluster.to_string()), get_grapheme_cluster(cell_char));
+        assert_eq!(Some("a\u{301}".to_string()), get_grapheme_cluster(cluster));
+        assert_eq!('a', super::get_cell_char('a'));
+    }
+
+    #[test]
+    fn test_full_cluster_table() {
+        let mut table = super::ClusterTable::new(2);
+        let first = table.intern("a\u{301}").unwrap();
+        let second = table.intern("e\u{301}").unwrap();
+        assert_ne!(first, second);
+        // known clusters are still found, new ones can't be stored
+        assert_eq!(Some(first), table.intern("a\u{301}"));
+        assert_eq!(None, table.intern("o\u{301}"));
+        assert_eq!(Some(&"e\u{301}".to_string()), table.get(second));
+
+        // clearing makes room for new clusters
+        table.clear();
+        assert_eq!(None, table.get(second));
+        assert_eq!(Some(first), table.intern("o\u{301}"));
+    }
+
+    #[test]
+    fn test_grow_cluster_in_place() {
+        let mut table = super::ClusterTable::new(10);
+        let mut ch = 'e';
+        for _ in 0..100 {
+            ch = table.push(ch, '\u{301}').unwrap();
+        }
+        // the prefixes aren't stored and the cluster is cut at MAX_CLUSTER_CHARS
+        assert_eq!(1, table.clusters.len());
+        assert_eq!(super::MAX_CLUSTER_CHARS, table.get(ch).unwrap().chars().count());
+
+        // a cluster referenced by another cell isn't changed
+        let first = table.push('a', '\u{301}').unwrap();
+        table.seal(first);
+        let second = table.push(first, '\u{302}').unwrap();
+        assert_ne!(first, second);
+        assert_eq!(Some(&"a\u{301}".to_string()), table.get(first));
+        assert_eq!(Some(&"a\u{301}\u{302}".to_string()), table.get(second));
+    }
+
+    #[test]
+    fn test_clear_with_live_buffer() {
+        let _buf = crate::Buffer::new((10, 10));
+        let ch = intern_grapheme_cluster("u\u{301}").unwrap();
+        assert!(!super::clear_grapheme_clusters());
+        assert_eq!(Some("u\u{301}".to_string()), get_grapheme_cluster(ch));
+    }
+
+    #[test]
+    fn test_extends_cluster() {
+        assert!(super::extends_grapheme_cluster('e', '\u{301}'));
+        assert!(!super::extends_grapheme_cluster('e', 'f'));
+        let zwj = intern_grapheme_cluster("👨\u{200D}").unwrap();
+        assert!(super::extends_grapheme_cluster(zwj, '👩'));
+        assert_eq!(2, super::get_char_width(zwj));
+    }
+}
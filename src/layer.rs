@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use i18n_embed_fl::fl;
 
 use crate::{Buffer, Color, Line, Position, Rectangle, Sixel, Size, TextAttribute, TextPane, UnicodeConverter};
@@ -224,6 +226,7 @@ impl Layer {
             }
         }
 
+        crate::grapheme::seal_grapheme_cluster(attributed_char.ch);
         let cur_line = &mut self.lines[pos.y as usize];
         cur_line.set_char(pos.x, attributed_char);
         let font_dims = Size::new(8, 16);
@@ -296,20 +299,29 @@ impl Layer {
         self.size = size.into();
     }
 
-    /// .
-    ///
-    /// # Panics
+    /// Creates a paste preview layer from the data of [`crate::editor::EditState::get_clipboard_data`].
     ///
-    /// Panics if .
+    /// Returns `None` if the data is truncated or has an unknown version.
     pub fn from_clipboard_data(data: &[u8]) -> Option<Layer> {
-        if data[0] != 0 {
+        // version 0 stores chars as u16, version 1 as u32 followed by the grapheme clusters,
+        // version 2 adds the underline color
+        let mut data = data;
+        let version = read_bytes(&mut data, 1)?[0];
+        if version > 2 {
+            return None;
+        }
+        let x = read_u32(&mut data)? as i32;
+        let y = read_u32(&mut data)? as i32;
+        let width = read_u32(&mut data)? as usize;
+        let height = read_u32(&mut data)? as usize;
+        let record_size = match version {
+            0 => 14,
+            1 => 16,
+            _ => 20,
+        };
+        if width.checked_mul(height)?.checked_mul(record_size)? > data.len() {
             return None;
         }
-        let x = i32::from_le_bytes(data[1..5].try_into().unwrap());
-        let y = i32::from_le_bytes(data[5..9].try_into().unwrap());
-        let width = u32::from_le_bytes(data[9..13].try_into().unwrap()) as usize;
-        let height = u32::from_le_bytes(data[13..17].try_into().unwrap()) as usize;
-        let mut data = &data[17..];
 
         let mut layer = Layer::new(fl!(crate::LANGUAGE_LOADER, "layer-pasted-name"), (width, height));
         layer.properties.has_alpha_channel = true;
@@ -317,17 +329,48 @@ impl Layer {
         layer.set_offset((x, y));
         for y in 0..height {
             for x in 0..width {
+                let ch = if version == 0 {
+                    read_u16(&mut data)? as u32
+                } else {
+                    read_u32(&mut data)?
+                };
+                let attr = read_bytes(&mut data, 12)?;
+                let underline_color = if version >= 2 {
+                    Some(read_u32(&mut data)?).filter(|color| *color != NO_UNDERLINE_COLOR)
+                } else {
+                    None
+                };
                 let ch = AttributedChar {
-                    ch: unsafe { char::from_u32_unchecked(u16::from_le_bytes([data[0], data[1]]) as u32) },
+                    ch: char::from_u32(ch).unwrap_or(' '),
                     attribute: TextAttribute {
-                        attr: u16::from_le_bytes([data[2], data[3]]),
-                        font_page: u16::from_le_bytes([data[4], data[5]]) as usize,
-                        background_color: u32::from_le_bytes([data[6], data[7], data[8], data[9]]),
-                        foreground_color: u32::from_le_bytes([data[10], data[11], data[12], data[13]]),
+                        attr: u16::from_le_bytes([attr[0], attr[1]]),
+                        font_page: u16::from_le_bytes([attr[2], attr[3]]) as usize,
+                        background_color: u32::from_le_bytes([attr[4], attr[5], attr[6], attr[7]]),
+                        foreground_color: u32::from_le_bytes([attr[8], attr[9], attr[10], attr[11]]),
+                        underline_color,
                     },
                 };
                 layer.set_char((x as i32, y as i32), ch);
-                data = &data[14..];
+            }
+        }
+
+        if version >= 1 {
+            let cluster_count = read_u32(&mut data)?;
+            let mut clusters = HashMap::new();
+            for _ in 0..cluster_count {
+                let ch = char::from_u32(read_u32(&mut data)?)?;
+                let len = read_u32(&mut data)? as usize;
+                let cluster = std::str::from_utf8(read_bytes(&mut data, len)?).ok()?;
+                clusters.insert(ch, crate::intern_grapheme_cluster(cluster).unwrap_or(' '));
+            }
+            if !clusters.is_empty() {
+                for line in &mut layer.lines {
+                    for ch in &mut line.chars {
+                        if let Some(cluster) = clusters.get(&ch.ch) {
+                            ch.ch = *cluster;
+                        }
+                    }
+                }
             }
         }
         Some(layer)
@@ -356,6 +399,27 @@ impl Layer {
     }
 }
 
+/// Marks clipboard chars without underline color - rgb encoded colors never reach it.
+pub(crate) const NO_UNDERLINE_COLOR: u32 = u32::MAX;
+
+/// Splits `len` bytes off the front of `data`, `None` if the data is too short.
+fn read_bytes<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if data.len() < len {
+        return None;
+    }
+    let (bytes, rest) = data.split_at(len);
+    *data = rest;
+    Some(bytes)
+}
+
+fn read_u16(data: &mut &[u8]) -> Option<u16> {
+    Some(u16::from_le_bytes(read_bytes(data, 2)?.try_into().unwrap()))
+}
+
+fn read_u32(data: &mut &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(read_bytes(data, 4)?.try_into().unwrap()))
+}
+
 #[cfg(test)]
 mod tests {
     use i18n_embed_fl::fl;
@@ -440,4 +504,54 @@ mod tests {
 
         assert!(layer.get_char((0, 0)).ch == '5');
     }
+
+    #[test]
+    fn test_clipboard_underline_color() {
+        let mut state = EditState::default();
+        let mut attribute = TextAttribute::default();
+        attribute.set_is_underlined(true);
+        attribute.set_underline_color(Some(2));
+        state.set_char((0, 0), AttributedChar::new('a', attribute)).unwrap();
+        attribute.set_underline_color(None);
+        state.set_char((1, 0), AttributedChar::new('b', attribute)).unwrap();
+
+        state.set_selection(Rectangle::from_min_size((0, 0), (2, 1))).unwrap();
+        let data = state.get_clipboard_data().unwrap();
+        let layer = Layer::from_clipboard_data(&data).unwrap();
+
+        assert_eq!(Some(2), layer.get_char((0, 0)).attribute.get_underline_color());
+        assert_eq!(None, layer.get_char((1, 0)).attribute.get_underline_color());
+    }
+
+    #[test]
+    fn test_clipboard_grapheme_cluster() {
+        let mut state = EditState::default();
+        let cluster = crate::intern_grapheme_cluster("e\u{301}").unwrap();
+        state.set_char((0, 0), AttributedChar::new(cluster, TextAttribute::default())).unwrap();
+        state.set_char((1, 0), AttributedChar::new('😀', TextAttribute::default())).unwrap();
+
+        state.set_selection(Rectangle::from_min_size((0, 0), (2, 1))).unwrap();
+        let data = state.get_clipboard_data().unwrap();
+        let layer = Layer::from_clipboard_data(&data).unwrap();
+
+        assert_eq!("e\u{301}", layer.get_char((0, 0)).get_text());
+        assert_eq!('😀', layer.get_char((1, 0)).ch);
+    }
+
+    #[test]
+    fn test_clipboard_truncated() {
+        let mut state = EditState::default();
+        let cluster = crate::intern_grapheme_cluster("e\u{301}").unwrap();
+        state.set_char((0, 0), AttributedChar::new(cluster, TextAttribute::default())).unwrap();
+        state.set_selection(Rectangle::from_min_size((0, 0), (2, 1))).unwrap();
+        let data = state.get_clipboard_data().unwrap();
+
+        for len in 0..data.len() {
+            assert!(Layer::from_clipboard_data(&data[..len]).is_none());
+        }
+        // a huge size must not be allocated for a short record
+        let mut data = data.clone();
+        data[9..17].copy_from_slice(&[0xFF; 8]);
+        assert!(Layer::from_clipboard_data(&data).is_none());
+    }
 }
@@ -28,6 +28,12 @@ pub use layer::*;
 mod line;
 pub use line::*;
 
+mod grapheme;
+pub use grapheme::*;
+
+mod scrollback;
+pub use scrollback::*;
+
 mod position;
 pub use position::*;
 
@@ -61,9 +67,15 @@ pub use crc::*;
 mod terminal_state;
 pub use terminal_state::*;
 
+mod mouse_event;
+pub use mouse_event::*;
+
 mod sixel_mod;
 pub use sixel_mod::*;
 
+mod rendering;
+pub use rendering::*;
+
 mod selection;
 pub use selection::*;
 
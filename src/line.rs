@@ -1,7 +1,26 @@
 use super::AttributedChar;
+
+/// Line attributes set with `ESC # Ps` (DECSWL, DECDWL, DECDHL).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineSize {
+    #[default]
+    Single,
+    DoubleWidth,
+    DoubleHeightTop,
+    DoubleHeightBottom,
+}
+
+impl LineSize {
+    /// Double height lines are double width as well.
+    pub fn is_double_width(self) -> bool {
+        self != LineSize::Single
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Line {
     pub chars: Vec<AttributedChar>,
+    pub line_size: LineSize,
 }
 
 impl Line {
@@ -12,13 +31,17 @@ impl Line {
     pub fn with_capacity(capacity: i32) -> Self {
         Line {
             chars: Vec::with_capacity(capacity as usize),
+            line_size: LineSize::Single,
         }
     }
 
     pub fn create(width: i32) -> Self {
         let mut chars = Vec::new();
         chars.resize(width as usize, AttributedChar::invisible());
-        Line { chars }
+        Line {
+            chars,
+            line_size: LineSize::Single,
+        }
     }
 
     pub fn get_line_length(&self) -> i32 {
@@ -43,11 +66,33 @@ impl Line {
         }
         self.chars[index as usize] = char;
     }
+
+    /// Replaces halves of double width characters that lost their other half with spaces.
+    pub fn repair_wide_chars(&mut self) {
+        self.repair_wide_chars_in(0..self.chars.len() as i32);
+    }
+
+    /// Like [`Line::repair_wide_chars`] but only checks the cells in `range`.
+    pub fn repair_wide_chars_in(&mut self, range: std::ops::Range<i32>) {
+        let start = range.start.max(0) as usize;
+        let end = (range.end.max(0) as usize).min(self.chars.len());
+        for i in start..end {
+            let ch = self.chars[i];
+            if ch.is_wide_continuation() {
+                if i == 0 || !self.chars[i - 1].is_wide() {
+                    self.chars[i].ch = ' ';
+                    self.chars[i].attribute.attr &= !crate::attribute::WIDE_CONTINUATION;
+                }
+            } else if ch.is_wide() && (i + 1 >= self.chars.len() || !self.chars[i + 1].is_wide_continuation()) {
+                self.chars[i].ch = ' ';
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{AttributedChar, Line};
+    use crate::{AttributedChar, Line, TextAttribute};
 
     #[test]
     fn test_insert_char() {
@@ -66,4 +111,31 @@ mod tests {
         line.set_char(100, AttributedChar::default());
         assert_eq!(101, line.chars.len());
     }
+
+    #[test]
+    fn test_repair_wide_chars() {
+        let mut line = Line::new();
+        line.set_char(0, AttributedChar::new('中', TextAttribute::default()));
+        line.set_char(1, AttributedChar::wide_continuation(TextAttribute::default()));
+        line.set_char(2, AttributedChar::new('文', TextAttribute::default()));
+        line.set_char(4, AttributedChar::wide_continuation(TextAttribute::default()));
+        line.repair_wide_chars();
+
+        assert_eq!('中', line.chars[0].ch);
+        assert!(line.chars[1].is_wide_continuation());
+        assert_eq!(' ', line.chars[2].ch);
+        assert!(!line.chars[4].is_wide_continuation());
+    }
+
+    #[test]
+    fn test_repair_wide_chars_in() {
+        let mut line = Line::new();
+        line.set_char(0, AttributedChar::new('中', TextAttribute::default()));
+        line.set_char(3, AttributedChar::wide_continuation(TextAttribute::default()));
+        line.repair_wide_chars_in(2..10);
+
+        // cells outside of the range are kept
+        assert_eq!('中', line.chars[0].ch);
+        assert!(!line.chars[3].is_wide_continuation());
+    }
 }
@@ -0,0 +1,324 @@
+use crate::{MouseEncoding, MouseMode, Position, TerminalState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    /// No button is pressed - used for motion events.
+    None,
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight,
+}
+
+impl MouseButton {
+    fn get_code(self) -> u32 {
+        match self {
+            MouseButton::Left => 0,
+            MouseButton::Middle => 1,
+            MouseButton::Right => 2,
+            MouseButton::None => 3,
+            MouseButton::WheelUp => 64,
+            MouseButton::WheelDown => 65,
+            MouseButton::WheelLeft => 66,
+            MouseButton::WheelRight => 67,
+        }
+    }
+
+    pub fn is_wheel(self) -> bool {
+        matches!(
+            self,
+            MouseButton::WheelUp | MouseButton::WheelDown | MouseButton::WheelLeft | MouseButton::WheelRight
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyModifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+impl KeyModifiers {
+    pub const NONE: KeyModifiers = KeyModifiers {
+        shift: false,
+        alt: false,
+        ctrl: false,
+    };
+
+    pub fn is_empty(self) -> bool {
+        !self.shift && !self.alt && !self.ctrl
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseEventType {
+    Press,
+    Release,
+    Motion,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub event_type: MouseEventType,
+    pub button: MouseButton,
+    pub modifiers: KeyModifiers,
+    /// 0 based cell position on the terminal screen
+    pub position: Position,
+    /// 0 based pixel position on the terminal screen, only used by `MouseEncoding::SgrPixels`
+    pub pixel_position: Position,
+}
+
+impl MouseEvent {
+    pub fn new(event_type: MouseEventType, button: MouseButton, position: Position) -> Self {
+        Self {
+            event_type,
+            button,
+            modifiers: KeyModifiers::NONE,
+            position,
+            pixel_position: Position::default(),
+        }
+    }
+
+    pub fn with_modifiers(mut self, modifiers: KeyModifiers) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
+
+    pub fn with_pixel_position(mut self, pixel_position: Position) -> Self {
+        self.pixel_position = pixel_position;
+        self
+    }
+
+    fn get_button_code(&self, release_as_button_3: bool) -> u32 {
+        let mut code = if self.event_type == MouseEventType::Release && release_as_button_3 {
+            3
+        } else {
+            self.button.get_code()
+        };
+        if self.modifiers.shift {
+            code |= 4;
+        }
+        if self.modifiers.alt {
+            code |= 8;
+        }
+        if self.modifiers.ctrl {
+            code |= 16;
+        }
+        if self.event_type == MouseEventType::Motion {
+            code |= 32;
+        }
+        code
+    }
+}
+
+impl TerminalState {
+    /// Encodes a mouse event as report for the remote side according to the current mouse tracking mode and encoding.
+    /// Gives back `None` if the event isn't reported in that mode or can't be encoded.
+    /// See <https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h2-Mouse-Tracking>
+    pub fn encode_mouse_event(&self, event: &MouseEvent) -> Option<Vec<u8>> {
+        if event.position.x < 0 || event.position.y < 0 {
+            return None;
+        }
+        if !self.reports_mouse_event(event) {
+            return None;
+        }
+        let event = if self.mouse_mode == MouseMode::X10 {
+            // X10 mode reports the button without modifiers
+            MouseEvent {
+                modifiers: KeyModifiers::NONE,
+                ..*event
+            }
+        } else {
+            *event
+        };
+        let x = event.position.x as u32 + 1;
+        let y = event.position.y as u32 + 1;
+
+        match self.mouse_encoding {
+            MouseEncoding::Default => encode_normal(event.get_button_code(true), x, y),
+            MouseEncoding::Utf8 => {
+                let mut result = b"\x1B[M".to_vec();
+                for value in [event.get_button_code(true), x, y] {
+                    let ch = char::from_u32(value + 32).filter(|ch| (*ch as u32) < 0x800)?;
+                    let mut buf = [0; 4];
+                    result.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                }
+                Some(result)
+            }
+            MouseEncoding::Sgr => Some(encode_sgr(&event, x, y)),
+            MouseEncoding::Urxvt => Some(format!("\x1B[{};{x};{y}M", event.get_button_code(true) + 32).into_bytes()),
+            MouseEncoding::SgrPixels => {
+                if event.pixel_position.x < 0 || event.pixel_position.y < 0 {
+                    return None;
+                }
+                Some(encode_sgr(&event, event.pixel_position.x as u32 + 1, event.pixel_position.y as u32 + 1))
+            }
+        }
+    }
+
+    fn reports_mouse_event(&self, event: &MouseEvent) -> bool {
+        // wheel "buttons" don't have release events
+        if event.event_type == MouseEventType::Release && event.button.is_wheel() {
+            return false;
+        }
+        match self.mouse_mode {
+            MouseMode::X10 => event.event_type == MouseEventType::Press && !event.button.is_wheel() && event.button != MouseButton::None,
+            MouseMode::VT200 | MouseMode::VT200_Highlight => event.event_type != MouseEventType::Motion,
+            MouseMode::ButtonEvents => event.event_type != MouseEventType::Motion || event.button != MouseButton::None,
+            MouseMode::AnyEvents => true,
+            // the deprecated modes don't track the mouse
+            _ => false,
+        }
+    }
+}
+
+fn encode_normal(button_code: u32, x: u32, y: u32) -> Option<Vec<u8>> {
+    let mut result = b"\x1B[M".to_vec();
+    for value in [button_code, x, y] {
+        result.push(u8::try_from(value + 32).ok()?);
+    }
+    Some(result)
+}
+
+fn encode_sgr(event: &MouseEvent, x: u32, y: u32) -> Vec<u8> {
+    let final_char = if event.event_type == MouseEventType::Release { 'm' } else { 'M' };
+    format!("\x1B[<{};{x};{y}{final_char}", event.get_button_code(false)).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{KeyModifiers, MouseButton, MouseEncoding, MouseEvent, MouseEventType, MouseMode, Position, TerminalState};
+
+    fn state(mouse_mode: MouseMode, mouse_encoding: MouseEncoding) -> TerminalState {
+        let mut state = TerminalState::from((80, 25));
+        state.mouse_mode = mouse_mode;
+        state.mouse_encoding = mouse_encoding;
+        state
+    }
+
+    fn press(button: MouseButton, x: i32, y: i32) -> MouseEvent {
+        MouseEvent::new(MouseEventType::Press, button, Position::new(x, y))
+    }
+
+    fn release(button: MouseButton, x: i32, y: i32) -> MouseEvent {
+        MouseEvent::new(MouseEventType::Release, button, Position::new(x, y))
+    }
+
+    fn motion(button: MouseButton, x: i32, y: i32) -> MouseEvent {
+        MouseEvent::new(MouseEventType::Motion, button, Position::new(x, y))
+    }
+
+    const CTRL: KeyModifiers = KeyModifiers {
+        shift: false,
+        alt: false,
+        ctrl: true,
+    };
+
+    #[test]
+    fn test_default_mode() {
+        let state = state(MouseMode::Default, MouseEncoding::Default);
+        assert_eq!(None, state.encode_mouse_event(&press(MouseButton::Left, 0, 0)));
+    }
+
+    #[test]
+    fn test_x10() {
+        let state = state(MouseMode::X10, MouseEncoding::Default);
+        assert_eq!(Some(b"\x1B[M !!".to_vec()), state.encode_mouse_event(&press(MouseButton::Left, 0, 0)));
+        assert_eq!(
+            Some(b"\x1B[M\"*%".to_vec()),
+            state.encode_mouse_event(&press(MouseButton::Right, 9, 4).with_modifiers(CTRL))
+        );
+        assert_eq!(None, state.encode_mouse_event(&release(MouseButton::Left, 0, 0)));
+        assert_eq!(None, state.encode_mouse_event(&motion(MouseButton::Left, 0, 0)));
+        assert_eq!(None, state.encode_mouse_event(&press(MouseButton::WheelUp, 0, 0)));
+    }
+
+    #[test]
+    fn test_vt200() {
+        let state = state(MouseMode::VT200, MouseEncoding::Default);
+        assert_eq!(
+            Some(b"\x1B[M1!!".to_vec()),
+            state.encode_mouse_event(&press(MouseButton::Middle, 0, 0).with_modifiers(CTRL))
+        );
+        assert_eq!(Some(b"\x1B[M#!!".to_vec()), state.encode_mouse_event(&release(MouseButton::Middle, 0, 0)));
+        assert_eq!(Some(b"\x1B[M`!!".to_vec()), state.encode_mouse_event(&press(MouseButton::WheelUp, 0, 0)));
+        assert_eq!(None, state.encode_mouse_event(&release(MouseButton::WheelUp, 0, 0)));
+        assert_eq!(None, state.encode_mouse_event(&motion(MouseButton::Left, 0, 0)));
+        // coordinates beyond 223 can't be encoded
+        assert_eq!(None, state.encode_mouse_event(&press(MouseButton::Left, 223, 0)));
+    }
+
+    #[test]
+    fn test_motion_tracking() {
+        let state = state(MouseMode::ButtonEvents, MouseEncoding::Default);
+        assert_eq!(Some(b"\x1B[M@\"#".to_vec()), state.encode_mouse_event(&motion(MouseButton::Left, 1, 2)));
+        assert_eq!(None, state.encode_mouse_event(&motion(MouseButton::None, 1, 2)));
+
+        let state = self::state(MouseMode::AnyEvents, MouseEncoding::Default);
+        assert_eq!(Some(b"\x1B[MC\"#".to_vec()), state.encode_mouse_event(&motion(MouseButton::None, 1, 2)));
+    }
+
+    #[test]
+    fn test_utf8_extended() {
+        let state = state(MouseMode::VT200, MouseEncoding::Utf8);
+        assert_eq!(Some(b"\x1B[M !!".to_vec()), state.encode_mouse_event(&press(MouseButton::Left, 0, 0)));
+        let mut expected = b"\x1B[M ".to_vec();
+        expected.extend_from_slice("\u{10C}#".as_bytes());
+        assert_eq!(Some(expected), state.encode_mouse_event(&press(MouseButton::Left, 235, 2)));
+        assert_eq!(None, state.encode_mouse_event(&press(MouseButton::Left, 2100, 2)));
+    }
+
+    #[test]
+    fn test_sgr() {
+        let state = state(MouseMode::ButtonEvents, MouseEncoding::Sgr);
+        assert_eq!(Some(b"\x1B[<0;1;1M".to_vec()), state.encode_mouse_event(&press(MouseButton::Left, 0, 0)));
+        assert_eq!(
+            Some(b"\x1B[<2;300;25m".to_vec()),
+            state.encode_mouse_event(&release(MouseButton::Right, 299, 24))
+        );
+        assert_eq!(
+            Some(b"\x1B[<48;5;6M".to_vec()),
+            state.encode_mouse_event(&motion(MouseButton::Left, 4, 5).with_modifiers(CTRL))
+        );
+        assert_eq!(Some(b"\x1B[<65;1;1M".to_vec()), state.encode_mouse_event(&press(MouseButton::WheelDown, 0, 0)));
+    }
+
+    #[test]
+    fn test_urxvt() {
+        let state = state(MouseMode::VT200, MouseEncoding::Urxvt);
+        assert_eq!(Some(b"\x1B[32;1;1M".to_vec()), state.encode_mouse_event(&press(MouseButton::Left, 0, 0)));
+        assert_eq!(
+            Some(b"\x1B[35;300;25M".to_vec()),
+            state.encode_mouse_event(&release(MouseButton::Right, 299, 24))
+        );
+    }
+
+    #[test]
+    fn test_sgr_pixels() {
+        let state = state(MouseMode::VT200, MouseEncoding::SgrPixels);
+        let event = press(MouseButton::Left, 0, 0).with_pixel_position(Position::new(100, 200));
+        assert_eq!(Some(b"\x1B[<0;101;201M".to_vec()), state.encode_mouse_event(&event));
+        let event = release(MouseButton::Left, 0, 0).with_pixel_position(Position::new(100, 200));
+        assert_eq!(Some(b"\x1B[<0;101;201m".to_vec()), state.encode_mouse_event(&event));
+    }
+
+    #[test]
+    fn test_encoding_keeps_tracking_mode() {
+        // ?1003h?1006h - any motion is reported with the SGR encoding
+        let state = state(MouseMode::AnyEvents, MouseEncoding::Sgr);
+        assert_eq!(Some(b"\x1B[<35;2;3M".to_vec()), state.encode_mouse_event(&motion(MouseButton::None, 1, 2)));
+
+        // ?1000h?1006h - only presses and releases, no drags
+        let state = self::state(MouseMode::VT200, MouseEncoding::Sgr);
+        assert_eq!(None, state.encode_mouse_event(&motion(MouseButton::Left, 1, 2)));
+        assert_eq!(Some(b"\x1B[<0;2;3m".to_vec()), state.encode_mouse_event(&release(MouseButton::Left, 1, 2)));
+
+        // an encoding alone doesn't enable reporting
+        let state = self::state(MouseMode::Default, MouseEncoding::Sgr);
+        assert_eq!(None, state.encode_mouse_event(&press(MouseButton::Left, 1, 2)));
+    }
+}
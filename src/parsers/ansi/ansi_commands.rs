@@ -1,6 +1,10 @@
 #![allow(clippy::unnecessary_wraps)]
-use super::{constants::COLOR_OFFSETS, set_font_selection_success, BaudEmulation, EngineState, Parser};
-use crate::{update_crc16, AttributedChar, BitFont, Buffer, CallbackAction, Caret, EngineResult, FontSelectionState, ParserError, TextPane, XTERM_256_PALETTE};
+use super::{constants::COLOR_OFFSETS, set_font_selection_success, BaudEmulation, ConformanceLevel, EngineState, Parser};
+use crate::{
+    update_crc16, AttributeChangeExtent, AttributedChar, AutoWrapMode, BitFont, Buffer, CallbackAction, Caret, CaretShape, CharacterSets, EngineResult,
+    FontSelectionState, Line, LineSize, MouseEncoding, MouseMode, OriginMode, ParserError, TerminalScrolling, TextAttribute, TextPane, UnderlineStyle,
+    XTERM_256_PALETTE,
+};
 
 impl Parser {
     /// Sequence: `CSI Ps ... m`</p>
@@ -191,7 +195,17 @@ impl Parser {
                 3 => {
                     caret.attribute.set_is_italic(true);
                 }
-                4 => caret.attribute.set_is_underlined(true),
+                4 => {
+                    if self.is_sub_parameter(i + 1) {
+                        // SGR 4:Ps - underline style
+                        let Some(style) = UnderlineStyle::from_sgr(self.parsed_numbers[i + 1]) else {
+                            return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
+                        };
+                        caret.attribute.set_underline_style(style);
+                    } else {
+                        caret.attribute.set_is_underlined(true);
+                    }
+                }
                 5 | 6 => {
                     caret.attribute.set_is_blinking(true);
                 }
@@ -245,6 +259,11 @@ impl Parser {
                 */
                 53 => caret.attribute.set_is_overlined(true),
                 55 => caret.attribute.set_is_overlined(false),
+                58 => {
+                    caret.attribute.set_underline_color(Some(self.parse_extended_colors(buf, &mut i)?));
+                    continue;
+                }
+                59 => caret.attribute.set_underline_color(None),
                 // high intensity colors
                 90..=97 => caret.attribute.set_foreground(8 + COLOR_OFFSETS[n as usize - 90] as u32),
                 100..=107 => caret.attribute.set_background(8 + COLOR_OFFSETS[n as usize - 100] as u32),
@@ -255,49 +274,66 @@ impl Parser {
                     );
                 }
             }
-            i += 1;
+            i = self.next_parameter(i);
         }
 
         Ok(CallbackAction::Update)
     }
 
+    fn is_sub_parameter(&self, i: usize) -> bool {
+        self.sub_parameters.contains(&i)
+    }
+
+    /// Gives back the index of the next parameter after `i`, skipping the sub parameters of `i`.
+    fn next_parameter(&self, mut i: usize) -> usize {
+        i += 1;
+        while self.is_sub_parameter(i) {
+            i += 1;
+        }
+        i
+    }
+
+    /// Parses the color of `SGR 38`, `48` & `58` in both forms:
+    /// `38;5;n` / `38;2;r;g;b` and the ITU T.416 forms `38:5:n` / `38:2:[cs]:r:g:b`.
     fn parse_extended_colors(&mut self, buf: &mut Buffer, i: &mut usize) -> EngineResult<u32> {
         if *i + 1 >= self.parsed_numbers.len() {
             return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
         }
-        match self.parsed_numbers.get(*i + 1) {
+        let is_sub_parameter_form = self.is_sub_parameter(*i + 1);
+        let sub_parameter_count = self.next_parameter(*i) - *i - 1;
+
+        let (color, next) = match self.parsed_numbers.get(*i + 1) {
             Some(5) => {
                 // ESC[38/48;5;⟨n⟩m Select fg/bg color from 256 color lookup
-                if *i + 3 > self.parsed_numbers.len() {
+                if *i + 3 > self.parsed_numbers.len() || is_sub_parameter_form && sub_parameter_count < 2 {
                     return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
                 }
                 let color = self.parsed_numbers[*i + 2];
-                *i += 3;
-                if (0..=255).contains(&color) {
-                    let color = buf.palette.insert_color(XTERM_256_PALETTE[color as usize].1.clone());
-                    Ok(color)
-                } else {
-                    Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into())
+                if !(0..=255).contains(&color) {
+                    return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
                 }
+                (buf.palette.insert_color(XTERM_256_PALETTE[color as usize].1.clone()), *i + 3)
             }
             Some(2) => {
+                self.check_xterm_extensions()?;
                 // ESC[38/48;2;⟨r⟩;⟨g⟩;⟨b⟩ m Select RGB fg/bg color
-                if *i + 5 > self.parsed_numbers.len() {
+                // the sub parameter form may have a color space id in front of the rgb values: 38:2:⟨cs⟩:⟨r⟩:⟨g⟩:⟨b⟩
+                let start = if is_sub_parameter_form && sub_parameter_count >= 5 { *i + 3 } else { *i + 2 };
+                if start + 3 > self.parsed_numbers.len() || is_sub_parameter_form && sub_parameter_count < 4 {
                     return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
                 }
-                let r = self.parsed_numbers[*i + 2];
-                let g = self.parsed_numbers[*i + 3];
-                let b = self.parsed_numbers[*i + 4];
-                *i += 5;
-                if (0..=255).contains(&r) && (0..=255).contains(&g) && (0..=255).contains(&b) {
-                    let color = buf.palette.insert_color_rgb(r as u8, g as u8, b as u8);
-                    Ok(color)
-                } else {
-                    Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into())
+                let r = self.parsed_numbers[start];
+                let g = self.parsed_numbers[start + 1];
+                let b = self.parsed_numbers[start + 2];
+                if !((0..=255).contains(&r) && (0..=255).contains(&g) && (0..=255).contains(&b)) {
+                    return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
                 }
+                (buf.palette.insert_color_rgb(r as u8, g as u8, b as u8), start + 3)
             }
-            _ => Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into()),
-        }
+            _ => return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into()),
+        };
+        *i = if is_sub_parameter_form { self.next_parameter(*i) } else { next };
+        Ok(color)
     }
 
     /// Sequence: `CSI Pn SP @`</p>
@@ -640,6 +676,27 @@ impl Parser {
         Ok(CallbackAction::NoUpdate)
     }
 
+    /// Sequence: `CSI Ps SP q`</p>
+    /// Mnemonic: DECSCUSR</p>
+    /// Description: Set cursor style</p>
+    ///
+    /// Ps = 0, 1 blinking block, 2 steady block, 3 blinking underline,
+    /// 4 steady underline, 5 blinking bar, 6 steady bar.
+    ///
+    /// Source: <URL:https://vt100.net/docs/vt510-rm/DECSCUSR.html>
+    pub(crate) fn set_cursor_style(&mut self, caret: &mut Caret) -> EngineResult<CallbackAction> {
+        let style = self.parsed_numbers.first().copied().unwrap_or(0);
+        let shape = match style {
+            0..=2 => CaretShape::Block,
+            3 | 4 => CaretShape::Underline,
+            5 | 6 => CaretShape::Bar,
+            _ => return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into()),
+        };
+        caret.shape = shape;
+        caret.is_blinking = style == 0 || style % 2 == 1;
+        Ok(CallbackAction::Update)
+    }
+
     /// Sequence: `CSI Pn SP d`</p>
     /// Mnemonic: TSR</p>
     /// Description: Tabulation stop remove</p>
@@ -681,7 +738,9 @@ impl Parser {
     pub(crate) fn soft_terminal_reset(&mut self, buf: &mut Buffer, caret: &mut Caret) {
         self.state = EngineState::Default;
         buf.reset_terminal();
+        self.apply_default_modes(buf);
         caret.reset();
+        self.charsets = CharacterSets::default();
     }
 
     /// Sequence: `CSI Ps1 ; Ps2 * r`</p>
@@ -825,6 +884,89 @@ impl Parser {
         Ok(CallbackAction::Update)
     }
 
+    /// Sequence: `ESC # Ps`</p>
+    /// Mnemonic: DECDHL / DECSWL / DECDWL</p>
+    /// Description: Line size attribute of the caret line</p>
+    ///
+    /// Ps = 3 double height top half, 4 double height bottom half,
+    /// 5 single width, 6 double width.
+    /// The right half of a line is lost by making it double width.
+    ///
+    /// Source: <URL:https://vt100.net/docs/vt510-rm/DECDHL.html>
+    pub(crate) fn set_line_size(&mut self, buf: &mut Buffer, current_layer: usize, caret: &mut Caret, ch: char) -> EngineResult<CallbackAction> {
+        let line_size = match ch {
+            '3' => LineSize::DoubleHeightTop,
+            '4' => LineSize::DoubleHeightBottom,
+            '5' => LineSize::Single,
+            '6' => LineSize::DoubleWidth,
+            _ => return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into()),
+        };
+        let width = buf.terminal_state.get_width();
+        let layer = &mut buf.layers[current_layer];
+        let y = caret.pos.y as usize;
+        if layer.lines.len() <= y {
+            layer.lines.resize(y + 1, Line::with_capacity(width));
+        }
+        let line = &mut layer.lines[y];
+        line.line_size = line_size;
+        if line_size.is_double_width() {
+            line.chars.truncate((width / 2) as usize);
+        }
+        buf.terminal_state.limit_caret_pos(buf, caret);
+        Ok(CallbackAction::Update)
+    }
+
+    /// Sequence: `CSI Ps $ p` / `CSI ? Ps $ p`</p>
+    /// Mnemonic: DECRQM</p>
+    /// Description: Request mode</p>
+    ///
+    /// The terminal responds with `CSI [?] Ps ; Pm $ y` where Pm is
+    /// 0 - mode not recognized, 1 - set, 2 - reset.
+    ///
+    /// Source: <URL:https://vt100.net/docs/vt510-rm/DECRQM.html>
+    pub(crate) fn request_mode(&mut self, buf: &Buffer, caret: &Caret, dec_private: bool) -> EngineResult<CallbackAction> {
+        self.state = EngineState::Default;
+        let [mode] = self.parsed_numbers[..] else {
+            return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
+        };
+        let state = &buf.terminal_state;
+        let is_set = if dec_private {
+            match mode {
+                1 => Some(state.application_cursor_keys),
+                4 => Some(state.scroll_state == TerminalScrolling::Smooth),
+                6 => Some(state.origin_mode == OriginMode::WithinMargins),
+                7 => Some(state.auto_wrap_mode == AutoWrapMode::AutoWrap),
+                25 => Some(caret.is_visible()),
+                33 => Some(caret.ice_mode()),
+                35 => Some(!caret.is_blinking),
+                69 => Some(state.dec_margin_mode_left_right),
+                1004 => Some(state.focus_event_mode),
+                47 | 1047 | 1049 => Some(buf.is_alternate_screen_active()),
+                2004 => Some(state.bracketed_paste_mode),
+                9 => Some(state.mouse_mode == MouseMode::X10),
+                1000 => Some(state.mouse_mode == MouseMode::VT200),
+                1001 => Some(state.mouse_mode == MouseMode::VT200_Highlight),
+                1002 => Some(state.mouse_mode == MouseMode::ButtonEvents),
+                1003 => Some(state.mouse_mode == MouseMode::AnyEvents),
+                1005 | 1006 | 1015 | 1016 => Some(MouseEncoding::from_mode(mode) == Some(state.mouse_encoding)),
+                1007 => Some(state.alternate_scroll_mode),
+                _ => None,
+            }
+        } else {
+            match mode {
+                4 => Some(caret.insert_mode),
+                _ => None,
+            }
+        };
+        let value = match is_set {
+            Some(true) => 1,
+            Some(false) => 2,
+            None => 0,
+        };
+        let prefix = if dec_private { "?" } else { "" };
+        Ok(CallbackAction::SendString(format!("\x1B[{prefix}{mode};{value}$y")))
+    }
+
     /// Sequence: `CSI Ps c`</p>
     /// Mnemonic: DA</p>
     /// Description: Device attributes</p>
@@ -841,13 +983,100 @@ impl Parser {
     /// Status: standard
     pub(crate) fn device_attributes(&mut self) -> EngineResult<CallbackAction> {
         self.state = EngineState::Default;
-        // respond with IcyTerm as ASCII followed by the package version.
-        Ok(CallbackAction::SendString(format!(
-            "\x1b[=73;99;121;84;101;114;109;{};{};{}c",
-            env!("CARGO_PKG_VERSION_MAJOR"),
-            env!("CARGO_PKG_VERSION_MINOR"),
-            env!("CARGO_PKG_VERSION_PATCH")
-        )))
+        let reply = match self.conformance_level {
+            // respond with IcyTerm as ASCII followed by the package version.
+            ConformanceLevel::IcyTerm => format!(
+                "\x1b[=73;99;121;84;101;114;109;{};{};{}c",
+                env!("CARGO_PKG_VERSION_MAJOR"),
+                env!("CARGO_PKG_VERSION_MINOR"),
+                env!("CARGO_PKG_VERSION_PATCH")
+            ),
+            // CTerm as ASCII followed by the SyncTERM cterm revision
+            ConformanceLevel::CTerm => "\x1b[=67;84;101;114;109;1;316c".to_string(),
+            // VT100 with advanced video option
+            ConformanceLevel::Vt100 => "\x1b[?1;2c".to_string(),
+            // 1 - 132 columns, 4 - sixel, 6 - selective erase, 22 - ANSI color, 28 - rectangular editing
+            ConformanceLevel::Vt220 => "\x1b[?62;1;6;22c".to_string(),
+            ConformanceLevel::Vt320 => "\x1b[?63;1;6;22c".to_string(),
+            ConformanceLevel::Vt420 => "\x1b[?64;1;6;22;28c".to_string(),
+            ConformanceLevel::Xterm => "\x1b[?65;1;4;6;22;28c".to_string(),
+        };
+        Ok(CallbackAction::SendString(reply))
+    }
+
+    /// Sequence: `CSI > Ps c`</p>
+    /// Mnemonic: DA2</p>
+    /// Description: Secondary device attributes</p>
+    ///
+    /// Requests the terminal type, firmware version and keyboard. The reply
+    /// is `CSI > Pp ; Pv ; Pc c`, VT100 and `CTerm` don't answer.
+    ///
+    /// Source: <URL:https://vt100.net/docs/vt510-rm/DA2.html>
+    pub(crate) fn secondary_device_attributes(&mut self) -> EngineResult<CallbackAction> {
+        self.state = EngineState::Default;
+        let reply = match self.conformance_level {
+            ConformanceLevel::Vt100 | ConformanceLevel::CTerm => return Ok(CallbackAction::NoUpdate),
+            ConformanceLevel::Vt220 => "\x1b[>1;10;0c",
+            ConformanceLevel::Vt320 => "\x1b[>24;10;0c",
+            ConformanceLevel::Vt420 => "\x1b[>41;10;0c",
+            ConformanceLevel::IcyTerm | ConformanceLevel::Xterm => "\x1b[>41;390;0c",
+        };
+        Ok(CallbackAction::SendString(reply.to_string()))
+    }
+
+    /// Sequence: `CSI = Ps c`</p>
+    /// Mnemonic: DA3</p>
+    /// Description: Tertiary device attributes</p>
+    ///
+    /// Requests the terminal unit id, the reply is DECRPTUI `DCS ! | D...D ST`.
+    /// Only VT400 and newer terminals answer.
+    ///
+    /// Source: <URL:https://vt100.net/docs/vt510-rm/DA3.html>
+    pub(crate) fn tertiary_device_attributes(&mut self) -> EngineResult<CallbackAction> {
+        self.state = EngineState::Default;
+        match self.conformance_level {
+            ConformanceLevel::Vt420 | ConformanceLevel::IcyTerm | ConformanceLevel::Xterm => {
+                Ok(CallbackAction::SendString("\x1BP!|00000000\x1B\\".to_string()))
+            }
+            ConformanceLevel::Vt100 | ConformanceLevel::Vt220 | ConformanceLevel::Vt320 | ConformanceLevel::CTerm => Ok(CallbackAction::NoUpdate),
+        }
+    }
+
+    pub fn get_conformance_level(&self) -> ConformanceLevel {
+        self.conformance_level
+    }
+
+    /// Changes the terminal the parser identifies as and applies the default modes of that level.
+    pub fn set_conformance_level(&mut self, level: ConformanceLevel, buf: &mut Buffer) {
+        self.conformance_level = level;
+        self.apply_default_modes(buf);
+    }
+
+    /// Sets the modes that depend on the conformance level to their defaults.
+    /// That's done on RIS, DECSTR and when the level changes.
+    pub(crate) fn apply_default_modes(&self, buf: &mut Buffer) {
+        buf.terminal_state.auto_wrap_mode = self.conformance_level.default_auto_wrap_mode();
+    }
+
+    /// Ends the current sequence with an error if the DEC operating level is lower than `level`.
+    pub(crate) fn check_conformance_level(&mut self, level: u8) -> EngineResult<()> {
+        if self.conformance_level.vt_level() < level {
+            return Err(self.unsupported_by_conformance_level());
+        }
+        Ok(())
+    }
+
+    /// Ends the current sequence with an error if the conformance level has no xterm extensions.
+    pub(crate) fn check_xterm_extensions(&mut self) -> EngineResult<()> {
+        if !self.conformance_level.has_xterm_extensions() {
+            return Err(self.unsupported_by_conformance_level());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn unsupported_by_conformance_level(&mut self) -> anyhow::Error {
+        self.state = EngineState::Default;
+        ParserError::UnsupportedEscapeSequence(format!("{} isn't supported by {:?}", self.current_escape_sequence, self.conformance_level)).into()
     }
 
     /// Sequence: `CSI Ps ; Pn1 ; Pn2 ; Pn3 t`</p>
@@ -987,18 +1216,38 @@ impl Parser {
         Ok(CallbackAction::Update)
     }
 
+    /// Gives back the area the rectangular area commands are limited to,
+    /// with origin mode set that's the area inside the margins.
+    /// The lines are buffer lines - on terminal buffers the area is the visible screen.
+    fn get_rect_bounds(buf: &Buffer) -> (i32, i32, i32, i32) {
+        let first_line = buf.get_first_visible_line();
+        let last_line = if buf.is_terminal_buffer {
+            first_line + buf.terminal_state.get_height() - 1
+        } else {
+            buf.get_line_count().max(buf.terminal_state.get_height()) - 1
+        };
+        let mut bounds = (first_line, 0, last_line, buf.terminal_state.get_width() - 1);
+        if buf.terminal_state.origin_mode == OriginMode::WithinMargins {
+            if let Some((top, bottom)) = buf.terminal_state.get_margins_top_bottom() {
+                (bounds.0, bounds.2) = (first_line + top, first_line + bottom);
+            }
+            if let Some((left, right)) = buf.terminal_state.get_margins_left_right() {
+                (bounds.1, bounds.3) = (left, right);
+            }
+        }
+        bounds
+    }
+
+    /// Reads the `Pt ; Pl ; Pb ; Pr` rectangle starting at the given parameter.
+    /// Omitted or 0 parameters default to the bounds.
     fn get_rect_area(&self, buf: &Buffer, offset: usize) -> (i32, i32, i32, i32) {
-        let top_line: i32 = self.parsed_numbers[offset]
-            .max(1)
-            .min(buf.get_line_count().max(buf.terminal_state.get_height()))
-            - 1;
-        let left_column = self.parsed_numbers[offset + 1].max(1).min(buf.terminal_state.get_width()) - 1;
-
-        let bottom_line = self.parsed_numbers[offset + 2]
-            .max(1)
-            .min(buf.get_line_count().max(buf.terminal_state.get_height()))
-            - 1;
-        let right_column = self.parsed_numbers[offset + 3].max(1).min(buf.terminal_state.get_width()) - 1;
+        let (top, left, bottom, right) = Self::get_rect_bounds(buf);
+        let param = |i: usize| self.parsed_numbers.get(offset + i).copied().filter(|n| *n > 0);
+
+        let top_line = param(0).map_or(top, |n| (top + n - 1).min(bottom));
+        let left_column = param(1).map_or(left, |n| (left + n - 1).min(right));
+        let bottom_line = param(2).map_or(bottom, |n| (top + n - 1).min(bottom));
+        let right_column = param(3).map_or(right, |n| (left + n - 1).min(right));
 
         (top_line, left_column, bottom_line, right_column)
     }
@@ -1070,13 +1319,279 @@ impl Parser {
 
         let (top_line, left_column, bottom_line, right_column) = self.get_rect_area(buf, 0);
 
+        for y in top_line..=bottom_line {
+            selective_erase(buf, 0, y, left_column..right_column + 1);
+        }
+
+        Ok(CallbackAction::Update)
+    }
+
+    /// Sequence: `CSI Pts ; Pls ; Pbs ; Prs ; Pps ; Ptd ; Pld ; Ppd $ v`</p>
+    /// Mnemonic: DECCRA</p>
+    /// Description: Copy rectangular area</p>
+    ///
+    /// Copies a rectangular area of characters from one section to another
+    /// section in page memory. The copied text retains its character values
+    /// and attributes. The parameters are:
+    ///
+    /// Pts Top line of the source
+    /// Pls Left column of the source
+    /// Pbs Bottom line of the source
+    /// Prs Right column of the source
+    /// Pps Page number of the source
+    /// Ptd Top line of the destination
+    /// Pld Left column of the destination
+    /// Ppd Page number of the destination
+    ///
+    /// The co-ordinates are affected by DECOM. Parts of the destination
+    /// outside the page (or the margins in origin mode) are clipped. Only
+    /// one page is supported, the page numbers are ignored.
+    ///
+    /// Source: <URL:https://vt100.net/docs/vt510-rm/DECCRA.html>
+    pub(crate) fn copy_rectangular_area(&mut self, buf: &mut Buffer) -> EngineResult<CallbackAction> {
+        self.state = EngineState::Default;
+        let (top_line, left_column, bottom_line, right_column) = self.get_rect_area(buf, 0);
+        let (top, left, bottom, right) = Self::get_rect_bounds(buf);
+        let param = |i: usize| self.parsed_numbers.get(i).copied().filter(|n| *n > 0).unwrap_or(1);
+        let dest_top = top + param(5) - 1;
+        let dest_left = left + param(6) - 1;
+
+        let mut chars = Vec::new();
         for y in top_line..=bottom_line {
             for x in left_column..=right_column {
-                let ch = buf.get_char((x, y));
-                buf.layers[0].set_char((x, y), AttributedChar::new(' ', ch.attribute));
+                chars.push(((x - left_column, y - top_line), buf.get_char((x, y))));
+            }
+        }
+        for ((dx, dy), ch) in chars {
+            let (x, y) = (dest_left + dx, dest_top + dy);
+            if x <= right && y <= bottom {
+                buf.layers[0].set_char((x, y), ch);
+            }
+        }
+
+        Ok(CallbackAction::Update)
+    }
+
+    /// Sequence: `CSI Pt ; Pl ; Pb ; Pr ; Ps1 ; ... Psn $ r`</p>
+    /// Mnemonic: DECCARA</p>
+    /// Description: Change attributes in rectangular area</p>
+    ///
+    /// Changes the visual character attributes of an area without changing
+    /// the characters. Ps1 … Psn select the attributes to change:
+    ///
+    /// 0 (default) all attributes off, 1 bold, 4 underline, 5 blink, 8 invisible,
+    /// 22 bold off, 24 underline off, 25 blink off, 28 visible
+    ///
+    /// The negative image (7/27) can't be set or cleared because the colors are
+    /// swapped instead of keeping an attribute, use DECRARA to reverse it.
+    ///
+    /// The co-ordinates are affected by DECOM. DECSACE selects whether the
+    /// area is a rectangle or a stream of characters.
+    ///
+    /// Source: <URL:https://vt100.net/docs/vt510-rm/DECCARA.html>
+    pub(crate) fn change_attributes_in_rectangular_area(&mut self, buf: &mut Buffer) -> EngineResult<CallbackAction> {
+        self.state = EngineState::Default;
+        let mut attributes = self.parsed_numbers.get(4..).unwrap_or_default().to_vec();
+        if attributes.is_empty() {
+            attributes.push(0);
+        }
+        self.change_area_attributes(buf, |attr| {
+            for n in &attributes {
+                match n {
+                    0 => {
+                        attr.set_is_bold(false);
+                        attr.set_is_underlined(false);
+                        attr.set_is_blinking(false);
+                        attr.set_is_concealed(false);
+                    }
+                    1 => attr.set_is_bold(true),
+                    4 => attr.set_is_underlined(true),
+                    5 => attr.set_is_blinking(true),
+                    8 => attr.set_is_concealed(true),
+                    22 => attr.set_is_bold(false),
+                    24 => attr.set_is_underlined(false),
+                    25 => attr.set_is_blinking(false),
+                    28 => attr.set_is_concealed(false),
+                    _ => {}
+                }
+            }
+        });
+        Ok(CallbackAction::Update)
+    }
+
+    /// Sequence: `CSI Pt ; Pl ; Pb ; Pr ; Ps1 ; ... Psn $ t`</p>
+    /// Mnemonic: DECRARA</p>
+    /// Description: Reverse attributes in rectangular area</p>
+    ///
+    /// Toggles the visual character attributes of an area. Ps1 … Psn select
+    /// the attributes to reverse:
+    ///
+    /// 0 (default) all attributes, 1 bold, 4 underline, 5 blink, 7 negative image, 8 invisible
+    ///
+    /// The co-ordinates are affected by DECOM. DECSACE selects whether the
+    /// area is a rectangle or a stream of characters.
+    ///
+    /// Source: <URL:https://vt100.net/docs/vt510-rm/DECRARA.html>
+    pub(crate) fn reverse_attributes_in_rectangular_area(&mut self, buf: &mut Buffer) -> EngineResult<CallbackAction> {
+        self.state = EngineState::Default;
+        let mut attributes = self.parsed_numbers.get(4..).unwrap_or_default().to_vec();
+        if attributes.is_empty() {
+            attributes.push(0);
+        }
+        self.change_area_attributes(buf, |attr| {
+            for n in &attributes {
+                if matches!(n, 0 | 1) {
+                    attr.set_is_bold(!attr.is_bold());
+                }
+                if matches!(n, 0 | 4) {
+                    attr.set_is_underlined(!attr.is_underlined());
+                }
+                if matches!(n, 0 | 5) {
+                    attr.set_is_blinking(!attr.is_blinking());
+                }
+                if matches!(n, 0 | 7) {
+                    swap_colors(attr);
+                }
+                if matches!(n, 0 | 8) {
+                    attr.set_is_concealed(!attr.is_concealed());
+                }
+            }
+        });
+        Ok(CallbackAction::Update)
+    }
+
+    fn change_area_attributes(&self, buf: &mut Buffer, change: impl Fn(&mut TextAttribute)) {
+        let (top_line, left_column, bottom_line, right_column) = self.get_rect_area(buf, 0);
+        let (_, left, _, right) = Self::get_rect_bounds(buf);
+        let is_stream = buf.terminal_state.attribute_change_extent == AttributeChangeExtent::Stream;
+        for y in top_line..=bottom_line {
+            let (start, end) = if is_stream {
+                (
+                    if y == top_line { left_column } else { left },
+                    if y == bottom_line { right_column } else { right },
+                )
+            } else {
+                (left_column, right_column)
+            };
+            for x in start..=end {
+                let mut ch = buf.layers[0].get_char((x, y));
+                if !ch.is_visible() {
+                    // unwritten cells become blanks, otherwise the change wouldn't show
+                    ch = AttributedChar::new(' ', TextAttribute::default());
+                }
+                change(&mut ch.attribute);
+                buf.layers[0].set_char((x, y), ch);
+            }
+        }
+    }
+
+    /// Sequence: `CSI Ps * x`</p>
+    /// Mnemonic: DECSACE</p>
+    /// Description: Select attribute change extent</p>
+    ///
+    /// Selects how DECCARA and DECRARA apply to the specified area:
+    ///
+    /// Ps = 0, 1 the stream of characters from the start to the end position, wrapping at the line ends
+    /// Ps = 2 the rectangle defined by the start and end position
+    ///
+    /// Source: <URL:https://vt100.net/docs/vt510-rm/DECSACE.html>
+    pub(crate) fn select_attribute_change_extent(&mut self, buf: &mut Buffer) -> EngineResult<CallbackAction> {
+        self.state = EngineState::Default;
+        buf.terminal_state.attribute_change_extent = match self.parsed_numbers.first() {
+            None | Some(0 | 1) => AttributeChangeExtent::Stream,
+            Some(2) => AttributeChangeExtent::Rectangle,
+            _ => return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into()),
+        };
+        Ok(CallbackAction::NoUpdate)
+    }
+
+    /// Sequence: `CSI Ps " q`</p>
+    /// Mnemonic: DECSCA</p>
+    /// Description: Select character protection attribute</p>
+    ///
+    /// Defines the characters that come after it as erasable or not erasable
+    /// by the selective erase functions DECSED, DECSEL and DECSERA.
+    ///
+    /// Ps = 0, 2 erasable, 1 not erasable.
+    ///
+    /// Source: <URL:https://vt100.net/docs/vt510-rm/DECSCA.html>
+    pub(crate) fn select_character_protection(&mut self, caret: &mut Caret) -> EngineResult<CallbackAction> {
+        self.state = EngineState::Default;
+        match self.parsed_numbers.first() {
+            None | Some(0 | 2) => caret.attribute.set_is_protected(false),
+            Some(1) => caret.attribute.set_is_protected(true),
+            _ => return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into()),
+        }
+        Ok(CallbackAction::NoUpdate)
+    }
+
+    /// Sequence: `CSI ? Ps J`</p>
+    /// Mnemonic: DECSED</p>
+    /// Description: Selective erase in display</p>
+    ///
+    /// Erases the erasable characters in the display, chars protected by DECSCA are kept.
+    ///
+    /// Ps = 0 from the cursor to the end of the display, 1 from the start of
+    /// the display to the cursor, 2 complete display.
+    ///
+    /// Source: <URL:https://vt100.net/docs/vt510-rm/DECSED.html>
+    pub(crate) fn selective_erase_in_display(&mut self, buf: &mut Buffer, current_layer: usize, caret: &Caret) -> EngineResult<CallbackAction> {
+        self.state = EngineState::Default;
+        let width = buf.terminal_state.get_width();
+        let pos = caret.get_position();
+        let (first, last) = (buf.get_first_visible_line(), buf.get_last_visible_line());
+        match self.parsed_numbers.first() {
+            None | Some(0) => {
+                selective_erase(buf, current_layer, pos.y, pos.x..width);
+                (pos.y + 1..last).for_each(|y| selective_erase(buf, current_layer, y, 0..width));
             }
+            Some(1) => {
+                (first..pos.y).for_each(|y| selective_erase(buf, current_layer, y, 0..width));
+                selective_erase(buf, current_layer, pos.y, 0..pos.x + 1);
+            }
+            Some(2) => (first..last).for_each(|y| selective_erase(buf, current_layer, y, 0..width)),
+            _ => return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into()),
         }
+        Ok(CallbackAction::Update)
+    }
 
+    /// Sequence: `CSI ? Ps K`</p>
+    /// Mnemonic: DECSEL</p>
+    /// Description: Selective erase in line</p>
+    ///
+    /// Erases the erasable characters in the caret line, chars protected by DECSCA are kept.
+    ///
+    /// Ps = 0 from the cursor to the end of the line, 1 from the start of
+    /// the line to the cursor, 2 complete line.
+    ///
+    /// Source: <URL:https://vt100.net/docs/vt510-rm/DECSEL.html>
+    pub(crate) fn selective_erase_in_line(&mut self, buf: &mut Buffer, current_layer: usize, caret: &Caret) -> EngineResult<CallbackAction> {
+        self.state = EngineState::Default;
+        let width = buf.terminal_state.get_width();
+        let pos = caret.get_position();
+        let columns = match self.parsed_numbers.first() {
+            None | Some(0) => pos.x..width,
+            Some(1) => 0..pos.x + 1,
+            Some(2) => 0..width,
+            _ => return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into()),
+        };
+        selective_erase(buf, current_layer, pos.y, columns);
         Ok(CallbackAction::Update)
     }
 }
+
+fn swap_colors(attr: &mut TextAttribute) {
+    let fg = attr.get_foreground();
+    attr.set_foreground(attr.get_background());
+    attr.set_background(fg);
+}
+
+/// Replaces the chars that aren't protected with spaces, the visual attributes are kept.
+fn selective_erase(buf: &mut Buffer, layer: usize, y: i32, columns: std::ops::Range<i32>) {
+    for x in columns {
+        let ch = buf.layers[layer].get_char((x, y));
+        if !ch.attribute.is_protected() {
+            buf.layers[layer].set_char((x, y), AttributedChar::new(' ', ch.attribute));
+        }
+    }
+}
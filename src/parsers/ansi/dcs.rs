@@ -2,9 +2,11 @@ use std::thread;
 
 use base64::{engine::general_purpose, Engine};
 
-use crate::{BitFont, Buffer, CallbackAction, Caret, EngineResult, ParserError, Sixel, HEX_TABLE};
+use crate::{
+    AttributeChangeExtent, BitFont, Buffer, CallbackAction, Caret, CaretShape, EngineResult, ParserError, Sixel, TextAttribute, UnderlineStyle, HEX_TABLE,
+};
 
-use super::{parse_next_number, Parser};
+use super::{constants::COLOR_OFFSETS, parse_next_number, ConformanceLevel, Parser};
 
 #[derive(Debug, Clone, Copy)]
 enum HexMacroState {
@@ -18,6 +20,12 @@ impl Parser {
         if self.parse_string.starts_with("CTerm:Font:") {
             return self.load_custom_font(buf);
         }
+        if let Some(request) = self.parse_string.strip_prefix("$q") {
+            return Ok(CallbackAction::SendString(request_status_string(buf, caret, request)));
+        }
+        if let Some(request) = self.parse_string.strip_prefix("+q") {
+            return Ok(CallbackAction::SendString(request_termcap(self.conformance_level, request)));
+        }
         let mut i = 0;
         self.parsed_numbers.clear();
         for ch in self.parse_string.chars() {
@@ -190,3 +198,127 @@ impl Parser {
         Err(ParserError::UnsupportedDCSSequence(format!("invalid custom font in dcs: {}", self.parse_string)).into())
     }
 }
+
+/// DECRQSS - `DCS $ q Pt ST` is answered with `DCS 1 $ r <setting> ST`, or `DCS 0 $ r ST` for unknown requests.
+fn request_status_string(buf: &Buffer, caret: &Caret, request: &str) -> String {
+    let setting = match request {
+        "m" => Some(format!("{}m", get_sgr_parameters(buf, caret.attribute))),
+        "r" => {
+            let (top, bottom) = buf.terminal_state.get_margins_top_bottom().unwrap_or((0, buf.terminal_state.get_height() - 1));
+            Some(format!("{};{}r", top + 1, bottom + 1))
+        }
+        "s" => {
+            let (left, right) = buf.terminal_state.get_margins_left_right().unwrap_or((0, buf.terminal_state.get_width() - 1));
+            Some(format!("{};{}s", left + 1, right + 1))
+        }
+        "t" => Some(format!("{}t", buf.terminal_state.get_height())),
+        "\"q" => Some(format!("{}\"q", u8::from(caret.attribute.is_protected()))),
+        "*x" => Some(format!(
+            "{}*x",
+            if buf.terminal_state.attribute_change_extent == AttributeChangeExtent::Rectangle {
+                2
+            } else {
+                1
+            }
+        )),
+        "$|" => Some(format!("{}$|", buf.terminal_state.get_width())),
+        " q" => {
+            let style = match caret.shape {
+                CaretShape::Block => 1,
+                CaretShape::Underline => 3,
+                CaretShape::Bar => 5,
+            } + i32::from(!caret.is_blinking);
+            Some(format!("{style} q"))
+        }
+        _ => None,
+    };
+    match setting {
+        Some(setting) => format!("\x1BP1$r{setting}\x1B\\"),
+        None => "\x1BP0$r\x1B\\".to_string(),
+    }
+}
+
+/// The SGR parameters that select `attr`.
+fn get_sgr_parameters(buf: &Buffer, attr: TextAttribute) -> String {
+    let mut params = vec!["0".to_string()];
+    let flags = [
+        (attr.is_bold(), "1"),
+        (attr.is_faint(), "2"),
+        (attr.is_italic(), "3"),
+        (attr.is_blinking(), "5"),
+        (attr.is_concealed(), "8"),
+        (attr.is_crossed_out(), "9"),
+        (attr.is_overlined(), "53"),
+    ];
+    params.extend(flags.iter().filter(|(is_set, _)| *is_set).map(|(_, p)| p.to_string()));
+    match attr.get_underline_style() {
+        UnderlineStyle::None => {}
+        UnderlineStyle::Single => params.push("4".to_string()),
+        UnderlineStyle::Double => params.push("21".to_string()),
+        style => params.push(format!("4:{}", style.to_sgr())),
+    }
+    let color = |color: u32, base: u32, extended: u32| {
+        if color < 16 {
+            // the palette is in DOS order
+            let ansi = COLOR_OFFSETS.iter().position(|c| u32::from(*c) == color % 8).unwrap_or_default() as u32;
+            let base = if color < 8 { base } else { base + 60 };
+            format!("{}", base + ansi)
+        } else {
+            let (r, g, b) = buf.palette.get_rgb(color);
+            format!("{extended};2;{r};{g};{b}")
+        }
+    };
+    if attr.get_foreground() != 7 {
+        params.push(color(attr.get_foreground(), 30, 38));
+    }
+    if attr.get_background() != 0 {
+        params.push(color(attr.get_background(), 40, 48));
+    }
+    if let Some(underline_color) = attr.get_underline_color() {
+        let (r, g, b) = buf.palette.get_rgb(underline_color);
+        params.push(format!("58:2::{r}:{g}:{b}"));
+    }
+    params.join(";")
+}
+
+/// XTGETTCAP - `DCS + q <hex encoded names> ST`, every capability gets its own answer:
+/// `DCS 1 + r <name>=<hex encoded value> ST` or `DCS 0 + r <name> ST` if it's unknown.
+fn request_termcap(conformance_level: ConformanceLevel, request: &str) -> String {
+    let mut result = Vec::new();
+    for hex_name in request.split(';') {
+        let name = decode_hex(hex_name).unwrap_or_default();
+        let value = match name.as_str() {
+            "TN" | "name" => Some(conformance_level.terminal_name()),
+            "Co" | "colors" => Some("256"),
+            "RGB" => Some("8/8/8"),
+            "Smulx" => Some("\x1B[4:%p1%dm"),
+            "Setulc" => Some("\x1B[58:2::%p1%{65536}%/%d:%p1%{256}%/%{255}%&%d:%p1%{255}%&%d%;m"),
+            "Ss" => Some("\x1B[%p1%d q"),
+            "Se" => Some("\x1B[0 q"),
+            "Ms" => Some("\x1B]52;%p1%s;%p2%s\x07"),
+            _ => None,
+        };
+        match value {
+            Some(value) => result.push(format!("\x1BP1+r{hex_name}={}\x1B\\", encode_hex(value))),
+            None => result.push(format!("\x1BP0+r{hex_name}\x1B\\")),
+        }
+    }
+    result.concat()
+}
+
+fn decode_hex(hex: &str) -> Option<String> {
+    if hex.len() % 2 == 1 {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+fn encode_hex(text: &str) -> String {
+    text.bytes()
+        .flat_map(|b| [HEX_TABLE[b as usize >> 4] as char, HEX_TABLE[b as usize & 0xF] as char])
+        .collect()
+}
@@ -0,0 +1,310 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Read,
+};
+
+use base64::{engine::general_purpose, Engine};
+
+use crate::{Buffer, CallbackAction, Caret, EngineResult, ParserError, Position, Sixel, Size};
+
+use super::Parser;
+
+/// Default limit of the decoded image data of a transfer - a 4096x4096 RGBA image.
+const MAX_DATA_SIZE: usize = 64 * 1024 * 1024;
+
+/// Default storage quota of the transmitted images, kitty uses the same value.
+const MAX_STORAGE_SIZE: usize = 320 * 1024 * 1024;
+
+/// Images that have been transmitted with the kitty graphics protocol but aren't necessarily displayed yet.
+pub(crate) struct KittyGraphics {
+    images: HashMap<u32, Sixel>,
+    /// The image ids, oldest first - these are evicted when the storage quota is exceeded.
+    image_order: VecDeque<u32>,
+    /// Size of the picture data of all stored images.
+    storage_size: usize,
+    /// Storage quota for the picture data of all stored images.
+    pub(crate) max_storage_size: usize,
+    /// A chunked transfer (`m=1`) in progress: the command of the first chunk and the payload so far.
+    transfer: Option<(KittyCommand, String)>,
+    /// The remaining chunks of a transfer that exceeded the size limit are skipped.
+    discard_transfer: bool,
+    /// Maximum size of the image data, transfers with larger (base64 or zlib) payloads are dropped.
+    pub(crate) max_data_size: usize,
+}
+
+impl Default for KittyGraphics {
+    fn default() -> Self {
+        Self {
+            images: HashMap::new(),
+            image_order: VecDeque::new(),
+            storage_size: 0,
+            max_storage_size: MAX_STORAGE_SIZE,
+            transfer: None,
+            discard_transfer: false,
+            max_data_size: MAX_DATA_SIZE,
+        }
+    }
+}
+
+impl KittyGraphics {
+    /// Stores an image and evicts the oldest images if the storage quota is exceeded.
+    fn store_image(&mut self, image_id: u32, sixel: Sixel) {
+        self.remove_image(image_id);
+        self.storage_size += sixel.picture_data.len();
+        self.images.insert(image_id, sixel);
+        self.image_order.push_back(image_id);
+        while self.storage_size > self.max_storage_size {
+            let Some(oldest) = self.image_order.front().copied() else {
+                break;
+            };
+            self.remove_image(oldest);
+        }
+    }
+
+    fn remove_image(&mut self, image_id: u32) {
+        if let Some(sixel) = self.images.remove(&image_id) {
+            self.storage_size -= sixel.picture_data.len();
+            self.image_order.retain(|id| *id != image_id);
+        }
+    }
+
+    fn clear_images(&mut self) {
+        self.images.clear();
+        self.image_order.clear();
+        self.storage_size = 0;
+    }
+}
+
+#[derive(Clone, Debug)]
+struct KittyCommand {
+    /// a: t - transmit, T - transmit & display, p - display (put), d - delete, q - query
+    action: char,
+    /// f: 24 - RGB, 32 - RGBA, 100 - PNG
+    format: u32,
+    /// t: only direct transmission (d) is supported
+    medium: char,
+    /// o: z - zlib compressed payload
+    compression: Option<char>,
+    width: i32,
+    height: i32,
+    image_id: u32,
+    more_chunks: bool,
+    /// q: 1 - suppress OK responses, 2 - suppress errors as well
+    quiet: u8,
+    /// d: what to delete
+    delete: char,
+    x: i32,
+    y: i32,
+    /// C: 1 - don't move the cursor after displaying
+    no_cursor_movement: bool,
+}
+
+impl Default for KittyCommand {
+    fn default() -> Self {
+        Self {
+            action: 't',
+            format: 32,
+            medium: 'd',
+            compression: None,
+            width: 0,
+            height: 0,
+            image_id: 0,
+            more_chunks: false,
+            quiet: 0,
+            delete: 'a',
+            x: 0,
+            y: 0,
+            no_cursor_movement: false,
+        }
+    }
+}
+
+impl KittyCommand {
+    fn parse(control_data: &str) -> Result<Self, String> {
+        let mut cmd = KittyCommand::default();
+        for pair in control_data.split(',').filter(|pair| !pair.is_empty()) {
+            let Some((key, value)) = pair.split_once('=') else {
+                return Err(format!("invalid control data '{pair}'"));
+            };
+            let char_value = || value.chars().next().ok_or_else(|| format!("missing value for '{key}'"));
+            let number_value = || value.parse::<i64>().map_err(|_| format!("invalid number '{value}' for '{key}'"));
+            match key {
+                "a" => cmd.action = char_value()?,
+                "f" => cmd.format = number_value()? as u32,
+                "t" => cmd.medium = char_value()?,
+                "o" => cmd.compression = Some(char_value()?),
+                "s" => cmd.width = number_value()? as i32,
+                "v" => cmd.height = number_value()? as i32,
+                "i" => cmd.image_id = number_value()? as u32,
+                "m" => cmd.more_chunks = number_value()? == 1,
+                "q" => cmd.quiet = number_value()? as u8,
+                "d" => cmd.delete = char_value()?,
+                "x" => cmd.x = number_value()? as i32,
+                "y" => cmd.y = number_value()? as i32,
+                "C" => cmd.no_cursor_movement = number_value()? == 1,
+                // placement details (source rectangles, cell offsets, scaling, z-index) are ignored
+                _ => {}
+            }
+        }
+        Ok(cmd)
+    }
+}
+
+impl Parser {
+    /// Executes a kitty graphics protocol command: `APC G <control data> ; <payload> ST`
+    pub(super) fn execute_kitty_graphics(&mut self, buf: &mut Buffer, current_layer: usize, caret: &mut Caret) -> EngineResult<CallbackAction> {
+        let (control_data, payload) = self.parse_string[1..].split_once(';').unwrap_or((&self.parse_string[1..], ""));
+        let mut cmd = match KittyCommand::parse(control_data) {
+            Ok(cmd) => cmd,
+            Err(err) => return Err(ParserError::UnsupportedAPCSequence(err).into()),
+        };
+
+        if self.kitty_graphics.discard_transfer {
+            self.kitty_graphics.discard_transfer = cmd.more_chunks;
+            return Ok(CallbackAction::NoUpdate);
+        }
+        let mut payload = payload.to_string();
+        if let Some((first_cmd, mut data)) = self.kitty_graphics.transfer.take() {
+            // following chunks only carry the m (and q) keys
+            data.push_str(&payload);
+            cmd = KittyCommand {
+                more_chunks: cmd.more_chunks,
+                ..first_cmd
+            };
+            payload = data;
+        }
+        // base64 takes 4 bytes for 3 bytes of data
+        if payload.len() / 4 * 3 > self.kitty_graphics.max_data_size {
+            self.kitty_graphics.discard_transfer = cmd.more_chunks;
+            return get_response(&cmd, Err("EFBIG:payload too large".to_string()));
+        }
+        if cmd.more_chunks {
+            self.kitty_graphics.transfer = Some((cmd, payload));
+            return Ok(CallbackAction::NoUpdate);
+        }
+
+        let result = match cmd.action {
+            't' | 'T' | 'q' => decode_image(&cmd, &payload, self.kitty_graphics.max_data_size).map(|sixel| {
+                if cmd.action == 'q' {
+                    return CallbackAction::NoUpdate;
+                }
+                if cmd.action == 'T' {
+                    place_image(buf, current_layer, caret, &cmd, sixel.clone());
+                }
+                if cmd.image_id != 0 {
+                    self.kitty_graphics.store_image(cmd.image_id, sixel);
+                }
+                CallbackAction::Update
+            }),
+            'p' => {
+                if let Some(sixel) = self.kitty_graphics.images.get(&cmd.image_id) {
+                    place_image(buf, current_layer, caret, &cmd, sixel.clone());
+                    Ok(CallbackAction::Update)
+                } else {
+                    Err(format!("ENOENT:image {} not found", cmd.image_id))
+                }
+            }
+            'd' => {
+                self.delete_kitty_images(buf, current_layer, caret, &cmd);
+                Ok(CallbackAction::Update)
+            }
+            action => Err(format!("EINVAL:unsupported action '{action}'")),
+        };
+        get_response(&cmd, result)
+    }
+
+    fn delete_kitty_images(&mut self, buf: &mut Buffer, current_layer: usize, caret: &Caret, cmd: &KittyCommand) {
+        let font_dims = buf.get_font_dimensions();
+        let sixels = &mut buf.layers[current_layer].sixels;
+        // sixel pictures don't have an image id and aren't affected
+        match cmd.delete.to_ascii_lowercase() {
+            'i' => sixels.retain(|sixel| sixel.image_id != Some(cmd.image_id)),
+            'c' | 'p' => {
+                let cell = if cmd.delete.eq_ignore_ascii_case(&'c') {
+                    caret.get_position()
+                } else {
+                    Position::new(cmd.x - 1, cmd.y - 1)
+                };
+                sixels.retain(|sixel| sixel.image_id.is_none() || !sixel.as_rectangle(font_dims).is_inside(cell));
+            }
+            _ => sixels.retain(|sixel| sixel.image_id.is_none()),
+        }
+        // upper case deletes the image data as well
+        if cmd.delete.is_ascii_uppercase() {
+            match cmd.delete {
+                'I' => self.kitty_graphics.remove_image(cmd.image_id),
+                'A' => self.kitty_graphics.clear_images(),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Answers the command if it names an image and the quiet level allows it.
+fn get_response(cmd: &KittyCommand, result: Result<CallbackAction, String>) -> EngineResult<CallbackAction> {
+    let response = match &result {
+        Ok(_) if cmd.quiet == 0 && cmd.action != 'd' => Some("OK".to_string()),
+        Err(err) if cmd.quiet < 2 => Some(err.clone()),
+        _ => None,
+    };
+    // terminals only answer commands that name an image
+    if cmd.image_id != 0 {
+        if let Some(response) = response {
+            return Ok(CallbackAction::SendString(format!("\x1B_Gi={};{response}\x1B\\", cmd.image_id)));
+        }
+    }
+    match result {
+        Ok(action) => Ok(action),
+        Err(err) => Err(ParserError::UnsupportedAPCSequence(err).into()),
+    }
+}
+
+fn decode_image(cmd: &KittyCommand, payload: &str, max_data_size: usize) -> Result<Sixel, String> {
+    if cmd.medium != 'd' {
+        return Err(format!("EINVAL:unsupported transmission medium '{}'", cmd.medium));
+    }
+    // padding is optional
+    let Ok(mut data) = general_purpose::STANDARD_NO_PAD.decode(payload.trim_end_matches('=')) else {
+        return Err("EINVAL:invalid base64 payload".to_string());
+    };
+    match cmd.compression {
+        None => {}
+        Some('z') => {
+            let mut decompressed = Vec::new();
+            // one byte more than allowed is read to detect too large data
+            let mut decoder = flate2::read::ZlibDecoder::new(&data[..]).take(max_data_size as u64 + 1);
+            if decoder.read_to_end(&mut decompressed).is_err() {
+                return Err("EINVAL:invalid zlib data".to_string());
+            }
+            if decompressed.len() > max_data_size {
+                return Err("EFBIG:decompressed data too large".to_string());
+            }
+            data = decompressed;
+        }
+        Some(compression) => return Err(format!("EINVAL:unsupported compression '{compression}'")),
+    }
+
+    let (size, picture_data) = match cmd.format {
+        24 | 32 => {
+            let bytes_per_pixel = cmd.format as usize / 8;
+            if cmd.width <= 0 || cmd.height <= 0 || data.len() < cmd.width as usize * cmd.height as usize * bytes_per_pixel {
+                return Err("EINVAL:image size doesn't match the payload".to_string());
+            }
+            let pixels = data.chunks_exact(bytes_per_pixel).take(cmd.width as usize * cmd.height as usize);
+            let picture_data = if bytes_per_pixel == 3 {
+                pixels.flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xFF]).collect()
+            } else {
+                pixels.flatten().copied().collect()
+            };
+            (Size::new(cmd.width, cmd.height), picture_data)
+        }
+        100 => return Sixel::from_png(&data).map_err(|err| format!("EBADPNG:{err}")),
+        format => return Err(format!("EINVAL:unsupported format {format}")),
+    };
+    Ok(Sixel::from_data(size, 1, 1, picture_data))
+}
+
+fn place_image(buf: &mut Buffer, current_layer: usize, caret: &mut Caret, cmd: &KittyCommand, mut sixel: Sixel) {
+    sixel.image_id = Some(cmd.image_id);
+    super::place_picture(buf, current_layer, caret, sixel, !cmd.no_cursor_movement);
+}
@@ -0,0 +1,177 @@
+use std::io::Write;
+
+use base64::{engine::general_purpose, Engine};
+
+use crate::{
+    ansi::Parser,
+    parsers::{create_buffer, get_action, update_buffer},
+    CallbackAction, Position,
+};
+
+fn kitty_command(control_data: &str, payload: &[u8]) -> Vec<u8> {
+    format!("\x1B_G{control_data};{}\x1B\\", general_purpose::STANDARD.encode(payload)).into_bytes()
+}
+
+fn rgba_image(width: usize, height: usize) -> Vec<u8> {
+    (0..width * height).flat_map(|i| [i as u8, 0x10, 0x20, 0xFF]).collect()
+}
+
+#[test]
+fn test_transmit_and_display_rgba() {
+    let mut parser = Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[3;5H");
+    let act = get_action(&mut buf, &mut caret, &mut parser, &kitty_command("a=T,f=32,s=16,v=32,i=7", &rgba_image(16, 32)));
+    assert_eq!(CallbackAction::SendString("\x1B_Gi=7;OK\x1B\\".to_string()), act);
+
+    let sixels = &buf.layers[0].sixels;
+    assert_eq!(1, sixels.len());
+    assert_eq!(Position::new(4, 2), sixels[0].position);
+    assert_eq!(16, sixels[0].get_width());
+    assert_eq!(32, sixels[0].get_height());
+    assert_eq!(Some(7), sixels[0].image_id);
+    assert_eq!(&[1, 0x10, 0x20, 0xFF], &sixels[0].picture_data[4..8]);
+
+    // the caret is placed after the image on its last line
+    assert_eq!(Position::new(6, 3), caret.get_position());
+}
+
+#[test]
+fn test_transmit_rgb_then_place() {
+    let mut parser = Parser::default();
+    let rgb: Vec<u8> = (0..8 * 8).flat_map(|_| [0xAA, 0xBB, 0xCC]).collect();
+    let (mut buf, mut caret) = create_buffer(&mut parser, &kitty_command("a=t,f=24,s=8,v=8,i=1,q=1", &rgb));
+    assert!(buf.layers[0].sixels.is_empty());
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[2;2H\x1B_Ga=p,i=1,C=1,q=1\x1B\\");
+    let sixels = &buf.layers[0].sixels;
+    assert_eq!(1, sixels.len());
+    assert_eq!(Position::new(1, 1), sixels[0].position);
+    assert_eq!(&[0xAA, 0xBB, 0xCC, 0xFF], &sixels[0].picture_data[0..4]);
+    assert_eq!(Position::new(1, 1), caret.get_position());
+
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B_Ga=p,i=2\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1B_Gi=2;ENOENT:image 2 not found\x1B\\".to_string()), act);
+}
+
+#[test]
+fn test_chunked_png() {
+    let mut png_data = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_data, 4, 2);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0xFF, 0, 0].repeat(8)).unwrap();
+    }
+    let payload = general_purpose::STANDARD.encode(&png_data);
+    let (first, rest) = payload.split_at(payload.len() / 8 * 4);
+
+    let mut parser = Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, format!("\x1B_Ga=T,f=100,q=2,m=1;{first}\x1B\\").as_bytes());
+    assert!(buf.layers[0].sixels.is_empty());
+    update_buffer(&mut buf, &mut caret, &mut parser, format!("\x1B_Gm=0;{rest}\x1B\\").as_bytes());
+
+    let sixels = &buf.layers[0].sixels;
+    assert_eq!(1, sixels.len());
+    assert_eq!(4, sixels[0].get_width());
+    assert_eq!(2, sixels[0].get_height());
+    assert_eq!(&[0xFF, 0, 0, 0xFF], &sixels[0].picture_data[0..4]);
+}
+
+#[test]
+fn test_compressed_payload() {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&rgba_image(2, 2)).unwrap();
+    let data = encoder.finish().unwrap();
+
+    let mut parser = Parser::default();
+    let (buf, _) = create_buffer(&mut parser, &kitty_command("a=T,f=32,o=z,s=2,v=2,q=2", &data));
+    assert_eq!(rgba_image(2, 2), buf.layers[0].sixels[0].picture_data);
+}
+
+#[test]
+fn test_delete_images() {
+    let mut parser = Parser::default();
+    let mut input = kitty_command("a=T,s=8,v=16,i=1,q=2", &rgba_image(8, 16));
+    input.extend(kitty_command("a=T,s=8,v=16,i=2,q=2", &rgba_image(8, 16)));
+    input.extend(kitty_command("a=T,s=8,v=16,i=3,q=2", &rgba_image(8, 16)));
+    let (mut buf, mut caret) = create_buffer(&mut parser, &input);
+    assert_eq!(3, buf.layers[0].sixels.len());
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B_Ga=d,d=i,i=2\x1B\\");
+    assert_eq!(vec![Some(1), Some(3)], buf.layers[0].sixels.iter().map(|s| s.image_id).collect::<Vec<_>>());
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B_Ga=d,d=p,x=1,y=1\x1B\\");
+    assert_eq!(vec![Some(3)], buf.layers[0].sixels.iter().map(|s| s.image_id).collect::<Vec<_>>());
+
+    // the image data is kept by lower case deletes
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B_Ga=d\x1B\\\x1B_Ga=p,i=1,q=2\x1B\\");
+    assert_eq!(1, buf.layers[0].sixels.len());
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B_Ga=d,d=A\x1B\\");
+    assert!(buf.layers[0].sixels.is_empty());
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B_Ga=p,i=1\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1B_Gi=1;ENOENT:image 1 not found\x1B\\".to_string()), act);
+}
+
+#[test]
+fn test_chunked_transfer_limit() {
+    let mut parser = Parser::default();
+    parser.kitty_graphics.max_data_size = 100;
+    let payload = general_purpose::STANDARD.encode(rgba_image(4, 4));
+    let (mut buf, mut caret) = create_buffer(&mut parser, format!("\x1B_Ga=T,s=4,v=4,i=3,m=1;{payload}\x1B\\").as_bytes());
+    let act = get_action(&mut buf, &mut caret, &mut parser, format!("\x1B_Gm=1;{payload}\x1B\\").as_bytes());
+    assert_eq!(CallbackAction::SendString("\x1B_Gi=3;EFBIG:payload too large\x1B\\".to_string()), act);
+
+    // the rest of the dropped transfer is skipped
+    update_buffer(
+        &mut buf,
+        &mut caret,
+        &mut parser,
+        format!("\x1B_Gm=1;{payload}\x1B\\\x1B_Gm=0;{payload}\x1B\\").as_bytes(),
+    );
+    assert!(buf.layers[0].sixels.is_empty());
+
+    update_buffer(&mut buf, &mut caret, &mut parser, &kitty_command("a=T,s=4,v=4,q=2", &rgba_image(4, 4)));
+    assert_eq!(1, buf.layers[0].sixels.len());
+}
+
+#[test]
+fn test_decompression_limit() {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&[0; 1024]).unwrap();
+    let data = encoder.finish().unwrap();
+
+    let mut parser = Parser::default();
+    parser.kitty_graphics.max_data_size = 1023;
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    let act = get_action(&mut buf, &mut caret, &mut parser, &kitty_command("a=T,f=32,o=z,s=16,v=16,i=1", &data));
+    assert_eq!(CallbackAction::SendString("\x1B_Gi=1;EFBIG:decompressed data too large\x1B\\".to_string()), act);
+    assert!(buf.layers[0].sixels.is_empty());
+
+    parser.kitty_graphics.max_data_size = 1024;
+    update_buffer(&mut buf, &mut caret, &mut parser, &kitty_command("a=T,f=32,o=z,s=16,v=16,q=2", &data));
+    assert_eq!(1, buf.layers[0].sixels.len());
+}
+
+#[test]
+fn test_storage_quota() {
+    let mut parser = Parser::default();
+    // room for two 4x4 RGBA images
+    parser.kitty_graphics.max_storage_size = 128;
+    let mut input = kitty_command("a=t,s=4,v=4,i=1,q=2", &rgba_image(4, 4));
+    input.extend(kitty_command("a=t,s=4,v=4,i=2,q=2", &rgba_image(4, 4)));
+    input.extend(kitty_command("a=t,s=4,v=4,i=3,q=2", &rgba_image(4, 4)));
+    let (mut buf, mut caret) = create_buffer(&mut parser, &input);
+
+    // the oldest image is evicted
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B_Ga=p,i=1\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1B_Gi=1;ENOENT:image 1 not found\x1B\\".to_string()), act);
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B_Ga=p,i=2,q=2\x1B\\\x1B_Ga=p,i=3,q=2\x1B\\");
+    assert_eq!(2, buf.layers[0].sixels.len());
+
+    // replacing an image doesn't count twice
+    update_buffer(&mut buf, &mut caret, &mut parser, &kitty_command("a=t,s=4,v=4,i=3,q=2", &rgba_image(4, 4)));
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B_Ga=p,i=2,q=2\x1B\\");
+    assert_eq!(3, buf.layers[0].sixels.len());
+}
@@ -8,18 +8,21 @@ use std::{
 
 use self::sound::{AnsiMusic, MusicState};
 
-use super::BufferParser;
+use super::{ascii::CP437Converter, BufferParser, UnicodeConverter};
 use crate::{
-    update_crc16, AttributedChar, AutoWrapMode, Buffer, CallbackAction, Caret, EngineResult, FontSelectionState, HyperLink, IceMode, MouseMode, OriginMode,
-    ParserError, Position, TerminalScrolling, BEL, BS, CR, FF, LF,
+    update_crc16, AttributedChar, AutoWrapMode, Buffer, BufferType, CallbackAction, Caret, CharacterSet, CharacterSets, EngineResult, FontSelectionState,
+    HyperLink, IceMode, MouseEncoding, MouseMode, OriginMode, ParserError, Position, Sixel, TerminalScrolling, BEL, BS, CR, FF, LF, SI, SO,
 };
 
 mod ansi_commands;
 pub mod constants;
 mod dcs;
+mod kitty;
 mod osc;
 pub mod sound;
 
+#[cfg(test)]
+mod kitty_tests;
 #[cfg(test)]
 mod sixel_tests;
 #[cfg(test)]
@@ -29,13 +32,17 @@ mod tests;
 pub enum EngineState {
     Default,
     ReadEscapeSequence,
+    ReadLineAttribute,       // ESC #
+    ReadCharacterSet(usize), // ESC ( ) * + - designates G0-G3
 
     ReadCSISequence(bool),
-    ReadCSICommand,        // CSI ?
-    ReadCSIRequest,        // CSI =
-    ReadRIPSupportRequest, // CSI !
-    ReadDeviceAttrs,       // CSI <
+    ReadCSICommand,          // CSI ?
+    ReadCSIRequest,          // CSI =
+    ReadRIPSupportRequest,   // CSI !
+    ReadDeviceAttrs,         // CSI <
+    ReadCSISecondaryRequest, // CSI >
     EndCSI(char),
+    EndPrivateCSI(char), // CSI ? ... <intermediate>
 
     RecordDCS,
     RecordDCSEscape,
@@ -77,6 +84,74 @@ impl From<String> for MusicOption {
     }
 }
 
+/// What the remote side may do with the clipboard through OSC 52.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClipboardAccess {
+    Disabled,
+    #[default]
+    WriteOnly,
+    ReadWrite,
+}
+
+/// Terminal the parser identifies as. It selects the device attribute replies,
+/// the sequences that are honoured and the default modes.
+///
+/// The DEC terminals only accept the sequences of their operating level and none of the xterm extensions.
+/// `CTerm` accepts the same sequences as `IcyTerm`, only the replies differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConformanceLevel {
+    #[default]
+    IcyTerm,
+    Vt100,
+    Vt220,
+    Vt320,
+    Vt420,
+    Xterm,
+    /// Replies like `SyncTERM`, for BBS doors that probe for it
+    CTerm,
+}
+
+impl ConformanceLevel {
+    /// The DEC operating level: 1 for VT100 up to 4 for VT400, the other emulations are at least VT400 compatible.
+    pub fn vt_level(self) -> u8 {
+        match self {
+            ConformanceLevel::Vt100 => 1,
+            ConformanceLevel::Vt220 => 2,
+            ConformanceLevel::Vt320 => 3,
+            ConformanceLevel::Vt420 | ConformanceLevel::Xterm | ConformanceLevel::IcyTerm | ConformanceLevel::CTerm => 4,
+        }
+    }
+
+    /// The xterm and iTerm2 extensions: clipboard access (OSC 52), kitty & iTerm2 images, mouse tracking,
+    /// focus events, bracketed paste and true color SGR.
+    pub fn has_xterm_extensions(self) -> bool {
+        match self {
+            ConformanceLevel::Vt100 | ConformanceLevel::Vt220 | ConformanceLevel::Vt320 | ConformanceLevel::Vt420 => false,
+            ConformanceLevel::IcyTerm | ConformanceLevel::Xterm | ConformanceLevel::CTerm => true,
+        }
+    }
+
+    /// The DEC terminals power up with auto wrap off, the PC/xterm style emulations with auto wrap on.
+    pub fn default_auto_wrap_mode(self) -> AutoWrapMode {
+        match self {
+            ConformanceLevel::Vt100 | ConformanceLevel::Vt220 | ConformanceLevel::Vt320 | ConformanceLevel::Vt420 => AutoWrapMode::NoWrap,
+            ConformanceLevel::IcyTerm | ConformanceLevel::Xterm | ConformanceLevel::CTerm => AutoWrapMode::AutoWrap,
+        }
+    }
+
+    /// The terminfo name reported for the `TN` capability of XTGETTCAP.
+    pub fn terminal_name(self) -> &'static str {
+        match self {
+            ConformanceLevel::Vt100 => "vt100",
+            ConformanceLevel::Vt220 => "vt220",
+            ConformanceLevel::Vt320 => "vt320",
+            ConformanceLevel::Vt420 => "vt420",
+            ConformanceLevel::CTerm => "syncterm",
+            ConformanceLevel::IcyTerm | ConformanceLevel::Xterm => "xterm-256color",
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum BaudEmulation {
     #[default]
@@ -120,8 +195,11 @@ impl BaudEmulation {
 pub struct Parser {
     pub(crate) state: EngineState,
     saved_pos: Position,
-    saved_cursor_opt: Option<Caret>,
+    /// DECSC & DECSET 1049 save the caret together with the character set designations and shifts.
+    saved_cursor_opt: Option<(Caret, CharacterSets)>,
     pub(crate) parsed_numbers: Vec<i32>,
+    /// Indices of the parsed numbers that are colon separated sub parameters (ITU T.416, `4:3`).
+    pub(crate) sub_parameters: Vec<usize>,
 
     pub hyper_links: Vec<HyperLink>,
 
@@ -143,6 +221,10 @@ pub struct Parser {
     pub parse_string: String,
     pub macro_dcs: String,
     pub bs_is_ctrl_char: bool,
+    pub clipboard_access: ClipboardAccess,
+    pub(crate) conformance_level: ConformanceLevel,
+    pub(crate) charsets: CharacterSets,
+    kitty_graphics: kitty::KittyGraphics,
 }
 
 impl Default for Parser {
@@ -151,6 +233,7 @@ impl Default for Parser {
             state: EngineState::Default,
             saved_pos: Position::default(),
             parsed_numbers: Vec::new(),
+            sub_parameters: Vec::new(),
             current_escape_sequence: String::new(),
             saved_cursor_opt: None,
             ansi_music: MusicOption::Off,
@@ -165,6 +248,10 @@ impl Default for Parser {
             last_char: '\0',
             hyper_links: Vec::new(),
             bs_is_ctrl_char: false,
+            clipboard_access: ClipboardAccess::default(),
+            conformance_level: ConformanceLevel::default(),
+            charsets: CharacterSets::default(),
+            kitty_graphics: kitty::KittyGraphics::default(),
         }
     }
 }
@@ -185,6 +272,7 @@ impl BufferParser for Parser {
                         '[' => {
                             self.state = EngineState::ReadCSISequence(true);
                             self.parsed_numbers.clear();
+                            self.sub_parameters.clear();
                             Ok(CallbackAction::NoUpdate)
                         }
                         ']' => {
@@ -194,12 +282,13 @@ impl BufferParser for Parser {
                             Ok(CallbackAction::NoUpdate)
                         }
                         '7' => {
-                            self.saved_cursor_opt = Some(caret.clone());
+                            self.saved_cursor_opt = Some((caret.clone(), self.charsets.clone()));
                             Ok(CallbackAction::NoUpdate)
                         }
                         '8' => {
-                            if let Some(saved_caret) = &self.saved_cursor_opt {
+                            if let Some((saved_caret, charsets)) = &self.saved_cursor_opt {
                                 *caret = saved_caret.clone();
+                                self.charsets = charsets.clone();
                             }
                             Ok(CallbackAction::Update)
                         }
@@ -209,7 +298,9 @@ impl BufferParser for Parser {
                             caret.ff(buf, current_layer);
                             caret.reset();
                             buf.reset_terminal();
+                            self.apply_default_modes(buf);
                             self.macros.clear();
+                            self.charsets = CharacterSets::default();
                             Ok(CallbackAction::Update)
                         }
 
@@ -244,6 +335,31 @@ impl BufferParser for Parser {
                             Ok(CallbackAction::NoUpdate)
                         }
 
+                        '#' => {
+                            self.state = EngineState::ReadLineAttribute;
+                            Ok(CallbackAction::NoUpdate)
+                        }
+                        '(' | ')' | '*' | '+' => {
+                            // SCS - Select character set
+                            self.state = EngineState::ReadCharacterSet(match ch {
+                                '(' => 0,
+                                ')' => 1,
+                                '*' => 2,
+                                _ => 3,
+                            });
+                            Ok(CallbackAction::NoUpdate)
+                        }
+                        'N' | 'O' => {
+                            // SS2/SS3 - Single shift
+                            self.charsets.single_shift(if ch == 'N' { 2 } else { 3 });
+                            Ok(CallbackAction::NoUpdate)
+                        }
+                        'n' | 'o' => {
+                            // LS2/LS3 - Locking shift
+                            self.charsets.locking_shift(if ch == 'n' { 2 } else { 3 });
+                            Ok(CallbackAction::NoUpdate)
+                        }
+
                         '_' => {
                             // Application Program String
                             self.state = EngineState::ReadAPS;
@@ -267,6 +383,21 @@ impl BufferParser for Parser {
                     }
                 };
             }
+            EngineState::ReadCharacterSet(g) => {
+                self.current_escape_sequence.push(ch);
+                let g = *g;
+                self.state = EngineState::Default;
+                let Some(set) = CharacterSet::from_designator(ch) else {
+                    return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
+                };
+                self.charsets.designate(g, set);
+                return Ok(CallbackAction::NoUpdate);
+            }
+            EngineState::ReadLineAttribute => {
+                self.current_escape_sequence.push(ch);
+                self.state = EngineState::Default;
+                return self.set_line_size(buf, current_layer, caret, ch);
+            }
             EngineState::ReadAPS => {
                 if ch == '\x1B' {
                     self.state = EngineState::ReadAPSEscape;
@@ -277,8 +408,7 @@ impl BufferParser for Parser {
             EngineState::ReadAPSEscape => {
                 if ch == '\\' {
                     self.state = EngineState::Default;
-                    self.execute_aps_command(buf, caret);
-                    return Ok(CallbackAction::NoUpdate);
+                    return self.execute_aps_command(buf, current_layer, caret);
                 }
                 self.state = EngineState::ReadAPS;
                 self.parse_string.push('\x1B');
@@ -372,13 +502,18 @@ impl BufferParser for Parser {
                     self.state = EngineState::ReadOSCSequenceEscape;
                     return Ok(CallbackAction::NoUpdate);
                 }
+                if ch == BEL {
+                    // xterm allows BEL as string terminator
+                    self.state = EngineState::Default;
+                    return self.parse_osc(buf, current_layer, caret);
+                }
                 self.parse_string.push(ch);
                 return Ok(CallbackAction::NoUpdate);
             }
             EngineState::ReadOSCSequenceEscape => {
                 if ch == '\\' {
                     self.state = EngineState::Default;
-                    return self.parse_osc(buf, caret);
+                    return self.parse_osc(buf, current_layer, caret);
                 }
                 self.state = EngineState::ReadOSCSequence;
                 self.parse_string.push('\x1B');
@@ -394,7 +529,11 @@ impl BufferParser for Parser {
                         if self.parsed_numbers.len() != 1 {
                             return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
                         }
+                        if matches!(self.parsed_numbers.first(), Some(9 | 1000..=1007 | 1015 | 1016 | 2004)) {
+                            self.check_xterm_extensions()?;
+                        }
                         match self.parsed_numbers.first() {
+                            Some(1) => buf.terminal_state.application_cursor_keys = false,
                             Some(4) => buf.terminal_state.scroll_state = TerminalScrolling::Fast,
                             Some(6) => {
                                 //  buf.terminal_state.origin_mode = OriginMode::WithinMargins;
@@ -409,8 +548,25 @@ impl BufferParser for Parser {
                                 buf.terminal_state.clear_margins_left_right();
                             }
 
-                            Some(9 | 1000..=1007 | 1015 | 1016) => {
-                                buf.terminal_state.mouse_mode = MouseMode::Default;
+                            Some(1004) => buf.terminal_state.focus_event_mode = false,
+                            Some(9 | 1000..=1003) => buf.terminal_state.mouse_mode = MouseMode::Default,
+                            Some(1007) => buf.terminal_state.alternate_scroll_mode = false,
+                            Some(mode @ (1005 | 1006 | 1015 | 1016)) => {
+                                if MouseEncoding::from_mode(*mode) == Some(buf.terminal_state.mouse_encoding) {
+                                    buf.terminal_state.mouse_encoding = MouseEncoding::Default;
+                                }
+                            }
+
+                            // Alternate screen see https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h2-The-Alternate-Screen-Buffer
+                            Some(47) => buf.leave_alternate_screen(caret, false),
+                            Some(1047) => buf.leave_alternate_screen(caret, true),
+                            Some(2004) => buf.terminal_state.bracketed_paste_mode = false,
+                            Some(1049) => {
+                                buf.leave_alternate_screen(caret, true);
+                                if let Some((saved_caret, charsets)) = &self.saved_cursor_opt {
+                                    *caret = saved_caret.clone();
+                                    self.charsets = charsets.clone();
+                                }
                             }
                             _ => {
                                 return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
@@ -422,7 +578,11 @@ impl BufferParser for Parser {
                         if self.parsed_numbers.len() != 1 {
                             return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
                         }
+                        if matches!(self.parsed_numbers.first(), Some(9 | 1000..=1007 | 1015 | 1016 | 2004)) {
+                            self.check_xterm_extensions()?;
+                        }
                         match self.parsed_numbers.first() {
+                            Some(1) => buf.terminal_state.application_cursor_keys = true,
                             Some(4) => buf.terminal_state.scroll_state = TerminalScrolling::Smooth,
                             Some(6) => buf.terminal_state.origin_mode = OriginMode::UpperLeftCorner,
                             Some(7) => buf.terminal_state.auto_wrap_mode = AutoWrapMode::AutoWrap,
@@ -444,18 +604,20 @@ impl BufferParser for Parser {
                             Some(1002) => buf.terminal_state.mouse_mode = MouseMode::ButtonEvents,
                             Some(1003) => buf.terminal_state.mouse_mode = MouseMode::AnyEvents,
 
-                            Some(1004) => buf.terminal_state.mouse_mode = MouseMode::FocusEvent,
-                            Some(1007) => {
-                                buf.terminal_state.mouse_mode = MouseMode::AlternateScroll;
-                            }
-                            Some(1005) => buf.terminal_state.mouse_mode = MouseMode::ExtendedMode,
-                            Some(1006) => {
-                                buf.terminal_state.mouse_mode = MouseMode::SGRExtendedMode;
+                            Some(1004) => buf.terminal_state.focus_event_mode = true,
+                            Some(1007) => buf.terminal_state.alternate_scroll_mode = true,
+                            Some(mode @ (1005 | 1006 | 1015 | 1016)) => {
+                                if let Some(encoding) = MouseEncoding::from_mode(*mode) {
+                                    buf.terminal_state.mouse_encoding = encoding;
+                                }
                             }
-                            Some(1015) => {
-                                buf.terminal_state.mouse_mode = MouseMode::URXVTExtendedMode;
+
+                            Some(47 | 1047) => buf.enter_alternate_screen(current_layer, caret, false),
+                            Some(2004) => buf.terminal_state.bracketed_paste_mode = true,
+                            Some(1049) => {
+                                self.saved_cursor_opt = Some((caret.clone(), self.charsets.clone()));
+                                buf.enter_alternate_screen(current_layer, caret, true);
                             }
-                            Some(1016) => buf.terminal_state.mouse_mode = MouseMode::PixelPosition,
 
                             Some(cmd) => {
                                 return Err(ParserError::UnsupportedCustomCommand(*cmd).into());
@@ -473,6 +635,17 @@ impl BufferParser for Parser {
                     ';' => {
                         self.parsed_numbers.push(0);
                     }
+                    '$' => {
+                        self.state = EngineState::EndPrivateCSI('$');
+                    }
+                    'J' => {
+                        self.check_conformance_level(2)?;
+                        return self.selective_erase_in_display(buf, current_layer, caret);
+                    }
+                    'K' => {
+                        self.check_conformance_level(2)?;
+                        return self.selective_erase_in_line(buf, current_layer, caret);
+                    }
                     'n' => {
                         self.state = EngineState::Default;
                         match self.parsed_numbers.first() {
@@ -541,6 +714,9 @@ impl BufferParser for Parser {
                             Some(2) => {
                                 // font mode report
                                 let mut mode_report = "\x1B[=2".to_string();
+                                if buf.terminal_state.application_cursor_keys {
+                                    mode_report.push_str(";1");
+                                }
                                 if buf.terminal_state.origin_mode == OriginMode::WithinMargins {
                                     mode_report.push_str(";6");
                                 }
@@ -559,18 +735,31 @@ impl BufferParser for Parser {
                                     mode_report.push_str(";35");
                                 }
                                 match buf.terminal_state.mouse_mode {
-                                    MouseMode::Default => {}
                                     MouseMode::X10 => mode_report.push_str(";9"),
                                     MouseMode::VT200 => mode_report.push_str(";1000"),
                                     MouseMode::VT200_Highlight => mode_report.push_str(";1001"),
                                     MouseMode::ButtonEvents => mode_report.push_str(";1002"),
                                     MouseMode::AnyEvents => mode_report.push_str(";1003"),
-                                    MouseMode::FocusEvent => mode_report.push_str(";1004"),
-                                    MouseMode::AlternateScroll => mode_report.push_str(";1007"),
-                                    MouseMode::ExtendedMode => mode_report.push_str(";1005"),
-                                    MouseMode::SGRExtendedMode => mode_report.push_str(";1006"),
-                                    MouseMode::URXVTExtendedMode => mode_report.push_str(";1015"),
-                                    MouseMode::PixelPosition => mode_report.push_str(";1016"),
+                                    _ => {}
+                                }
+                                match buf.terminal_state.mouse_encoding {
+                                    MouseEncoding::Default => {}
+                                    MouseEncoding::Utf8 => mode_report.push_str(";1005"),
+                                    MouseEncoding::Sgr => mode_report.push_str(";1006"),
+                                    MouseEncoding::Urxvt => mode_report.push_str(";1015"),
+                                    MouseEncoding::SgrPixels => mode_report.push_str(";1016"),
+                                }
+                                if buf.terminal_state.alternate_scroll_mode {
+                                    mode_report.push_str(";1007");
+                                }
+                                if buf.terminal_state.focus_event_mode {
+                                    mode_report.push_str(";1004");
+                                }
+                                if buf.is_alternate_screen_active() {
+                                    mode_report.push_str(";1049");
+                                }
+                                if buf.terminal_state.bracketed_paste_mode {
+                                    mode_report.push_str(";2004");
                                 }
 
                                 if mode_report.len() == "\x1B[=2".len() {
@@ -601,6 +790,7 @@ impl BufferParser for Parser {
                         self.parsed_numbers.push(0);
                     }
                     'r' => return self.reset_margins(buf),
+                    'c' => return self.tertiary_device_attributes(),
                     'm' => {
                         if self.parsed_numbers.len() != 2 {
                             return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
@@ -615,6 +805,24 @@ impl BufferParser for Parser {
                 }
             }
 
+            EngineState::ReadCSISecondaryRequest => {
+                self.current_escape_sequence.push(ch);
+                match ch {
+                    '0'..='9' => {
+                        let d = self.parsed_numbers.pop().unwrap_or_default();
+                        self.parsed_numbers.push(parse_next_number(d, ch as u8));
+                    }
+                    ';' => {
+                        self.parsed_numbers.push(0);
+                    }
+                    'c' => return self.secondary_device_attributes(),
+                    _ => {
+                        self.state = EngineState::Default;
+                        return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
+                    }
+                }
+            }
+
             EngineState::ReadRIPSupportRequest => {
                 self.current_escape_sequence.push(ch);
                 if let 'p' = ch {
@@ -671,13 +879,23 @@ impl BufferParser for Parser {
                 self.current_escape_sequence.push(ch);
                 match *func {
                     '*' => match ch {
+                        'x' | 'y' if self.conformance_level.vt_level() < 4 => {
+                            return Err(self.unsupported_by_conformance_level());
+                        }
                         'z' => return self.invoke_macro(buf, current_layer, caret),
                         'r' => return self.select_communication_speed(buf),
                         'y' => return self.request_checksum_of_rectangular_area(buf),
+                        'x' => return self.select_attribute_change_extent(buf),
                         _ => {}
                     },
 
                     '$' => match ch {
+                        'x' | 'z' | '{' | 'v' | 'r' | 't' if self.conformance_level.vt_level() < 4 => {
+                            return Err(self.unsupported_by_conformance_level());
+                        }
+                        'p' if self.conformance_level.vt_level() < 3 => {
+                            return Err(self.unsupported_by_conformance_level());
+                        }
                         'w' => {
                             self.state = EngineState::Default;
                             if let Some(2) = self.parsed_numbers.first() {
@@ -696,10 +914,23 @@ impl BufferParser for Parser {
                         'x' => return self.fill_rectangular_area(buf, caret),
                         'z' => return self.erase_rectangular_area(buf),
                         '{' => return self.selective_erase_rectangular_area(buf),
+                        'v' => return self.copy_rectangular_area(buf),
+                        'r' => return self.change_attributes_in_rectangular_area(buf),
+                        't' => return self.reverse_attributes_in_rectangular_area(buf),
+                        'p' => return self.request_mode(buf, caret, false),
 
                         _ => {}
                     },
 
+                    '"' => {
+                        if ch == 'q' {
+                            self.check_conformance_level(2)?;
+                            return self.select_character_protection(caret);
+                        }
+                        self.state = EngineState::Default;
+                        return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
+                    }
+
                     ' ' => {
                         self.state = EngineState::Default;
 
@@ -708,6 +939,7 @@ impl BufferParser for Parser {
                             'A' => self.scroll_right(buf, current_layer),
                             '@' => self.scroll_left(buf, current_layer),
                             'd' => return self.tabulation_stop_remove(buf),
+                            'q' => return self.set_cursor_style(caret),
                             _ => {
                                 self.current_escape_sequence.push(ch);
                                 return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
@@ -720,8 +952,22 @@ impl BufferParser for Parser {
                     }
                 }
             }
+            EngineState::EndPrivateCSI(func) => {
+                self.current_escape_sequence.push(ch);
+                if *func == '$' && ch == 'p' {
+                    self.check_conformance_level(3)?;
+                    return self.request_mode(buf, caret, true);
+                }
+                self.state = EngineState::Default;
+                return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
+            }
             EngineState::ReadCSISequence(is_start) => {
                 self.current_escape_sequence.push(ch);
+                if !self.sub_parameters.is_empty() && !matches!(ch, '0'..='9' | ';' | ':' | 'm') {
+                    // colon separated sub parameters are only defined for SGR
+                    self.state = EngineState::Default;
+                    return Err(ParserError::UnsupportedEscapeSequence(self.current_escape_sequence.clone()).into());
+                }
                 match ch {
                     'm' => return self.select_graphic_rendition(caret, buf),
                     'H' |    // Cursor Position
@@ -1086,9 +1332,14 @@ impl BufferParser for Parser {
                                 1 => {
                                     buf.clear_buffer_up(current_layer,caret);
                                 }
-                                2 |  // clear entire screen
+                                2 => {
+                                    // clear entire screen
+                                    buf.clear_screen(current_layer,caret);
+                                }
                                 3 => {
+                                    // clear entire screen & scrollback
                                     buf.clear_screen(current_layer,caret);
+                                    buf.scrollback.clear();
                                 }
                                 _ => {
                                     buf.clear_buffer_down(current_layer,caret);
@@ -1143,6 +1394,15 @@ impl BufferParser for Parser {
                         self.state = EngineState::ReadDeviceAttrs;
                         return Ok(CallbackAction::NoUpdate);
                     }
+                    '>' => {
+                        if !is_start {
+                            return Err(ParserError::UnsupportedEscapeSequence(
+                                self.current_escape_sequence.clone(),
+                            ).into());
+                        }
+                        self.state = EngineState::ReadCSISecondaryRequest;
+                        return Ok(CallbackAction::NoUpdate);
+                    }
 
                     '*' => {
                         self.state = EngineState::EndCSI('*');
@@ -1153,6 +1413,9 @@ impl BufferParser for Parser {
                     ' ' => {
                         self.state = EngineState::EndCSI(' ');
                     }
+                    '"' => {
+                        self.state = EngineState::EndCSI('"');
+                    }
 
                     'K' => {
                         // Erase in line
@@ -1295,7 +1558,7 @@ impl BufferParser for Parser {
                         } else {
                             1
                         };
-                        let ch = AttributedChar::new(self.last_char, caret.get_attribute());
+                        let ch = AttributedChar::new(crate::get_cell_char(self.last_char), caret.get_attribute());
                         (0..num).for_each(|_| buf.print_char(current_layer, caret, ch));
                         return Ok(CallbackAction::Update);
                     }
@@ -1380,6 +1643,12 @@ impl BufferParser for Parser {
                             self.parsed_numbers.push(parse_next_number(d, ch as u8));
                         } else if ch == ';' {
                             self.parsed_numbers.push(0);
+                        } else if ch == ':' {
+                            if self.parsed_numbers.is_empty() {
+                                self.parsed_numbers.push(0);
+                            }
+                            self.sub_parameters.push(self.parsed_numbers.len());
+                            self.parsed_numbers.push(0);
                         } else {
                             self.state = EngineState::Default;
                             // error in control sequence, terminate reading
@@ -1417,14 +1686,25 @@ impl BufferParser for Parser {
                     caret.del(buf, current_layer);
                     return Ok(CallbackAction::Update);
                 }
+                // SO/SI & the 8 bit single shifts are printable chars in CP437
+                SO | SI | '\u{8E}' | '\u{8F}' if buf.buffer_type == BufferType::Unicode => {
+                    match ch {
+                        SO => self.charsets.locking_shift(1),
+                        SI => self.charsets.locking_shift(0),
+                        '\u{8E}' => self.charsets.single_shift(2),
+                        _ => self.charsets.single_shift(3),
+                    }
+                    return Ok(CallbackAction::NoUpdate);
+                }
                 _ => {
+                    let ch = self.translate_char(buf, ch);
                     if ch == crate::BS && self.bs_is_ctrl_char {
                         caret.bs(buf, current_layer);
                     } else if (ch == '\x00' || ch == '\u{00FF}') && self.bs_is_ctrl_char {
                         caret.reset_color_attribute();
-                    } else {
+                    } else if !buf.print_combining_char(current_layer, caret, ch) {
                         self.last_char = ch;
-                        let ch = AttributedChar::new(self.last_char, caret.get_attribute());
+                        let ch = AttributedChar::new(crate::get_cell_char(ch), caret.get_attribute());
                         buf.print_char(current_layer, caret, ch);
                     }
                     return Ok(CallbackAction::Update);
@@ -1437,6 +1717,21 @@ impl BufferParser for Parser {
 }
 
 impl Parser {
+    /// Maps a printed char through the invoked character set.
+    fn translate_char(&mut self, buf: &Buffer, ch: char) -> char {
+        let translated = self.charsets.translate(ch);
+        if translated == ch || buf.buffer_type == BufferType::Unicode {
+            return translated;
+        }
+        // chars that aren't part of CP437 are printed untranslated
+        let cp437 = CP437Converter::default().convert_from_unicode(translated, 0);
+        if (cp437 as u32) < 0x100 {
+            cp437
+        } else {
+            ch
+        }
+    }
+
     fn invoke_macro_by_id(&mut self, buf: &mut Buffer, current_layer: usize, caret: &mut Caret, id: i32) {
         let m = if let Some(m) = self.macros.get(&(id as usize)) {
             m.clone()
@@ -1450,8 +1745,28 @@ impl Parser {
         }
     }
 
-    fn execute_aps_command(&self, _buf: &mut Buffer, _caret: &mut Caret) {
+    fn execute_aps_command(&mut self, buf: &mut Buffer, current_layer: usize, caret: &mut Caret) -> EngineResult<CallbackAction> {
+        if self.parse_string.starts_with('G') {
+            self.check_xterm_extensions()?;
+            return self.execute_kitty_graphics(buf, current_layer, caret);
+        }
         log::warn!("TODO execute APS command: {}", self.parse_string);
+        Ok(CallbackAction::NoUpdate)
+    }
+}
+
+/// Displays a picture at the caret. Moving the caret puts it behind the picture on its last line.
+fn place_picture(buf: &mut Buffer, current_layer: usize, caret: &mut Caret, mut sixel: Sixel, move_caret: bool) {
+    sixel.position = caret.get_position();
+    let cells = sixel.as_rectangle(buf.get_font_dimensions());
+    buf.add_sixel(current_layer, sixel);
+
+    if move_caret {
+        let x = caret.get_position().x + cells.get_width();
+        for _ in 1..cells.get_height() {
+            caret.lf(buf, current_layer);
+        }
+        caret.set_x_position(x);
     }
 }
 
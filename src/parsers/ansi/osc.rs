@@ -1,13 +1,80 @@
-use regex::Regex;
+use base64::{engine::general_purpose, Engine};
 
-use crate::{Buffer, CallbackAction, Caret, EngineResult, ParserError};
+use crate::{Buffer, CallbackAction, Caret, Color, EngineResult, ParserError, Sixel, Size, DOS_DEFAULT_PALETTE, XTERM_256_PALETTE};
+
+use super::{parse_next_number, place_picture, ClipboardAccess, Parser};
+
+/// The default foreground & background are the palette entries of the default attribute.
+const DEFAULT_FOREGROUND: u32 = 7;
+const DEFAULT_BACKGROUND: u32 = 0;
 
-use super::{parse_next_number, Parser};
-lazy_static::lazy_static! {
-    static ref OSC_PALETTE: Regex = Regex::new(r"(\d+)?;[rR][gG][bB]:([0-9a-fA-F]{2})/([0-9a-fA-F]{2})/([0-9a-fA-F]{2})").unwrap();
-}
 impl Parser {
-    pub(super) fn parse_osc(&mut self, buf: &mut Buffer, caret: &mut Caret) -> EngineResult<CallbackAction> {
+    pub(super) fn parse_osc(&mut self, buf: &mut Buffer, current_layer: usize, caret: &mut Caret) -> EngineResult<CallbackAction> {
+        // the text arguments may start with digits - so they're handled before the numbers are parsed
+        let (command, text) = self.parse_string.split_once(';').unwrap_or((&self.parse_string, ""));
+        match command {
+            "0" => {
+                buf.terminal_state.title = text.to_string();
+                buf.terminal_state.icon_name = text.to_string();
+                return Ok(CallbackAction::SetTitle(text.to_string()));
+            }
+            "1" => {
+                buf.terminal_state.icon_name = text.to_string();
+                return Ok(CallbackAction::NoUpdate);
+            }
+            "2" => {
+                buf.terminal_state.title = text.to_string();
+                return Ok(CallbackAction::SetTitle(text.to_string()));
+            }
+            "4" => {
+                return self.handle_osc_palette(text, buf);
+            }
+            "7" => {
+                buf.terminal_state.working_directory = Some(text.to_string());
+                return Ok(CallbackAction::NoUpdate);
+            }
+            "10" | "11" | "12" => {
+                return self.handle_osc_dynamic_colors(command.parse::<u32>()?, text, buf);
+            }
+            "52" => {
+                return self.handle_osc_clipboard(text);
+            }
+            "104" => {
+                // slots above 15 are allocated on demand for SGR 38/48 colors - a bare reset must not touch them
+                // and slots that don't exist yet are never created.
+                if text.is_empty() {
+                    let len = buf.palette.len();
+                    for (color, default) in DOS_DEFAULT_PALETTE.iter().enumerate().take(len) {
+                        buf.palette.set_color(color as u32, default.clone());
+                    }
+                }
+                for color in text.split(';').filter_map(|n| n.parse::<usize>().ok()) {
+                    if color >= buf.palette.len() {
+                        continue;
+                    }
+                    if let Some(default) = get_default_color(color) {
+                        buf.palette.set_color(color as u32, default);
+                    }
+                }
+                return Ok(CallbackAction::Update);
+            }
+            "110" => {
+                buf.palette
+                    .set_color(DEFAULT_FOREGROUND, DOS_DEFAULT_PALETTE[DEFAULT_FOREGROUND as usize].clone());
+                return Ok(CallbackAction::Update);
+            }
+            "111" => {
+                buf.palette
+                    .set_color(DEFAULT_BACKGROUND, DOS_DEFAULT_PALETTE[DEFAULT_BACKGROUND as usize].clone());
+                return Ok(CallbackAction::Update);
+            }
+            "112" => {
+                buf.terminal_state.cursor_color = None;
+                return Ok(CallbackAction::Update);
+            }
+            _ => {}
+        }
+
         let mut i = 0;
         for ch in self.parse_string.chars() {
             match ch {
@@ -28,29 +95,115 @@ impl Parser {
             i += 1;
         }
 
-        if !self.parsed_numbers.is_empty() && self.parsed_numbers[0] == 4 {
-            for a in OSC_PALETTE.captures_iter(&self.parse_string) {
-                let color = a.get(1).unwrap().as_str().parse::<u32>()?;
-                if color > 255 {
-                    log::error!("Invalid color index: {}", color);
-                    continue;
-                }
-                let r = u8::from_str_radix(a.get(2).unwrap().as_str(), 16)?;
-                let g = u8::from_str_radix(a.get(3).unwrap().as_str(), 16)?;
-                let b = u8::from_str_radix(a.get(4).unwrap().as_str(), 16)?;
-                buf.palette.set_color_rgb(color, r, g, b);
-            }
-            return Ok(CallbackAction::Update);
-        }
-
         if i == 3 && *self.parsed_numbers.first().unwrap() == 8 {
             self.handle_osc_hyperlinks(self.parse_string[3..].to_string(), buf, caret);
             return Ok(CallbackAction::NoUpdate);
         }
 
+        if self.parsed_numbers.first() == Some(&1337) {
+            if let Some(file) = self.parse_string[i..].strip_prefix("File=") {
+                let file = file.to_string();
+                return self.handle_iterm2_inline_image(&file, buf, current_layer, caret);
+            }
+        }
+
         Err(ParserError::UnsupportedOSCSequence(self.parse_string.clone()).into())
     }
 
+    /// OSC 4 ; <index> ; <color spec | ?> [; <index> ; <color spec | ?>]...
+    fn handle_osc_palette(&self, text: &str, buf: &mut Buffer) -> EngineResult<CallbackAction> {
+        let mut response = Vec::new();
+        let mut args = text.split(';');
+        while let (Some(color), Some(spec)) = (args.next(), args.next()) {
+            let Ok(color) = color.parse::<u32>() else {
+                return Err(ParserError::UnsupportedOSCSequence(self.parse_string.clone()).into());
+            };
+            if color > 255 {
+                log::error!("Invalid color index: {color}");
+                continue;
+            }
+            if spec == "?" {
+                // slots that aren't allocated yet still have the xterm default
+                let value = if (color as usize) < buf.palette.len() {
+                    buf.palette.get_color(color)
+                } else {
+                    get_default_color(color as usize).unwrap_or_default()
+                };
+                response.push(format!("\x1B]4;{color};{}\x1B\\", to_color_spec(&value)));
+            } else if let Some(c) = parse_color_spec(spec) {
+                buf.palette.set_color(color, c);
+            } else {
+                log::error!("Invalid color spec: {spec}");
+            }
+        }
+        if response.is_empty() {
+            Ok(CallbackAction::Update)
+        } else {
+            Ok(CallbackAction::SendString(response.concat()))
+        }
+    }
+
+    /// OSC 10/11/12 ; <color spec | ?> - each following argument addresses the next dynamic color.
+    fn handle_osc_dynamic_colors(&self, command: u32, text: &str, buf: &mut Buffer) -> EngineResult<CallbackAction> {
+        let mut response = Vec::new();
+        for (command, spec) in (command..=12).zip(text.split(';')) {
+            if spec == "?" {
+                let color = match command {
+                    10 => buf.palette.get_color(DEFAULT_FOREGROUND),
+                    11 => buf.palette.get_color(DEFAULT_BACKGROUND),
+                    _ => buf
+                        .terminal_state
+                        .cursor_color
+                        .clone()
+                        .unwrap_or_else(|| buf.palette.get_color(DEFAULT_FOREGROUND)),
+                };
+                response.push(format!("\x1B]{command};{}\x1B\\", to_color_spec(&color)));
+                continue;
+            }
+            let Some(color) = parse_color_spec(spec) else {
+                return Err(ParserError::UnsupportedOSCSequence(self.parse_string.clone()).into());
+            };
+            match command {
+                10 => buf.palette.set_color(DEFAULT_FOREGROUND, color),
+                11 => buf.palette.set_color(DEFAULT_BACKGROUND, color),
+                _ => buf.terminal_state.cursor_color = Some(color),
+            }
+        }
+        if response.is_empty() {
+            Ok(CallbackAction::Update)
+        } else {
+            Ok(CallbackAction::SendString(response.concat()))
+        }
+    }
+
+    /// OSC 52 ; <selection> ; <base64 data | ?>
+    fn handle_osc_clipboard(&self, text: &str) -> EngineResult<CallbackAction> {
+        if !self.conformance_level.has_xterm_extensions() {
+            return Err(ParserError::UnsupportedOSCSequence(self.parse_string.clone()).into());
+        }
+        let Some((selection, data)) = text.split_once(';') else {
+            return Err(ParserError::UnsupportedOSCSequence(self.parse_string.clone()).into());
+        };
+        if data == "?" {
+            if self.clipboard_access == ClipboardAccess::ReadWrite {
+                // like xterm an empty selection means "s0", unknown selection chars are ignored
+                let mut selection: String = selection.chars().filter(|ch| matches!(ch, 'c' | 'p' | 'q' | 's' | '0'..='7')).collect();
+                if selection.is_empty() {
+                    selection.push_str("s0");
+                }
+                return Ok(CallbackAction::QueryClipboard(selection));
+            }
+            return Ok(CallbackAction::NoUpdate);
+        }
+        if self.clipboard_access == ClipboardAccess::Disabled {
+            return Ok(CallbackAction::NoUpdate);
+        }
+        let Ok(data) = general_purpose::STANDARD.decode(data) else {
+            return Err(ParserError::UnsupportedOSCSequence(self.parse_string.clone()).into());
+        };
+        Ok(CallbackAction::SetClipboard(String::from_utf8_lossy(&data).to_string()))
+    }
+
     fn handle_osc_hyperlinks(&mut self, parse_string: impl Into<String>, buf: &mut Buffer, caret: &mut Caret) {
         let url = parse_string.into();
         if url.is_empty() {
@@ -72,4 +225,140 @@ impl Parser {
             });
         }
     }
+
+    /// iTerm2 inline images: `OSC 1337 ; File = [arguments] : base64 data ST`
+    fn handle_iterm2_inline_image(&mut self, file: &str, buf: &mut Buffer, current_layer: usize, caret: &mut Caret) -> EngineResult<CallbackAction> {
+        if !self.conformance_level.has_xterm_extensions() {
+            return Err(ParserError::UnsupportedOSCSequence(self.parse_string.clone()).into());
+        }
+        let Some((arguments, data)) = file.split_once(':') else {
+            return Err(ParserError::UnsupportedOSCSequence(self.parse_string.clone()).into());
+        };
+        let mut width = None;
+        let mut height = None;
+        let mut preserve_aspect_ratio = true;
+        let mut inline = false;
+        let font_dims = buf.get_font_dimensions();
+        for argument in arguments.split(';') {
+            let Some((key, value)) = argument.split_once('=') else {
+                continue;
+            };
+            match key {
+                "width" => width = parse_image_dimension(value, font_dims.width, buf.terminal_state.get_width()),
+                "height" => height = parse_image_dimension(value, font_dims.height, buf.terminal_state.get_height()),
+                "preserveAspectRatio" => preserve_aspect_ratio = value != "0",
+                "inline" => inline = value == "1",
+                _ => {}
+            }
+        }
+        if !inline {
+            // file downloads aren't supported
+            return Ok(CallbackAction::NoUpdate);
+        }
+
+        let data: String = data.chars().filter(|ch| !ch.is_ascii_whitespace()).collect();
+        let data = general_purpose::STANDARD_NO_PAD.decode(data.trim_end_matches('='))?;
+        let mut sixel = Sixel::from_png(&data)?;
+
+        let image_size = sixel.get_size();
+        if image_size.width > 0 && image_size.height > 0 && (width.is_some() || height.is_some()) {
+            // a scaled image can't get larger than the terminal screen
+            let max_width = buf.terminal_state.get_width().saturating_mul(font_dims.width).max(1);
+            let max_height = buf.terminal_state.get_height().saturating_mul(font_dims.height).max(1);
+            let width = width.map(|width| width.min(max_width));
+            let height = height.map(|height| height.min(max_height));
+            // the image sizes may be large, calculate in 64 bits
+            let scale = |value: i32, numerator: i32, denominator: i32| i64::from(value) * i64::from(numerator) / i64::from(denominator);
+            let (new_width, new_height) = match (width, height) {
+                (None, None) => (i64::from(image_size.width), i64::from(image_size.height)),
+                (Some(width), None) => (i64::from(width), scale(image_size.height, width, image_size.width)),
+                (None, Some(height)) => (scale(image_size.width, height, image_size.height), i64::from(height)),
+                (Some(width), Some(height)) if preserve_aspect_ratio => {
+                    // fit the image into the box
+                    if i64::from(width) * i64::from(image_size.height) < i64::from(height) * i64::from(image_size.width) {
+                        (i64::from(width), scale(image_size.height, width, image_size.width))
+                    } else {
+                        (scale(image_size.width, height, image_size.height), i64::from(height))
+                    }
+                }
+                (Some(width), Some(height)) => (i64::from(width), i64::from(height)),
+            };
+            sixel.resize(Size::new(
+                new_width.clamp(1, i64::from(max_width)) as i32,
+                new_height.clamp(1, i64::from(max_height)) as i32,
+            ));
+        }
+
+        place_picture(buf, current_layer, caret, sixel, true);
+        Ok(CallbackAction::Update)
+    }
+}
+
+/// Parses an iTerm2 image dimension in pixels: `N` cells, `Npx` pixels, `N%` of the terminal size or `auto`.
+/// Sizes that overflow are ignored like `auto`.
+fn parse_image_dimension(value: &str, cell_size: i32, terminal_cells: i32) -> Option<i32> {
+    if let Some(pixels) = value.strip_suffix("px") {
+        pixels.parse().ok()
+    } else if let Some(percent) = value.strip_suffix('%') {
+        percent
+            .parse::<i32>()
+            .ok()
+            .and_then(|percent| terminal_cells.checked_mul(cell_size)?.checked_mul(percent))
+            .map(|size| size / 100)
+    } else {
+        value.parse::<i32>().ok().and_then(|cells| cells.checked_mul(cell_size))
+    }
+    .filter(|size| *size > 0)
+}
+
+/// The xterm default of a palette entry - used for OSC 104 ; <index> resets and for queries of unallocated entries.
+/// A bare OSC 104 only resets the 16 DOS colors.
+fn get_default_color(color: usize) -> Option<Color> {
+    if color < DOS_DEFAULT_PALETTE.len() {
+        return Some(DOS_DEFAULT_PALETTE[color].clone());
+    }
+    XTERM_256_PALETTE.get(color).map(|(_, c)| c.clone())
+}
+
+/// Parses a xterm color spec: `rgb:r/g/b` with 1-4 hex digits per channel or `#rgb`, `#rrggbb`.
+fn parse_color_spec(spec: &str) -> Option<Color> {
+    // the spec is sliced by bytes below
+    if !spec.is_ascii() {
+        return None;
+    }
+    if let Some(hex) = spec.strip_prefix('#') {
+        let len = match hex.len() {
+            3 | 6 | 9 | 12 => hex.len() / 3,
+            _ => return None,
+        };
+        let channel = |i: usize| scale_channel(&hex[i * len..(i + 1) * len]);
+        return Some(Color::new(channel(0)?, channel(1)?, channel(2)?));
+    }
+    if spec.len() < 4 || !spec[..4].eq_ignore_ascii_case("rgb:") {
+        return None;
+    }
+    let mut channels = spec[4..].split('/');
+    let r = scale_channel(channels.next()?)?;
+    let g = scale_channel(channels.next()?)?;
+    let b = scale_channel(channels.next()?)?;
+    if channels.next().is_some() {
+        return None;
+    }
+    Some(Color::new(r, g, b))
+}
+
+/// Scales a channel of 1-4 hex digits to 8 bit.
+fn scale_channel(hex: &str) -> Option<u8> {
+    if hex.is_empty() || hex.len() > 4 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    let max = (1 << (4 * hex.len())) - 1;
+    Some((value * 255 / max) as u8)
+}
+
+/// The xterm reply format for colors: `rgb:rrrr/gggg/bbbb`
+fn to_color_spec(color: &Color) -> String {
+    let (r, g, b) = color.get_rgb();
+    format!("rgb:{r:02x}{r:02x}/{g:02x}{g:02x}/{b:02x}{b:02x}")
 }
@@ -1,9 +1,13 @@
 #![allow(clippy::float_cmp)]
 
+use base64::{engine::general_purpose, Engine};
+
 use crate::{
-    ansi::{sound::MusicAction, BaudEmulation, MusicOption},
+    ansi::{sound::MusicAction, BaudEmulation, ClipboardAccess, ConformanceLevel, MusicOption},
     parsers::{ansi, create_buffer, get_action, get_simple_action, update_buffer, update_buffer_force},
-    AttributedChar, CallbackAction, Caret, Color, IceMode, OutputFormat, Position, SaveOptions, TerminalScrolling, TextAttribute, TextPane, XTERM_256_PALETTE,
+    AttributedChar, AutoWrapMode, Buffer, BufferParser, BufferType, CallbackAction, Caret, CaretShape, Color, IceMode, LineSize, MouseButton, MouseEncoding,
+    MouseEvent, MouseEventType, MouseMode, OriginMode, OutputFormat, Position, SaveOptions, TerminalScrolling, TextAttribute, TextPane, UnderlineStyle,
+    DOS_DEFAULT_PALETTE, XTERM_256_PALETTE,
 };
 
 #[test]
@@ -1144,3 +1148,1055 @@ fn test_load_palette_case2() {
     assert_eq!(buf.palette.get_rgb(17), (0x00, 0x11, 0x22));
     assert_eq!(buf.palette.get_rgb(255), (0x01, 0xef, 0x2d));
 }
+
+#[test]
+fn test_scroll_up_fills_scrollback() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B]8;;http://example.com\x1B\\1\x1B]8;;\x1B\\\r\n2\r\n3");
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[2S");
+
+    assert_eq!('3', buf.get_char((0, 0)).ch);
+    assert_eq!(2, buf.scrollback.len());
+    let line = buf.scrollback.get_line(0).unwrap();
+    assert_eq!('1', line.get_char(0).ch);
+    assert_eq!(1, line.hyperlinks.len());
+    assert_eq!(Some("http://example.com".to_string()), line.hyperlinks[0].url);
+    assert_eq!('2', buf.scrollback.get_line(1).unwrap().get_char(0).ch);
+    assert!(buf.layers[0].hyperlinks().is_empty());
+}
+
+#[test]
+fn test_scroll_region_keeps_scrollback() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[5;10r\x1B[5;1H1\x1B[S");
+    assert!(buf.scrollback.is_empty());
+    assert_eq!(' ', buf.get_char((0, 4)).ch);
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[r\x1B[1;1H2\x1B[S");
+    assert_eq!(1, buf.scrollback.len());
+}
+
+#[test]
+fn test_erase_scrollback() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"1\x1B[S");
+    assert_eq!(1, buf.scrollback.len());
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[3J");
+    assert!(buf.scrollback.is_empty());
+}
+
+#[test]
+fn test_alternate_screen() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"main\x1B[2;3H");
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?1049h");
+    assert!(buf.is_alternate_screen_active());
+    assert_eq!(' ', buf.get_char((0, 0)).ch);
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[1;1Halt");
+    assert_eq!('a', buf.get_char((0, 0)).ch);
+
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[=2n");
+    assert_eq!(CallbackAction::SendString("\x1B[=2;7;25;35;1049n".to_string()), act);
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?1049l");
+    assert!(!buf.is_alternate_screen_active());
+    assert_eq!('m', buf.get_char((0, 0)).ch);
+    assert_eq!(Position::new(2, 1), caret.get_position());
+}
+
+#[test]
+fn test_alternate_screen_keeps_contents() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"main\x1B[?47h\x1B[1;1Halt\x1B[?47l");
+    assert_eq!('m', buf.get_char((0, 0)).ch);
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?47h");
+    assert_eq!('a', buf.get_char((0, 0)).ch);
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?1047l\x1B[?47h");
+    assert_eq!(' ', buf.get_char((0, 0)).ch);
+}
+
+#[test]
+fn test_alternate_screen_does_not_grow() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[?1049h");
+    for i in 0..30 {
+        update_buffer(&mut buf, &mut caret, &mut parser, format!("{}\r\n", i % 10).as_bytes());
+    }
+    assert_eq!(25, buf.get_height());
+    assert!(buf.scrollback.is_empty());
+    assert_eq!('9', buf.get_char((0, 23)).ch);
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?1049l");
+    assert_eq!(' ', buf.get_char((0, 0)).ch);
+}
+
+#[test]
+fn test_alternate_screen_after_scrolling() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    buf.is_terminal_buffer = true;
+    for i in 0..40 {
+        update_buffer(&mut buf, &mut caret, &mut parser, format!("{}\r\n", i % 10).as_bytes());
+    }
+    assert_eq!(41, buf.get_height());
+    assert_eq!(Position::new(0, 40), caret.get_position());
+
+    // the caret keeps its screen row
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?1047h");
+    assert_eq!(Position::new(0, 24), caret.get_position());
+    update_buffer(&mut buf, &mut caret, &mut parser, b"x");
+    assert_eq!('x', buf.get_char((0, 24)).ch);
+    assert_eq!(25, buf.get_height());
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[5;1H");
+    assert_eq!(Position::new(0, 4), caret.get_position());
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?1047l");
+    assert_eq!(41, buf.get_height());
+    assert_eq!(Position::new(0, 20), caret.get_position());
+    assert_eq!('0', buf.get_char((0, 20)).ch);
+}
+
+#[test]
+fn test_bracketed_paste() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    assert_eq!("foo\rbar\r", buf.terminal_state.encode_paste("foo\r\nbar\n"));
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?2004h");
+    assert!(buf.terminal_state.bracketed_paste_mode);
+    assert_eq!("\x1B[200~foo\rbar\x1B[201~", buf.terminal_state.encode_paste("foo\nbar"));
+    assert_eq!("\x1B[200~foo[201~\x1B[201~", buf.terminal_state.encode_paste("foo\x1B[201~"));
+    // removing the end marker once would form a new one
+    assert_eq!(
+        "\x1B[200~[20[201~1~rm -rf ~\r\x1B[201~",
+        buf.terminal_state.encode_paste("\x1B[20\x1B[201~1~rm -rf ~\n")
+    );
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?2004l");
+    assert!(!buf.terminal_state.bracketed_paste_mode);
+}
+
+#[test]
+fn test_focus_events() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[?1006h");
+    assert_eq!(None, buf.terminal_state.encode_focus_event(true));
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?1004h");
+    assert_eq!(MouseEncoding::Sgr, buf.terminal_state.mouse_encoding);
+    assert_eq!(Some("\x1B[I".to_string()), buf.terminal_state.encode_focus_event(true));
+    assert_eq!(Some("\x1B[O".to_string()), buf.terminal_state.encode_focus_event(false));
+
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[?2004h\x1B[=2n");
+    assert_eq!(CallbackAction::SendString("\x1B[=2;7;25;35;1006;1004;2004n".to_string()), act);
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?1004l");
+    assert_eq!(None, buf.terminal_state.encode_focus_event(true));
+    assert_eq!(MouseEncoding::Sgr, buf.terminal_state.mouse_encoding);
+}
+
+#[test]
+fn test_mouse_tracking_and_encoding() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[?1003h\x1B[?1006h");
+    assert_eq!(MouseMode::AnyEvents, buf.terminal_state.mouse_mode);
+    assert_eq!(MouseEncoding::Sgr, buf.terminal_state.mouse_encoding);
+    let motion = MouseEvent::new(MouseEventType::Motion, MouseButton::None, Position::new(0, 0));
+    assert_eq!(Some(b"\x1B[<35;1;1M".to_vec()), buf.terminal_state.encode_mouse_event(&motion));
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?1003l\x1B[?1000h");
+    assert_eq!(MouseMode::VT200, buf.terminal_state.mouse_mode);
+    assert_eq!(MouseEncoding::Sgr, buf.terminal_state.mouse_encoding);
+    let drag = MouseEvent::new(MouseEventType::Motion, MouseButton::Left, Position::new(0, 0));
+    assert_eq!(None, buf.terminal_state.encode_mouse_event(&drag));
+
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[?1006$p");
+    assert_eq!(CallbackAction::SendString("\x1B[?1006;1$y".to_string()), act);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[?1003$p");
+    assert_eq!(CallbackAction::SendString("\x1B[?1003;2$y".to_string()), act);
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?1006l");
+    assert_eq!(MouseEncoding::Default, buf.terminal_state.mouse_encoding);
+    assert_eq!(MouseMode::VT200, buf.terminal_state.mouse_mode);
+}
+
+#[test]
+fn test_cursor_key_mode() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[?1h");
+    assert!(buf.terminal_state.application_cursor_keys);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[=2n");
+    assert_eq!(CallbackAction::SendString("\x1B[=2;1;7;25;35n".to_string()), act);
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?1l");
+    assert!(!buf.terminal_state.application_cursor_keys);
+}
+
+fn print_unicode(parser: &mut ansi::Parser, buf: &mut crate::Buffer, caret: &mut Caret, text: &str) {
+    for ch in text.chars() {
+        parser.print_char(buf, 0, caret, ch).unwrap();
+    }
+}
+
+#[test]
+fn test_wide_chars() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    buf.buffer_type = crate::BufferType::Unicode;
+    print_unicode(&mut parser, &mut buf, &mut caret, "a中b");
+
+    assert_eq!('中', buf.get_char((1, 0)).ch);
+    assert!(buf.get_char((2, 0)).is_wide_continuation());
+    assert_eq!('b', buf.get_char((3, 0)).ch);
+    assert_eq!(4, caret.get_position().x);
+
+    // overwriting one half erases the whole character
+    print_unicode(&mut parser, &mut buf, &mut caret, "\x1B[1;3Hx");
+    assert_eq!(' ', buf.get_char((1, 0)).ch);
+    assert_eq!('x', buf.get_char((2, 0)).ch);
+    assert!(!buf.get_char((2, 0)).is_wide_continuation());
+
+    // a wide character doesn't get split at the end of the line
+    print_unicode(&mut parser, &mut buf, &mut caret, "\x1B[1;80H文");
+    assert_eq!(' ', buf.get_char((79, 0)).ch);
+    assert_eq!('文', buf.get_char((0, 1)).ch);
+    assert!(buf.get_char((1, 1)).is_wide_continuation());
+    assert_eq!(Position::new(2, 1), caret.get_position());
+}
+
+#[test]
+fn test_wide_chars_erase() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    buf.buffer_type = crate::BufferType::Unicode;
+    print_unicode(&mut parser, &mut buf, &mut caret, "中文字\x1B[1;2H\x1B[1X");
+    assert_eq!(' ', buf.get_char((0, 0)).ch);
+    assert!(!buf.get_char((1, 0)).is_wide_continuation());
+    assert_eq!('文', buf.get_char((2, 0)).ch);
+
+    // deleting a character splits the wide char behind the caret
+    print_unicode(&mut parser, &mut buf, &mut caret, "\x1B[1;3H\x1B[P");
+    assert_eq!(' ', buf.get_char((2, 0)).ch);
+    assert!(!buf.get_char((2, 0)).is_wide_continuation());
+    assert_eq!('字', buf.get_char((3, 0)).ch);
+}
+
+#[test]
+fn test_wide_chars_export() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    buf.buffer_type = crate::BufferType::Unicode;
+    print_unicode(&mut parser, &mut buf, &mut caret, "中b");
+
+    let mut opt = SaveOptions::new();
+    opt.modern_terminal_output = true;
+    opt.lossles_output = true;
+    let bytes = buf.to_bytes("ans", &opt).unwrap();
+    let text = String::from_utf8_lossy(&bytes);
+    assert!(text.contains("中b"));
+}
+
+#[test]
+fn test_combining_chars() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    buf.buffer_type = crate::BufferType::Unicode;
+    print_unicode(&mut parser, &mut buf, &mut caret, "e\u{301}x");
+
+    assert_eq!("e\u{301}", buf.get_char((0, 0)).get_text());
+    assert_eq!('x', buf.get_char((1, 0)).ch);
+    assert_eq!(2, caret.get_position().x);
+
+    // marks after a wide char belong to its left half
+    print_unicode(&mut parser, &mut buf, &mut caret, "中\u{302}");
+    assert_eq!("中\u{302}", buf.get_char((2, 0)).get_text());
+    assert!(buf.get_char((3, 0)).is_wide_continuation());
+
+    // marks after a wrap belong to the last char of the previous line
+    print_unicode(&mut parser, &mut buf, &mut caret, "\x1B[1;80Ha\u{308}");
+    assert_eq!("a\u{308}", buf.get_char((79, 0)).get_text());
+    assert_eq!(Position::new(0, 1), caret.get_position());
+}
+
+#[test]
+fn test_combining_chars_limit() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    buf.buffer_type = crate::BufferType::Unicode;
+    let marks = "\u{301}\u{302}".repeat(5000);
+    print_unicode(&mut parser, &mut buf, &mut caret, &format!("o{marks}x"));
+
+    let text = buf.get_char((0, 0)).get_text();
+    assert_eq!(crate::MAX_CLUSTER_CHARS, text.chars().count());
+    assert!(text.starts_with("o\u{301}\u{302}"));
+    assert_eq!('x', buf.get_char((1, 0)).ch);
+}
+
+#[test]
+fn test_zwj_sequence() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    buf.buffer_type = crate::BufferType::Unicode;
+    print_unicode(&mut parser, &mut buf, &mut caret, "👨\u{200D}👩\u{200D}👧!");
+
+    assert_eq!("👨\u{200D}👩\u{200D}👧", buf.get_char((0, 0)).get_text());
+    assert!(buf.get_char((1, 0)).is_wide_continuation());
+    assert_eq!('!', buf.get_char((2, 0)).ch);
+
+    let mut opt = SaveOptions::new();
+    opt.modern_terminal_output = true;
+    opt.lossles_output = true;
+    let bytes = buf.to_bytes("ans", &opt).unwrap();
+    assert!(String::from_utf8_lossy(&bytes).contains("👨\u{200D}👩\u{200D}👧!"));
+}
+
+#[test]
+fn test_underline_styles() {
+    let mut parser = ansi::Parser::default();
+    let (buf, _) = create_buffer(&mut parser, b"\x1B[4:3ma\x1B[4:4mb\x1B[4:5mc\x1B[4:2md\x1B[4:0me\x1B[4;3mf");
+    assert_eq!(UnderlineStyle::Curly, buf.get_char((0, 0)).attribute.get_underline_style());
+    assert_eq!(UnderlineStyle::Dotted, buf.get_char((1, 0)).attribute.get_underline_style());
+    assert_eq!(UnderlineStyle::Dashed, buf.get_char((2, 0)).attribute.get_underline_style());
+    assert_eq!(UnderlineStyle::Double, buf.get_char((3, 0)).attribute.get_underline_style());
+    assert_eq!(UnderlineStyle::None, buf.get_char((4, 0)).attribute.get_underline_style());
+    // semicolons separate parameters: underline + italic
+    assert_eq!(UnderlineStyle::Single, buf.get_char((5, 0)).attribute.get_underline_style());
+    assert!(buf.get_char((5, 0)).attribute.is_italic());
+}
+
+#[test]
+fn test_underline_color() {
+    let mut parser = ansi::Parser::default();
+    let (buf, _) = create_buffer(&mut parser, b"\x1B[4;58;2;255;0;0ma\x1B[58;5;2mb\x1B[59mc\x1B[58;2;1;2;3m\x1B[0md");
+    let color = buf.get_char((0, 0)).attribute.get_underline_color().unwrap();
+    assert_eq!((255, 0, 0), buf.palette.get_color(color).get_rgb());
+    let color = buf.get_char((1, 0)).attribute.get_underline_color().unwrap();
+    assert_eq!(XTERM_256_PALETTE[2].1.get_rgb(), buf.palette.get_color(color).get_rgb());
+    assert_eq!(None, buf.get_char((2, 0)).attribute.get_underline_color());
+    assert_eq!(None, buf.get_char((3, 0)).attribute.get_underline_color());
+}
+
+#[test]
+fn test_underline_export() {
+    let mut parser = ansi::Parser::default();
+    let (buf, _) = create_buffer(&mut parser, b"\x1B[4:3;58;2;255;0;0ma\x1B[4mb");
+
+    let mut opt = SaveOptions::new();
+    opt.modern_terminal_output = true;
+    opt.lossles_output = true;
+    let text = String::from_utf8_lossy(&buf.to_bytes("ans", &opt).unwrap()).to_string();
+    assert!(text.contains("\x1b[4:3m"));
+    assert!(text.contains("58;2;255;0;0"));
+    assert!(text.contains("\x1b[4mb"));
+
+    // classic output falls back to a single underline
+    let mut opt = SaveOptions::new();
+    opt.lossles_output = true;
+    let text = String::from_utf8_lossy(&buf.to_bytes("ans", &opt).unwrap()).to_string();
+    assert!(!text.contains("4:3") && !text.contains("58;"));
+}
+
+#[test]
+fn test_sgr_sub_parameters() {
+    let mut parser = ansi::Parser::default();
+    let (buf, _) = create_buffer(
+        &mut parser,
+        b"\x1B[38:2::255:0:0ma\x1B[38:2:0:255:0mb\x1B[38:2:1:0:0:255mc\x1B[48:5:123md\x1B[38;2;1;2;3me\x1B[38:5:9;4mf\x1B[0;1:2mg",
+    );
+    let fg = |x: i32| buf.palette.get_color(buf.get_char((x, 0)).attribute.get_foreground()).get_rgb();
+    assert_eq!((255, 0, 0), fg(0));
+    assert_eq!((0, 255, 0), fg(1));
+    assert_eq!((0, 0, 255), fg(2));
+    assert_eq!(
+        XTERM_256_PALETTE[123].1.get_rgb(),
+        buf.palette.get_color(buf.get_char((3, 0)).attribute.get_background()).get_rgb()
+    );
+    assert_eq!((1, 2, 3), fg(4));
+
+    // parameters after sub parameters still apply
+    assert_eq!(XTERM_256_PALETTE[9].1.get_rgb(), fg(5));
+    assert!(buf.get_char((5, 0)).attribute.is_underlined());
+
+    // unknown sub parameters are skipped
+    assert!(buf.get_char((6, 0)).attribute.is_bold());
+}
+
+#[test]
+fn test_sub_parameters_only_in_sgr() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    update_buffer_force(&mut buf, &mut caret, &mut parser, b"\x1B[2:5Ha\x1B[1:2Ab");
+    assert_eq!('a', buf.get_char((0, 0)).ch);
+    assert_eq!('b', buf.get_char((1, 0)).ch);
+    assert_eq!(Position::new(2, 0), caret.get_position());
+}
+
+#[test]
+fn test_sgr_sub_parameter_underline_color() {
+    let mut parser = ansi::Parser::default();
+    let (buf, _) = create_buffer(&mut parser, b"\x1B[4:3;58:2::10:20:30ma\x1B[58:5:1mb");
+    let attr = buf.get_char((0, 0)).attribute;
+    assert_eq!(UnderlineStyle::Curly, attr.get_underline_style());
+    assert_eq!((10, 20, 30), buf.palette.get_color(attr.get_underline_color().unwrap()).get_rgb());
+    let attr = buf.get_char((1, 0)).attribute;
+    assert_eq!(
+        XTERM_256_PALETTE[1].1.get_rgb(),
+        buf.palette.get_color(attr.get_underline_color().unwrap()).get_rgb()
+    );
+}
+
+fn iterm2_image(arguments: &str, width: u32, height: u32) -> Vec<u8> {
+    let mut png_data = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_data, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0x11, 0x22, 0x33, 0xFF].repeat((width * height) as usize)).unwrap();
+    }
+    let data = general_purpose::STANDARD.encode(&png_data);
+    format!("\x1B]1337;File={arguments}:{data}\x07").into_bytes()
+}
+
+#[test]
+fn test_iterm2_inline_image() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[2;3H");
+    update_buffer(&mut buf, &mut caret, &mut parser, &iterm2_image("name=dGVzdC5wbmc=;inline=1", 16, 32));
+
+    let sixels = &buf.layers[0].sixels;
+    assert_eq!(1, sixels.len());
+    assert_eq!(Position::new(2, 1), sixels[0].position);
+    assert_eq!(16, sixels[0].get_width());
+    assert_eq!(32, sixels[0].get_height());
+    assert_eq!(&[0x11, 0x22, 0x33, 0xFF], &sixels[0].picture_data[0..4]);
+    assert_eq!(Position::new(4, 2), caret.get_position());
+
+    // without inline=1 the file is a download
+    update_buffer(&mut buf, &mut caret, &mut parser, &iterm2_image("size=10", 16, 32));
+    assert_eq!(1, buf.layers[0].sixels.len());
+}
+
+#[test]
+fn test_iterm2_image_size() {
+    let size_for = |arguments: &str| {
+        let mut parser = ansi::Parser::default();
+        let (buf, _) = create_buffer(&mut parser, &iterm2_image(arguments, 20, 10));
+        buf.layers[0].sixels[0].get_size()
+    };
+    // cells, pixels and percent of the terminal size (80x25 cells of 8x16 pixels)
+    assert_eq!(crate::Size::new(40, 20), size_for("inline=1;width=5"));
+    assert_eq!(crate::Size::new(10, 5), size_for("inline=1;width=10px;height=auto"));
+    assert_eq!(crate::Size::new(320, 160), size_for("inline=1;width=50%"));
+    // the image is fitted into the box unless preserveAspectRatio=0
+    assert_eq!(crate::Size::new(32, 16), size_for("inline=1;width=4;height=2"));
+    assert_eq!(crate::Size::new(32, 100), size_for("inline=1;width=4;height=100px;preserveAspectRatio=0"));
+    // sizes that overflow are ignored, large sizes are limited to the terminal area
+    assert_eq!(crate::Size::new(20, 10), size_for("inline=1;width=999999999999"));
+    assert_eq!(crate::Size::new(640, 320), size_for("inline=1;width=99999999"));
+    assert_eq!(
+        crate::Size::new(20, 400),
+        size_for("inline=1;width=20px;height=2147483647px;preserveAspectRatio=0")
+    );
+}
+
+#[test]
+fn test_picture_size_limit() {
+    let mut sixel = crate::Sixel::new(Position::default());
+    sixel.picture_data = vec![0xFF; 4];
+    sixel.set_width(1);
+    sixel.set_height(1);
+    sixel.resize(crate::Size::new(i32::MAX, i32::MAX));
+    assert_eq!(crate::Size::new(1, 1), sixel.get_size());
+    sixel.resize(crate::Size::new(2, 2));
+    assert_eq!(vec![0xFF; 16], sixel.picture_data);
+}
+
+#[test]
+fn test_osc_title() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]0;2024 Session\x07");
+    assert_eq!(CallbackAction::SetTitle("2024 Session".to_string()), act);
+    assert_eq!("2024 Session", buf.terminal_state.title);
+    assert_eq!("2024 Session", buf.terminal_state.icon_name);
+
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]1;icon\x1B\\");
+    assert_eq!(CallbackAction::NoUpdate, act);
+    assert_eq!("icon", buf.terminal_state.icon_name);
+
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]2;title;with;semicolons\x1B\\");
+    assert_eq!(CallbackAction::SetTitle("title;with;semicolons".to_string()), act);
+    assert_eq!("icon", buf.terminal_state.icon_name);
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B]7;file://host/home/user\x07");
+    assert_eq!(Some("file://host/home/user".to_string()), buf.terminal_state.working_directory);
+}
+
+#[test]
+fn test_osc_clipboard() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]52;c;SGVsbG8=\x07");
+    assert_eq!(CallbackAction::SetClipboard("Hello".to_string()), act);
+
+    // queries need read access
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]52;c;?\x07");
+    assert_eq!(CallbackAction::NoUpdate, act);
+    parser.clipboard_access = ClipboardAccess::ReadWrite;
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]52;c;?\x07");
+    assert_eq!(CallbackAction::QueryClipboard("c".to_string()), act);
+    assert_eq!("\x1B]52;c;SGVsbG8=\x1B\\", crate::TerminalState::encode_clipboard_response("c", "Hello"));
+
+    // the selection is echoed back in the response
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]52;p;?\x07");
+    assert_eq!(CallbackAction::QueryClipboard("p".to_string()), act);
+    assert_eq!("\x1B]52;p;SGVsbG8=\x1B\\", crate::TerminalState::encode_clipboard_response("p", "Hello"));
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]52;;?\x07");
+    assert_eq!(CallbackAction::QueryClipboard("s0".to_string()), act);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]52;c\x1B[x;?\x07");
+    assert_eq!(CallbackAction::QueryClipboard("c".to_string()), act);
+
+    parser.clipboard_access = ClipboardAccess::Disabled;
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]52;c;SGVsbG8=\x07");
+    assert_eq!(CallbackAction::NoUpdate, act);
+}
+
+#[test]
+fn test_osc_palette_query() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B]4;1;rgb:ab/46/42;2;#102030\x07");
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]4;1;?;2;?\x1B\\");
+    assert_eq!(
+        CallbackAction::SendString("\x1B]4;1;rgb:abab/4646/4242\x1B\\\x1B]4;2;rgb:1010/2020/3030\x1B\\".to_string()),
+        act
+    );
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B]4;3;rgb:f/8/0\x07\x1B]4;4;rgb:ffff/8080/0000\x07");
+    assert_eq!((0xFF, 0x88, 0x00), buf.palette.get_rgb(3));
+    assert_eq!((0xFF, 0x80, 0x00), buf.palette.get_rgb(4));
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B]104;1\x07");
+    assert_eq!(DOS_DEFAULT_PALETTE[1], buf.palette.get_color(1));
+    assert_eq!((0x10, 0x20, 0x30), buf.palette.get_rgb(2));
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B]104\x07");
+    assert_eq!(DOS_DEFAULT_PALETTE[2], buf.palette.get_color(2));
+
+    // colors above 15 are only reset when requested and existing slots aren't created
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B]4;100;#102030\x07");
+    assert_eq!((0x10, 0x20, 0x30), buf.palette.get_rgb(100));
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B]104\x07");
+    assert_eq!((0x10, 0x20, 0x30), buf.palette.get_rgb(100));
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B]104;100;200\x07");
+    assert_eq!(XTERM_256_PALETTE[100].1, buf.palette.get_color(100));
+    assert_eq!(101, buf.palette.len());
+}
+
+#[test]
+fn test_osc_palette_query_unallocated() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    assert!(buf.palette.len() <= 200);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]4;200;?\x07");
+    let (r, g, b) = XTERM_256_PALETTE[200].1.get_rgb();
+    let expected = format!("\x1B]4;200;rgb:{r:02x}{r:02x}/{g:02x}{g:02x}/{b:02x}{b:02x}\x1B\\");
+    assert_eq!(CallbackAction::SendString(expected), act);
+}
+
+#[test]
+fn test_osc_palette_reset_keeps_truecolor() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[38;2;10;20;30mA");
+    let len = buf.palette.len();
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B]104\x07");
+    let fg = buf.get_char(Position::new(0, 0)).attribute.get_foreground();
+    assert_eq!(Color::new(10, 20, 30), buf.palette.get_color(fg));
+    assert_eq!(len, buf.palette.len());
+}
+
+#[test]
+fn test_osc_invalid_color_spec() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    // non ascii specs must not split a char
+    for ch in "\x1B]4;1;#aé\x07\x1B]4;1;rgbé1/2/3\x07\x1B]10;#aé\x07".chars() {
+        let _ = parser.print_char(&mut buf, 0, &mut caret, ch);
+    }
+    update_buffer_force(&mut buf, &mut caret, &mut parser, b"\x1B]4;1;rgb:+f/+f/+f\x07");
+    assert_eq!(DOS_DEFAULT_PALETTE[1], buf.palette.get_color(1));
+    assert_eq!(DOS_DEFAULT_PALETTE[7], buf.palette.get_color(7));
+}
+
+#[test]
+fn test_osc_dynamic_colors() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]10;?\x07");
+    assert_eq!(CallbackAction::SendString("\x1B]10;rgb:aaaa/aaaa/aaaa\x1B\\".to_string()), act);
+
+    // following arguments address the next dynamic colors
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B]10;#e0e0e0;rgb:10/10/10;rgb:ff/00/00\x07");
+    assert_eq!((0xE0, 0xE0, 0xE0), buf.palette.get_rgb(7));
+    assert_eq!((0x10, 0x10, 0x10), buf.palette.get_rgb(0));
+    assert_eq!(Some(Color::new(0xFF, 0, 0)), buf.terminal_state.cursor_color);
+
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B]11;?;?\x07");
+    assert_eq!(
+        CallbackAction::SendString("\x1B]11;rgb:1010/1010/1010\x1B\\\x1B]12;rgb:ffff/0000/0000\x1B\\".to_string()),
+        act
+    );
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B]110\x07\x1B]111\x07\x1B]112\x07");
+    assert_eq!(DOS_DEFAULT_PALETTE[7], buf.palette.get_color(7));
+    assert_eq!(DOS_DEFAULT_PALETTE[0], buf.palette.get_color(0));
+    assert_eq!(None, buf.terminal_state.cursor_color);
+}
+
+#[test]
+fn test_cursor_style() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[4 q");
+    assert_eq!(CaretShape::Underline, caret.shape);
+    assert!(!caret.is_blinking);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1BP$q q\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1BP1$r4 q\x1B\\".to_string()), act);
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[5 q");
+    assert_eq!(CaretShape::Bar, caret.shape);
+    assert!(caret.is_blinking);
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[ q");
+    assert_eq!(CaretShape::Block, caret.shape);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1BP$q q\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1BP1$r1 q\x1B\\".to_string()), act);
+
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1BP$qunknown\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1BP0$r\x1B\\".to_string()), act);
+}
+
+#[test]
+fn test_request_mode() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[?2004h\x1B[?1006h");
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[?2004$p");
+    assert_eq!(CallbackAction::SendString("\x1B[?2004;1$y".to_string()), act);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[?1006$p");
+    assert_eq!(CallbackAction::SendString("\x1B[?1006;1$y".to_string()), act);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[?1000$p");
+    assert_eq!(CallbackAction::SendString("\x1B[?1000;2$y".to_string()), act);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[?25$p");
+    assert_eq!(CallbackAction::SendString("\x1B[?25;1$y".to_string()), act);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[?12345$p");
+    assert_eq!(CallbackAction::SendString("\x1B[?12345;0$y".to_string()), act);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[?6$p");
+    assert_eq!(CallbackAction::SendString("\x1B[?6;2$y".to_string()), act);
+    buf.terminal_state.origin_mode = OriginMode::WithinMargins;
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[?6$p");
+    assert_eq!(CallbackAction::SendString("\x1B[?6;1$y".to_string()), act);
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[4h");
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[4$p");
+    assert_eq!(CallbackAction::SendString("\x1B[4;1$y".to_string()), act);
+}
+
+#[test]
+fn test_request_status_string() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[1;4;31;104m");
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1BP$qm\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1BP1$r0;1;4;31;104m\x1B\\".to_string()), act);
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[0;38;2;1;2;3m\x1B[5;20r");
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1BP$qm\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1BP1$r0;38;2;1;2;3m\x1B\\".to_string()), act);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1BP$qr\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1BP1$r5;20r\x1B\\".to_string()), act);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1BP$q$|\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1BP1$r80$|\x1B\\".to_string()), act);
+}
+
+#[test]
+fn test_request_termcap() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    // "TN;Co;xx"
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1BP+q544E;436F;7878\x1B\\");
+    assert_eq!(
+        CallbackAction::SendString("\x1BP1+r544E=787465726D2D323536636F6C6F72\x1B\\\x1BP1+r436F=323536\x1B\\\x1BP0+r7878\x1B\\".to_string()),
+        act
+    );
+
+    // the name follows the conformance level
+    parser.conformance_level = ConformanceLevel::Vt220;
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1BP+q544E\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1BP1+r544E=7674323230\x1B\\".to_string()), act);
+}
+
+#[test]
+fn test_line_size_attributes() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B#6");
+    assert_eq!(LineSize::DoubleWidth, buf.layers[0].lines[0].line_size);
+
+    // the caret is limited to the left half of the line
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[1;70H");
+    assert_eq!(Position::new(39, 0), caret.get_position());
+    update_buffer(&mut buf, &mut caret, &mut parser, b"AB");
+    assert_eq!(b'A', buf.get_char(Position::new(39, 0)).ch as u8);
+    assert_eq!(b'B', buf.get_char(Position::new(0, 1)).ch as u8);
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B#3\r\n\x1B#4\x1B#5");
+    assert_eq!(LineSize::DoubleHeightTop, buf.layers[0].lines[1].line_size);
+    assert_eq!(LineSize::Single, buf.layers[0].lines[2].line_size);
+}
+
+#[test]
+fn test_line_size_scrolls_with_line() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B#6A\r\n\x1B#3B\x1B[25H\x1B#6");
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[S");
+    assert_eq!(LineSize::DoubleHeightTop, buf.get_line_size(0));
+    assert_eq!(LineSize::Single, buf.get_line_size(1));
+    assert_eq!(LineSize::DoubleWidth, buf.get_line_size(23));
+    assert_eq!(LineSize::Single, buf.get_line_size(24));
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[T");
+    assert_eq!(LineSize::Single, buf.get_line_size(0));
+    assert_eq!(LineSize::DoubleHeightTop, buf.get_line_size(1));
+    assert_eq!(b'B', buf.get_char(Position::new(0, 1)).ch as u8);
+    assert_eq!(LineSize::Single, buf.get_line_size(23));
+    assert_eq!(LineSize::DoubleWidth, buf.get_line_size(24));
+}
+
+#[test]
+fn test_render_double_width_line() {
+    let mut parser = ansi::Parser::default();
+    let (buf, _) = create_buffer(&mut parser, b"A\r\n\x1B#6A\r\n\x1B#3A\r\n\x1B#4A");
+    let font_size = buf.get_font_dimensions();
+    let (size, pixels) = buf.render_to_rgba(crate::Rectangle {
+        start: Position::new(0, 0),
+        size: crate::Size::new(2, 4),
+    });
+    let pixel = |x: i32, y: i32| {
+        let o = ((y * size.width + x) * 4) as usize;
+        pixels[o..o + 4].to_vec()
+    };
+    for cy in 0..font_size.height {
+        for cx in 0..font_size.width {
+            let single = pixel(cx, cy);
+            assert_eq!(single, pixel(2 * cx, font_size.height + cy));
+            assert_eq!(single, pixel(2 * cx + 1, font_size.height + cy));
+            // double height lines are scaled vertically and show one half of the glyph each
+            let double_height_y = 2 * font_size.height + 2 * cy;
+            assert_eq!(single, pixel(2 * cx, double_height_y));
+            assert_eq!(single, pixel(2 * cx + 1, double_height_y + 1));
+        }
+    }
+}
+
+#[test]
+fn test_dec_special_graphics() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    buf.buffer_type = BufferType::Unicode;
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B(0lqk\x1B(Bq");
+    assert_eq!('┌', buf.get_char(Position::new(0, 0)).ch);
+    assert_eq!('─', buf.get_char(Position::new(1, 0)).ch);
+    assert_eq!('┐', buf.get_char(Position::new(2, 0)).ch);
+    assert_eq!('q', buf.get_char(Position::new(3, 0)).ch);
+
+    // G1 is invoked with SO/SI, G2/G3 with single shifts
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\r\n\x1B)0\x1B*A\x1B+>x\x0Ex\x0Fx\x1BN#\x1BOa#");
+    let line: String = (0..6).map(|x| buf.get_char(Position::new(x, 1)).ch).collect();
+    assert_eq!("x│x£α#", line);
+}
+
+#[test]
+fn test_save_cursor_charsets() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    buf.buffer_type = BufferType::Unicode;
+    // DECSC/DECRC restore the designations and shifts
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B)0\x0E\x1B7\x0F\x1B)B\x1B8q");
+    assert_eq!('─', buf.get_char(Position::new(0, 0)).ch);
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x0Fq");
+    assert_eq!('q', buf.get_char(Position::new(1, 0)).ch);
+
+    // as does leaving the alternate screen
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?1049h\x1B(0q\x1B[?1049lq");
+    assert_eq!('q', buf.get_char(Position::new(2, 0)).ch);
+}
+
+#[test]
+fn test_dec_special_graphics_cp437() {
+    let mut parser = ansi::Parser::default();
+    let (buf, _) = create_buffer(&mut parser, b"\x1B(0lqx\x0E\x1B(B");
+    assert_eq!(BufferType::CP437, buf.buffer_type);
+    assert_eq!(0xDA, buf.get_char(Position::new(0, 0)).ch as u32);
+    assert_eq!(0xC4, buf.get_char(Position::new(1, 0)).ch as u32);
+    assert_eq!(0xB3, buf.get_char(Position::new(2, 0)).ch as u32);
+    // SO is a printable char in CP437
+    assert_eq!(0x0E, buf.get_char(Position::new(3, 0)).ch as u32);
+}
+
+fn get_line(buf: &Buffer, y: i32, len: i32) -> String {
+    (0..len).map(|x| buf.get_char(Position::new(x, y)).ch).collect()
+}
+
+#[test]
+fn test_select_character_protection() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[1\"qA\x1B[0mB\x1B[0\"qC");
+    assert!(buf.get_char(Position::new(0, 0)).attribute.is_protected());
+    // SGR 0 doesn't reset the protection
+    assert!(buf.get_char(Position::new(1, 0)).attribute.is_protected());
+    assert!(!buf.get_char(Position::new(2, 0)).attribute.is_protected());
+
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1BP$q\"q\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1BP1$r0\"q\x1B\\".to_string()), act);
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[1\"q");
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1BP$q\"q\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1BP1$r1\"q\x1B\\".to_string()), act);
+}
+
+#[test]
+fn test_selective_erase_in_line() {
+    // similar to the vttest DECSEL test: only the unprotected chars get erased
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"ab\x1B[1\"qcd\x1B[0\"qef\x1B[1;4H\x1B[?K");
+    assert_eq!("abcd  ", get_line(&buf, 0, 6));
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[1;5Hef\x1B[1;3H\x1B[?1K");
+    assert_eq!("  cdef", get_line(&buf, 0, 6));
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?2K");
+    assert_eq!("  cd  ", get_line(&buf, 0, 6));
+
+    // a normal erase in line erases protected chars as well
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[2K");
+    assert_eq!("      ", get_line(&buf, 0, 6));
+}
+
+#[test]
+fn test_selective_erase_in_display() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"abc\r\nd\x1B[1\"qe\x1B[0\"qf\r\nghi\x1B[2;2H\x1B[?J");
+    assert_eq!("abc", get_line(&buf, 0, 3));
+    assert_eq!("de ", get_line(&buf, 1, 3));
+    assert_eq!("   ", get_line(&buf, 2, 3));
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[3;1Hghi\x1B[2;2H\x1B[?1J");
+    assert_eq!("   ", get_line(&buf, 0, 3));
+    assert_eq!(" e ", get_line(&buf, 1, 3));
+    assert_eq!("ghi", get_line(&buf, 2, 3));
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?2J");
+    assert_eq!(" e ", get_line(&buf, 1, 3));
+    assert_eq!("   ", get_line(&buf, 2, 3));
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[2J");
+    assert_eq!("   ", get_line(&buf, 1, 3));
+}
+
+#[test]
+fn test_selective_erase_protected_area() {
+    let mut parser = ansi::Parser::default();
+    let (buf, _) = create_buffer(&mut parser, b"ab\x1B[1\"qcd\x1B[0\"qef\r\n\x1B[1\"qgh\x1B[0\"qijkl\x1B[1;2;2;5${");
+    assert_eq!("a cd f", get_line(&buf, 0, 6));
+    assert_eq!("gh   l", get_line(&buf, 1, 6));
+}
+
+#[test]
+fn test_copy_rectangular_area() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"abc\r\ndef\x1B[1;2;2;3;1;4;5;1$v");
+    assert_eq!("    bc", get_line(&buf, 3, 6));
+    assert_eq!("    ef", get_line(&buf, 4, 6));
+    assert_eq!("abc", get_line(&buf, 0, 3));
+
+    // the co-ordinates are relative to the margins in origin mode, the destination gets clipped
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[2J\x1B[Habc\r\ndef\x1B[?69h\x1B[1;5r\x1B[1;5s");
+    buf.terminal_state.origin_mode = OriginMode::WithinMargins;
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[1;1;2;3;1;5;4;1$v");
+    assert_eq!("   ab", get_line(&buf, 4, 5));
+    assert_eq!(' ', buf.get_char(Position::new(5, 4)).ch);
+    assert_eq!(' ', buf.get_char(Position::new(3, 5)).ch);
+}
+
+#[test]
+fn test_rectangular_area_after_scrolling() {
+    let mut parser = ansi::Parser::default();
+    let mut text = Vec::new();
+    for i in 0..40 {
+        text.extend(format!("{i:02}\r\n").bytes());
+    }
+    let (mut buf, mut caret) = create_buffer(&mut parser, &text);
+    let first = buf.get_first_visible_line();
+    assert!(first > 0);
+    let first_text = format!("{first:02}");
+    assert_eq!(first_text, get_line(&buf, first, 2));
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[1;1;1;4;1;2;10;1$v");
+    assert_eq!(format!("{:02}       {first_text}", first + 1), get_line(&buf, first + 1, 11));
+    assert_eq!("00", get_line(&buf, 0, 2));
+    assert_eq!("01", get_line(&buf, 1, 2));
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[2*x\x1B[1;1;1;2;1$r");
+    assert!(buf.get_char(Position::new(0, first)).attribute.is_bold());
+    assert!(!buf.get_char(Position::new(0, 0)).attribute.is_bold());
+
+    // the margins are screen relative
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[3;5r");
+    buf.terminal_state.origin_mode = OriginMode::WithinMargins;
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[1;1;1;2;4$r");
+    assert!(buf.get_char(Position::new(0, first + 2)).attribute.is_underlined());
+    assert!(!buf.get_char(Position::new(0, 2)).attribute.is_underlined());
+}
+
+#[test]
+fn test_change_attributes_in_rectangular_area() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[2*x\x1B[2;2;3;3;1;4$r");
+    for y in 0..4 {
+        for x in 0..4 {
+            let attr = buf.get_char(Position::new(x, y)).attribute;
+            let inside = (1..3).contains(&x) && (1..3).contains(&y);
+            assert_eq!(inside, attr.is_bold());
+            assert_eq!(inside, attr.is_underlined());
+        }
+    }
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[1;1;3;3;0$r");
+    assert!(!buf.get_char(Position::new(1, 1)).attribute.is_bold());
+    // no attribute parameters switch all attributes off
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[1;1;3;3;4$r\x1B[1;1;3;3$r");
+    assert!(!buf.get_char(Position::new(1, 1)).attribute.is_underlined());
+    // the negative image isn't a set operation - it must not toggle the colors
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[1;1;1;1;7$r");
+    assert_eq!(7, buf.get_char(Position::new(0, 0)).attribute.get_foreground());
+    assert_eq!(0, buf.get_char(Position::new(0, 0)).attribute.get_background());
+
+    // stream extent wraps at the line ends
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[*x\x1B[2;79;3;2;5$r");
+    assert!(!buf.get_char(Position::new(77, 1)).attribute.is_blinking());
+    assert!(buf.get_char(Position::new(78, 1)).attribute.is_blinking());
+    assert!(!buf.get_char(Position::new(10, 1)).attribute.is_blinking());
+    assert!(buf.get_char(Position::new(1, 2)).attribute.is_blinking());
+    assert!(!buf.get_char(Position::new(2, 2)).attribute.is_blinking());
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1BP$q*x\x1B\\");
+    assert_eq!(CallbackAction::SendString("\x1BP1$r1*x\x1B\\".to_string()), act);
+}
+
+#[test]
+fn test_reverse_attributes_in_rectangular_area() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1B[1mab\x1B[0mcd\x1B[2*x\x1B[1;1;1;3;1;7$t");
+    assert!(!buf.get_char(Position::new(0, 0)).attribute.is_bold());
+    assert!(buf.get_char(Position::new(2, 0)).attribute.is_bold());
+    assert!(!buf.get_char(Position::new(3, 0)).attribute.is_bold());
+    assert_eq!(0, buf.get_char(Position::new(2, 0)).attribute.get_foreground());
+    assert_eq!(7, buf.get_char(Position::new(2, 0)).attribute.get_background());
+
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[1;1;1;3;7$t");
+    assert_eq!(7, buf.get_char(Position::new(2, 0)).attribute.get_foreground());
+}
+
+#[test]
+fn test_conformance_level_device_attributes() {
+    let mut parser = ansi::Parser::default();
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[c");
+    assert!(matches!(act, CallbackAction::SendString(s) if s.starts_with("\x1b[=73;99;121;84;101;114;109;")));
+
+    parser.conformance_level = ConformanceLevel::CTerm;
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[c");
+    assert_eq!(CallbackAction::SendString("\x1b[=67;84;101;114;109;1;316c".to_string()), act);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[>c");
+    assert_eq!(CallbackAction::NoUpdate, act);
+
+    parser.conformance_level = ConformanceLevel::Vt220;
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[0c");
+    assert_eq!(CallbackAction::SendString("\x1b[?62;1;6;22c".to_string()), act);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[>0c");
+    assert_eq!(CallbackAction::SendString("\x1b[>1;10;0c".to_string()), act);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[=c");
+    assert_eq!(CallbackAction::NoUpdate, act);
+
+    parser.conformance_level = ConformanceLevel::Xterm;
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[>c");
+    assert_eq!(CallbackAction::SendString("\x1b[>41;390;0c".to_string()), act);
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[=0c");
+    assert_eq!(CallbackAction::SendString("\x1BP!|00000000\x1B\\".to_string()), act);
+}
+
+#[test]
+fn test_conformance_level_sequences() {
+    let mut parser = ansi::Parser {
+        conformance_level: ConformanceLevel::Vt220,
+        ..Default::default()
+    };
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    // rectangular editing needs a VT400
+    update_buffer_force(&mut buf, &mut caret, &mut parser, b"\x1B[65;1;1;2;2$x");
+    assert_eq!(' ', buf.get_char(Position::new(0, 0)).ch);
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[1\"qA");
+    assert!(buf.get_char(Position::new(0, 0)).attribute.is_protected());
+
+    parser.conformance_level = ConformanceLevel::Vt100;
+    update_buffer_force(&mut buf, &mut caret, &mut parser, b"\x1B[0\"qB");
+    assert!(buf.get_char(Position::new(1, 0)).attribute.is_protected());
+    // mode requests need a VT320
+    let results: Vec<_> = "\x1B[?25$p".chars().map(|ch| parser.print_char(&mut buf, 0, &mut caret, ch)).collect();
+    assert!(results.last().unwrap().is_err());
+    parser.conformance_level = ConformanceLevel::Vt320;
+    let act = get_action(&mut buf, &mut caret, &mut parser, b"\x1B[?25$p");
+    assert_eq!(CallbackAction::SendString("\x1B[?25;1$y".to_string()), act);
+
+    parser.conformance_level = ConformanceLevel::Vt420;
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[65;2;1;2;2$x");
+    assert_eq!('A', buf.get_char(Position::new(1, 1)).ch);
+}
+
+#[test]
+fn test_conformance_level_xterm_extensions() {
+    let mut parser = ansi::Parser {
+        conformance_level: ConformanceLevel::Vt220,
+        clipboard_access: ClipboardAccess::ReadWrite,
+        ..Default::default()
+    };
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"");
+    update_buffer_force(&mut buf, &mut caret, &mut parser, b"\x1B[?1000h\x1B[?1006h\x1B[?2004h\x1B[38;2;1;2;3mA");
+    assert_eq!(MouseMode::Default, buf.terminal_state.mouse_mode);
+    assert_eq!(MouseEncoding::Default, buf.terminal_state.mouse_encoding);
+    assert!(!buf.terminal_state.bracketed_paste_mode);
+    assert_eq!(7, buf.get_char(Position::new(0, 0)).attribute.get_foreground());
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B]52;c;?");
+    assert!(parser.print_char(&mut buf, 0, &mut caret, '\x07').is_err());
+    update_buffer_force(&mut buf, &mut caret, &mut parser, b"\x1B_Ga=T,s=1,v=1;AAAA/w==\x1B\\");
+    assert!(buf.layers[0].sixels.is_empty());
+
+    parser.conformance_level = ConformanceLevel::CTerm;
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[?1000h\x1B[?2004h");
+    assert_eq!(MouseMode::VT200, buf.terminal_state.mouse_mode);
+    assert!(buf.terminal_state.bracketed_paste_mode);
+}
+
+#[test]
+fn test_conformance_level_default_modes() {
+    let mut parser = ansi::Parser {
+        conformance_level: ConformanceLevel::Vt100,
+        ..Default::default()
+    };
+    let (mut buf, mut caret) = create_buffer(&mut parser, b"\x1Bc");
+    assert_eq!(AutoWrapMode::NoWrap, buf.terminal_state.auto_wrap_mode);
+
+    parser.conformance_level = ConformanceLevel::Xterm;
+    update_buffer(&mut buf, &mut caret, &mut parser, b"\x1B[!p");
+    assert_eq!(AutoWrapMode::AutoWrap, buf.terminal_state.auto_wrap_mode);
+
+    // changing the level applies its default modes
+    parser.set_conformance_level(ConformanceLevel::Vt100, &mut buf);
+    assert_eq!(ConformanceLevel::Vt100, parser.get_conformance_level());
+    assert_eq!(AutoWrapMode::NoWrap, buf.terminal_state.auto_wrap_mode);
+}
@@ -45,6 +45,11 @@ impl Parser {
 }
 
 impl BufferParser for Parser {
+    /// Avatar has no own keyboard codes, the remote side expects the keys of the ANSI terminal it falls back to.
+    fn encode_key(&self, buffer: &Buffer, key: super::Key, modifiers: crate::KeyModifiers) -> Option<Vec<u8>> {
+        self.ansi_parser.encode_key(buffer, key, modifiers)
+    }
+
     fn print_char(&mut self, buf: &mut Buffer, current_layer: usize, caret: &mut Caret, ch: char) -> EngineResult<CallbackAction> {
         match self.avt_state {
             AvtReadState::Chars => {
@@ -0,0 +1,239 @@
+/// 94 character sets that can be designated to G0-G3 (SCS - `ESC ( F`, `ESC ) F`, `ESC * F`, `ESC + F`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CharacterSet {
+    #[default]
+    Ascii,
+    /// DEC Special Graphics (line drawing) - `ESC ( 0`
+    DecSpecialGraphics,
+    /// United Kingdom - `ESC ( A`
+    Uk,
+    /// DEC Technical - `ESC ( >`
+    DecTechnical,
+    /// Teletext mosaic graphics, only used by the viewdata parser
+    Mosaic,
+}
+
+impl CharacterSet {
+    /// Gives back the set for a final character of a SCS sequence.
+    pub fn from_designator(ch: char) -> Option<Self> {
+        match ch {
+            'B' => Some(CharacterSet::Ascii),
+            '0' => Some(CharacterSet::DecSpecialGraphics),
+            'A' => Some(CharacterSet::Uk),
+            '>' => Some(CharacterSet::DecTechnical),
+            _ => None,
+        }
+    }
+
+    /// Translates a char of the GL area (0x21-0x7E) to its unicode representation in this set.
+    pub fn translate(self, ch: char) -> char {
+        let table: &[(char, char)] = match self {
+            CharacterSet::Ascii | CharacterSet::Mosaic => return ch,
+            CharacterSet::Uk => &[('#', '£')],
+            CharacterSet::DecSpecialGraphics => &DEC_SPECIAL_GRAPHICS,
+            CharacterSet::DecTechnical => &DEC_TECHNICAL,
+        };
+        table.iter().find(|(from, _)| *from == ch).map_or(ch, |(_, to)| *to)
+    }
+}
+
+/// The character sets designated to G0-G3 and the state of the shift functions that invoke them.
+#[derive(Debug, Clone, Default)]
+pub struct CharacterSets {
+    sets: [CharacterSet; 4],
+    /// set invoked into GL by SI (G0), SO (G1), LS2 & LS3
+    locking_shift: usize,
+    /// set invoked for the next char only by SS2 & SS3
+    single_shift: Option<usize>,
+}
+
+impl CharacterSets {
+    pub fn new(sets: [CharacterSet; 4]) -> Self {
+        Self {
+            sets,
+            locking_shift: 0,
+            single_shift: None,
+        }
+    }
+
+    pub fn get(&self, g: usize) -> CharacterSet {
+        self.sets[g]
+    }
+
+    pub fn designate(&mut self, g: usize, set: CharacterSet) {
+        self.sets[g] = set;
+    }
+
+    pub fn locking_shift(&mut self, g: usize) {
+        self.locking_shift = g;
+    }
+
+    pub fn single_shift(&mut self, g: usize) {
+        self.single_shift = Some(g);
+    }
+
+    /// Gives back the set the next char is taken from. A pending single shift is used up.
+    pub fn next_set(&mut self) -> CharacterSet {
+        self.sets[self.single_shift.take().unwrap_or(self.locking_shift)]
+    }
+
+    /// Maps a printed char through the invoked set. Chars outside the GL area aren't affected.
+    pub fn translate(&mut self, ch: char) -> char {
+        if !('\x21'..='\x7E').contains(&ch) {
+            return ch;
+        }
+        self.next_set().translate(ch)
+    }
+}
+
+/// <https://vt100.net/docs/vt220-rm/table2-4.html>
+const DEC_SPECIAL_GRAPHICS: [(char, char); 32] = [
+    ('_', ' '),
+    ('`', '◆'),
+    ('a', '▒'),
+    ('b', '␉'),
+    ('c', '␌'),
+    ('d', '␍'),
+    ('e', '␊'),
+    ('f', '°'),
+    ('g', '±'),
+    ('h', '␤'),
+    ('i', '␋'),
+    ('j', '┘'),
+    ('k', '┐'),
+    ('l', '┌'),
+    ('m', '└'),
+    ('n', '┼'),
+    ('o', '⎺'),
+    ('p', '⎻'),
+    ('q', '─'),
+    ('r', '⎼'),
+    ('s', '⎽'),
+    ('t', '├'),
+    ('u', '┤'),
+    ('v', '┴'),
+    ('w', '┬'),
+    ('x', '│'),
+    ('y', '≤'),
+    ('z', '≥'),
+    ('{', 'π'),
+    ('|', '≠'),
+    ('}', '£'),
+    ('~', '·'),
+];
+
+/// <https://vt100.net/docs/vt220-rm/table2-6.html> - the parts of the large sigma don't have a unicode equivalent.
+const DEC_TECHNICAL: [(char, char); 83] = [
+    ('!', '⎷'),
+    ('"', '┌'),
+    ('#', '─'),
+    ('$', '⌠'),
+    ('%', '⌡'),
+    ('&', '│'),
+    ('\'', '⎡'),
+    ('(', '⎣'),
+    (')', '⎤'),
+    ('*', '⎦'),
+    ('+', '⎧'),
+    (',', '⎩'),
+    ('-', '⎫'),
+    ('.', '⎭'),
+    ('/', '⎨'),
+    ('0', '⎬'),
+    ('<', '≤'),
+    ('=', '≠'),
+    ('>', '≥'),
+    ('?', '∫'),
+    ('@', '∴'),
+    ('A', '∝'),
+    ('B', '∞'),
+    ('C', '÷'),
+    ('D', 'Δ'),
+    ('E', '∇'),
+    ('F', 'Φ'),
+    ('G', 'Γ'),
+    ('H', '∼'),
+    ('I', '≃'),
+    ('J', 'Θ'),
+    ('K', '×'),
+    ('L', 'Λ'),
+    ('M', '⇔'),
+    ('N', '⇒'),
+    ('O', '≡'),
+    ('P', 'Π'),
+    ('Q', 'Ψ'),
+    ('R', ' '),
+    ('S', 'Σ'),
+    ('T', ' '),
+    ('U', ' '),
+    ('V', '√'),
+    ('W', 'Ω'),
+    ('X', 'Ξ'),
+    ('Y', 'Υ'),
+    ('Z', '⊂'),
+    ('[', '⊃'),
+    ('\\', '∩'),
+    (']', '∪'),
+    ('^', '∧'),
+    ('_', '∨'),
+    ('`', '¬'),
+    ('a', 'α'),
+    ('b', 'β'),
+    ('c', 'χ'),
+    ('d', 'δ'),
+    ('e', 'ε'),
+    ('f', 'φ'),
+    ('g', 'γ'),
+    ('h', 'η'),
+    ('i', 'ι'),
+    ('j', 'θ'),
+    ('k', 'κ'),
+    ('l', 'λ'),
+    ('m', ' '),
+    ('n', 'ν'),
+    ('o', '∂'),
+    ('p', 'π'),
+    ('q', 'ψ'),
+    ('r', 'ρ'),
+    ('s', 'σ'),
+    ('t', 'τ'),
+    ('u', ' '),
+    ('v', 'ƒ'),
+    ('w', 'ω'),
+    ('x', 'ξ'),
+    ('y', 'υ'),
+    ('z', 'ζ'),
+    ('{', '←'),
+    ('|', '↑'),
+    ('}', '→'),
+    ('~', '↓'),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{CharacterSet, CharacterSets};
+
+    #[test]
+    fn test_translate() {
+        assert_eq!('┌', CharacterSet::DecSpecialGraphics.translate('l'));
+        assert_eq!('A', CharacterSet::DecSpecialGraphics.translate('A'));
+        assert_eq!('£', CharacterSet::Uk.translate('#'));
+        assert_eq!('α', CharacterSet::DecTechnical.translate('a'));
+    }
+
+    #[test]
+    fn test_shifts() {
+        let mut sets = CharacterSets::default();
+        sets.designate(1, CharacterSet::DecSpecialGraphics);
+        sets.designate(2, CharacterSet::Uk);
+        assert_eq!('q', sets.translate('q'));
+        sets.locking_shift(1);
+        assert_eq!('─', sets.translate('q'));
+
+        // a single shift only applies to the next printable char
+        sets.single_shift(2);
+        assert_eq!(' ', sets.translate(' '));
+        assert_eq!('£', sets.translate('#'));
+        assert_eq!('#', sets.translate('#'));
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{ascii::CP437Converter, Buffer, BufferType, KeyModifiers, UnicodeConverter};
+
+/// A key pressed on the local keyboard, see [`super::BufferParser::encode_key`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Tab,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Insert,
+    Delete,
+    PageUp,
+    PageDown,
+    /// F1 - F12
+    Function(u8),
+}
+
+/// Encodes a key for ANSI/VT terminals. Cursor keys respect the cursor key mode (DECCKM),
+/// modifiers are sent xterm style (`CSI 1 ; Pm A`).
+pub fn encode_ansi_key(buf: &Buffer, key: Key, modifiers: KeyModifiers) -> Option<Vec<u8>> {
+    let modifier_param = 1 + u8::from(modifiers.shift) + 2 * u8::from(modifiers.alt) + 4 * u8::from(modifiers.ctrl);
+    let cursor_key = |final_char: char| {
+        if modifier_param > 1 {
+            format!("\x1B[1;{modifier_param}{final_char}")
+        } else if buf.terminal_state.application_cursor_keys {
+            format!("\x1BO{final_char}")
+        } else {
+            format!("\x1B[{final_char}")
+        }
+    };
+    let tilde_key = |number: u8| {
+        if modifier_param > 1 {
+            format!("\x1B[{number};{modifier_param}~")
+        } else {
+            format!("\x1B[{number}~")
+        }
+    };
+
+    let result = match key {
+        Key::Char(ch) => return encode_char(buf, ch, modifiers),
+        Key::Enter => "\r".to_string(),
+        Key::Backspace => "\x08".to_string(),
+        Key::Tab => {
+            if modifiers.shift {
+                "\x1B[Z".to_string()
+            } else {
+                "\t".to_string()
+            }
+        }
+        Key::Escape => "\x1B".to_string(),
+        Key::Up => cursor_key('A'),
+        Key::Down => cursor_key('B'),
+        Key::Right => cursor_key('C'),
+        Key::Left => cursor_key('D'),
+        Key::Home => cursor_key('H'),
+        Key::End => cursor_key('F'),
+        Key::Insert => tilde_key(2),
+        Key::Delete => tilde_key(3),
+        Key::PageUp => tilde_key(5),
+        Key::PageDown => tilde_key(6),
+        Key::Function(n @ 1..=4) => {
+            let final_char = (b'P' + n - 1) as char;
+            if modifier_param > 1 {
+                format!("\x1B[1;{modifier_param}{final_char}")
+            } else {
+                format!("\x1BO{final_char}")
+            }
+        }
+        Key::Function(n @ 5..=12) => tilde_key([15, 17, 18, 19, 20, 21, 23, 24][n as usize - 5]),
+        Key::Function(_) => return None,
+    };
+    Some(result.into_bytes())
+}
+
+/// Encodes a typed character in the character set of the buffer, ctrl gives the control code and alt prefixes it with ESC.
+fn encode_char(buf: &Buffer, ch: char, modifiers: KeyModifiers) -> Option<Vec<u8>> {
+    let mut result = Vec::new();
+    if modifiers.alt {
+        result.push(0x1B);
+    }
+    if modifiers.ctrl {
+        match ch {
+            'a'..='z' | 'A'..='Z' | '@' | '['..='_' => result.push(ch.to_ascii_uppercase() as u8 & 0x1F),
+            ' ' => result.push(0),
+            _ => return None,
+        }
+        return Some(result);
+    }
+    if buf.buffer_type == BufferType::Unicode {
+        let mut bytes = [0; 4];
+        result.extend_from_slice(ch.encode_utf8(&mut bytes).as_bytes());
+    } else {
+        result.push(u8::try_from(CP437Converter::default().convert_from_unicode(ch, 0) as u32).ok()?);
+    }
+    Some(result)
+}
+
+/// Encodes a key for a Commodore 64 PETSCII terminal.
+pub fn encode_petscii_key(key: Key) -> Option<Vec<u8>> {
+    let result = match key {
+        Key::Char(ch) => u8::try_from(crate::petscii::CharConverter::default().convert_from_unicode(ch, 0) as u32).ok()?,
+        Key::Enter => 0x0D,
+        Key::Backspace | Key::Delete => 0x14,
+        Key::Insert => 0x94,
+        // RUN/STOP
+        Key::Escape => 0x03,
+        Key::Up => 0x91,
+        Key::Down => 0x11,
+        Key::Right => 0x1D,
+        Key::Left => 0x9D,
+        Key::Home => 0x13,
+        // SHIFT + CLR/HOME
+        Key::End => 0x93,
+        Key::Function(n @ 1..=8) => [0x85, 0x89, 0x86, 0x8A, 0x87, 0x8B, 0x88, 0x8C][n as usize - 1],
+        Key::Tab | Key::PageUp | Key::PageDown | Key::Function(_) => return None,
+    };
+    Some(vec![result])
+}
+
+/// Encodes a key for an Atari ATASCII terminal.
+pub fn encode_atascii_key(key: Key) -> Option<Vec<u8>> {
+    let result = match key {
+        Key::Char(ch) => u8::try_from(crate::atascii::CharConverter::default().convert_from_unicode(ch, 0) as u32).ok()?,
+        Key::Enter => 0x9B,
+        Key::Backspace => 0x7E,
+        Key::Tab => 0x7F,
+        Key::Escape => 0x1B,
+        Key::Up => 0x1C,
+        Key::Down => 0x1D,
+        Key::Left => 0x1E,
+        Key::Right => 0x1F,
+        // SHIFT + CLEAR
+        Key::Home => 0x7D,
+        Key::Delete => 0xFE,
+        Key::Insert => 0xFF,
+        Key::End | Key::PageUp | Key::PageDown | Key::Function(_) => return None,
+    };
+    Some(vec![result])
+}
+
+/// Encodes a key for Viewdata (Prestel) and BBC Micro Mode 7 terminals.
+/// Viewdata sends the '#' key (0x5F) for enter, Mode 7 a carriage return.
+pub fn encode_viewdata_key(key: Key, is_mode7: bool) -> Option<Vec<u8>> {
+    let result = match key {
+        Key::Char(ch) => {
+            let ch = if is_mode7 {
+                crate::mode7::CharConverter::default().convert_from_unicode(ch, 0)
+            } else {
+                crate::viewdata::CharConverter::default().convert_from_unicode(ch, 0)
+            };
+            u8::try_from(ch as u32).ok().filter(|ch| *ch < 0x80)?
+        }
+        Key::Enter => {
+            if is_mode7 {
+                0x0D
+            } else {
+                0x5F
+            }
+        }
+        Key::Backspace | Key::Left => 0x08,
+        Key::Right => 0x09,
+        Key::Down => 0x0A,
+        Key::Up => 0x0B,
+        Key::Delete => 0x7F,
+        Key::Escape => 0x1B,
+        Key::Home => 0x1E,
+        Key::Tab | Key::End | Key::Insert | Key::PageUp | Key::PageDown | Key::Function(_) => return None,
+    };
+    Some(vec![result])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ansi, atascii, avatar, mode7, petscii, viewdata, Buffer, BufferParser, BufferType, Key, KeyModifiers};
+
+    const CTRL: KeyModifiers = KeyModifiers {
+        shift: false,
+        alt: false,
+        ctrl: true,
+    };
+
+    fn encode<T: BufferParser>(parser: &T, buf: &Buffer, key: Key) -> Option<Vec<u8>> {
+        parser.encode_key(buf, key, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_ansi_cursor_keys() {
+        let parser = ansi::Parser::default();
+        let mut buf = Buffer::new((80, 25));
+        assert_eq!(Some(b"\x1B[A".to_vec()), encode(&parser, &buf, Key::Up));
+        assert_eq!(Some(b"\x1B[1;5D".to_vec()), parser.encode_key(&buf, Key::Left, CTRL));
+        buf.terminal_state.application_cursor_keys = true;
+        assert_eq!(Some(b"\x1BOA".to_vec()), encode(&parser, &buf, Key::Up));
+        assert_eq!(Some(b"\x1BOH".to_vec()), encode(&parser, &buf, Key::Home));
+    }
+
+    #[test]
+    fn test_ansi_keys() {
+        let parser = ansi::Parser::default();
+        let mut buf = Buffer::new((80, 25));
+        assert_eq!(Some(b"\r".to_vec()), encode(&parser, &buf, Key::Enter));
+        assert_eq!(Some(b"\x1B[3~".to_vec()), encode(&parser, &buf, Key::Delete));
+        assert_eq!(
+            Some(b"\x1B[6;2~".to_vec()),
+            parser.encode_key(
+                &buf,
+                Key::PageDown,
+                KeyModifiers {
+                    shift: true,
+                    ..KeyModifiers::NONE
+                }
+            )
+        );
+        assert_eq!(Some(b"\x1BOP".to_vec()), encode(&parser, &buf, Key::Function(1)));
+        assert_eq!(Some(b"\x1B[24~".to_vec()), encode(&parser, &buf, Key::Function(12)));
+        assert_eq!(Some(vec![0x03]), parser.encode_key(&buf, Key::Char('c'), CTRL));
+        assert_eq!(Some(vec![0x84]), encode(&parser, &buf, Key::Char('ä')));
+        buf.buffer_type = BufferType::Unicode;
+        assert_eq!(Some("ä".as_bytes().to_vec()), encode(&parser, &buf, Key::Char('ä')));
+    }
+
+    #[test]
+    fn test_avatar_keys() {
+        // avatar sends ANSI keys
+        let parser = avatar::Parser::default();
+        let mut buf = Buffer::new((80, 25));
+        assert_eq!(Some(b"\x1B[A".to_vec()), encode(&parser, &buf, Key::Up));
+        assert_eq!(Some(b"\x1B[3~".to_vec()), encode(&parser, &buf, Key::Delete));
+        assert_eq!(Some(vec![0x84]), encode(&parser, &buf, Key::Char('ä')));
+        buf.terminal_state.application_cursor_keys = true;
+        assert_eq!(Some(b"\x1BOB".to_vec()), encode(&parser, &buf, Key::Down));
+    }
+
+    #[test]
+    fn test_petscii_keys() {
+        let parser = petscii::Parser::default();
+        let buf = Buffer::new((40, 25));
+        assert_eq!(Some(vec![0x91]), encode(&parser, &buf, Key::Up));
+        assert_eq!(Some(vec![0x11]), encode(&parser, &buf, Key::Down));
+        assert_eq!(Some(vec![0x14]), encode(&parser, &buf, Key::Backspace));
+        assert_eq!(Some(vec![0x0D]), encode(&parser, &buf, Key::Enter));
+        assert_eq!(Some(vec![0x41]), encode(&parser, &buf, Key::Char('a')));
+        assert_eq!(Some(vec![0x61]), encode(&parser, &buf, Key::Char('A')));
+    }
+
+    #[test]
+    fn test_atascii_keys() {
+        let parser = atascii::Parser::default();
+        let buf = Buffer::new((40, 24));
+        assert_eq!(Some(vec![0x1C]), encode(&parser, &buf, Key::Up));
+        assert_eq!(Some(vec![0x1F]), encode(&parser, &buf, Key::Right));
+        assert_eq!(Some(vec![0x9B]), encode(&parser, &buf, Key::Enter));
+        assert_eq!(Some(vec![0x7E]), encode(&parser, &buf, Key::Backspace));
+    }
+
+    #[test]
+    fn test_viewdata_keys() {
+        let parser = viewdata::Parser::default();
+        let buf = Buffer::new((40, 24));
+        assert_eq!(Some(vec![0x0B]), encode(&parser, &buf, Key::Up));
+        assert_eq!(Some(vec![0x5F]), encode(&parser, &buf, Key::Enter));
+        assert_eq!(Some(vec![b'*']), encode(&parser, &buf, Key::Char('*')));
+
+        let parser = mode7::Parser::default();
+        assert_eq!(Some(vec![0x0D]), encode(&parser, &buf, Key::Enter));
+        assert_eq!(Some(vec![0x09]), encode(&parser, &buf, Key::Right));
+    }
+}
@@ -1,13 +1,19 @@
-use crate::{EngineResult, Line, Size, TextPane};
+use crate::{EngineResult, KeyModifiers, Layer, Line, LineSize, Size, TextPane};
 use std::cmp::{max, min};
 
 use self::{ansi::sound::AnsiMusic, rip::bgi::MouseField};
 
-use super::{AttributedChar, Buffer, Caret, Position};
+use super::{AttributedChar, Buffer, BufferType, Caret, Position};
 
 mod parser_errors;
 pub use parser_errors::*;
 
+mod keys;
+pub use keys::*;
+
+mod charsets;
+pub use charsets::*;
+
 pub mod ansi;
 pub mod ascii;
 pub mod atascii;
@@ -26,6 +32,8 @@ pub const LF: char = '\n';
 pub const CR: char = '\r';
 pub const BS: char = '\x08';
 pub const FF: char = '\x0C';
+pub const SO: char = '\x0E';
+pub const SI: char = '\x0F';
 
 #[derive(Debug, PartialEq)]
 pub enum CallbackAction {
@@ -38,6 +46,13 @@ pub enum CallbackAction {
     ResizeTerminal(i32, i32),
     /// Pause for milliseconds
     Pause(u32),
+    /// The window title was changed (OSC 0/2)
+    SetTitle(String),
+    /// The remote side sets the clipboard (OSC 52)
+    SetClipboard(String),
+    /// The remote side requests the clipboard contents (OSC 52) for the given selection,
+    /// answer with [`crate::TerminalState::encode_clipboard_response`]
+    QueryClipboard(String),
 }
 
 pub trait UnicodeConverter: Send + Sync {
@@ -66,6 +81,12 @@ pub trait BufferParser {
     fn get_picture_data(&mut self) -> Option<(Size, Vec<u8>)> {
         None
     }
+
+    /// Encodes a local key press as the bytes the remote side expects for this emulation.
+    /// Gives back `None` if the key can't be sent. The default is the ANSI/VT encoding.
+    fn encode_key(&self, buffer: &Buffer, key: Key, modifiers: KeyModifiers) -> Option<Vec<u8>> {
+        encode_ansi_key(buffer, key, modifiers)
+    }
 }
 
 impl Caret {
@@ -83,6 +104,15 @@ impl Caret {
         if !buf.is_terminal_buffer {
             return;
         }
+        if buf.is_alternate_screen_active() {
+            // the alternate screen has a fixed size - scroll instead of growing the buffer
+            if was_ooe {
+                buf.terminal_state.limit_caret_pos(buf, self);
+            } else {
+                self.check_scrolling_on_caret_down(buf, current_layer, true);
+            }
+            return;
+        }
         if self.pos.y + 1 > buf.get_height() {
             buf.set_height(self.pos.y + 1);
         }
@@ -129,6 +159,7 @@ impl Caret {
             if i < line.chars.len() {
                 line.chars.remove(i);
             }
+            line.repair_wide_chars();
         }
     }
 
@@ -138,6 +169,7 @@ impl Caret {
             if i < line.chars.len() {
                 line.chars.insert(i, AttributedChar::new(' ', self.attribute));
             }
+            line.repair_wide_chars();
         }
     }
 
@@ -152,6 +184,7 @@ impl Caret {
                 line.set_char(i, AttributedChar::new(' ', self.attribute));
                 i += 1;
             }
+            line.repair_wide_chars();
         }
     }
 
@@ -226,12 +259,39 @@ impl Buffer {
 
     pub fn print_char(&mut self, layer: usize, caret: &mut Caret, ch: AttributedChar) {
         let buffer_width = self.layers[layer].get_width();
+        let mut line_width = if self.is_terminal_buffer {
+            self.terminal_state.get_width()
+        } else {
+            buffer_width
+        };
+        if self.layers[layer]
+            .lines
+            .get(caret.pos.y as usize)
+            .is_some_and(|line| line.line_size.is_double_width())
+        {
+            line_width /= 2;
+        }
+        let is_wide = self.buffer_type == BufferType::Unicode && ch.is_wide();
+        if is_wide && caret.pos.x + 1 >= line_width {
+            // a double width character doesn't fit into the last column
+            if let crate::AutoWrapMode::AutoWrap = self.terminal_state.auto_wrap_mode {
+                if caret.pos.x > 0 {
+                    caret.lf(self, layer);
+                }
+            } else {
+                caret.pos.x = (line_width - 2).max(0);
+            }
+        }
+        let cells = if is_wide { 2 } else { 1 };
+
         if caret.insert_mode {
             let layer = &mut self.layers[layer];
             if layer.lines.len() < caret.pos.y as usize + 1 {
                 layer.lines.resize(caret.pos.y as usize + 1, Line::with_capacity(buffer_width));
             }
-            layer.lines[caret.pos.y as usize].insert_char(caret.pos.x, AttributedChar::default());
+            for _ in 0..cells {
+                layer.lines[caret.pos.y as usize].insert_char(caret.pos.x, AttributedChar::default());
+            }
         }
         if caret.pos.y + 1 > self.layers[layer].get_height() {
             self.layers[layer].set_height(caret.pos.y + 1);
@@ -241,14 +301,21 @@ impl Buffer {
         }
 
         self.layers[layer].set_char(caret.pos, ch);
-        caret.pos.x += 1;
-        if caret.pos.x
-            >= if self.is_terminal_buffer {
-                self.terminal_state.get_width()
-            } else {
-                buffer_width
+        if is_wide {
+            self.layers[layer].set_char(caret.pos + Position::new(1, 0), AttributedChar::wide_continuation(ch.attribute));
+        }
+        if self.buffer_type == BufferType::Unicode {
+            if let Some(line) = self.layers[layer].lines.get_mut(caret.pos.y as usize) {
+                if caret.insert_mode {
+                    line.repair_wide_chars();
+                } else {
+                    // only the cells next to the written char can lose their other half
+                    line.repair_wide_chars_in(caret.pos.x - 1..caret.pos.x + cells + 1);
+                }
             }
-        {
+        }
+        caret.pos.x += cells;
+        if caret.pos.x >= line_width {
             if let crate::AutoWrapMode::AutoWrap = self.terminal_state.auto_wrap_mode {
                 caret.lf(self, layer);
             } else {
@@ -257,6 +324,41 @@ impl Buffer {
         }
     }
 
+    /// Appends combining marks, variation selectors and zero width joined chars to the grapheme cluster
+    /// of the cell before the caret. Returns false if `ch` starts a new cell.
+    pub fn print_combining_char(&mut self, layer: usize, caret: &Caret, ch: char) -> bool {
+        if self.buffer_type != BufferType::Unicode {
+            return false;
+        }
+        let mut pos = caret.pos;
+        if pos.x > 0 {
+            pos.x -= 1;
+        } else if pos.y > 0 {
+            pos.y -= 1;
+            pos.x = if self.is_terminal_buffer {
+                self.terminal_state.get_width()
+            } else {
+                self.layers[layer].get_width()
+            } - 1;
+        } else {
+            return false;
+        }
+        if pos.x > 0 && self.layers[layer].get_char(pos).is_wide_continuation() {
+            pos.x -= 1;
+        }
+        let mut prev = self.layers[layer].get_char(pos);
+        if !crate::extends_grapheme_cluster(prev.ch, ch) {
+            return false;
+        }
+        if prev.push_to_cluster(ch) {
+            // the cell keeps the only reference to a grown cluster, Layer::set_char would seal it
+            if let Some(line) = self.layers[layer].lines.get_mut(pos.y as usize) {
+                line.set_char(pos.x, prev);
+            }
+        }
+        true
+    }
+
     fn scroll_up(&mut self, layer: usize) {
         let start_line: i32 = self.get_first_editable_line();
         let end_line = self.get_last_editable_line();
@@ -264,6 +366,15 @@ impl Buffer {
         let start_column = self.get_first_editable_column();
         let end_column = self.get_last_editable_column();
 
+        if self.is_terminal_buffer
+            && !self.is_alternate_screen_active()
+            && start_line == self.get_first_visible_line()
+            && start_column == 0
+            && end_column + 1 >= self.get_width()
+        {
+            self.push_scrollback_line(layer, start_line);
+        }
+
         let layer = &mut self.layers[layer];
         for x in start_column..=end_column {
             (start_line..end_line).for_each(|y| {
@@ -272,6 +383,43 @@ impl Buffer {
             });
             layer.set_char((x, end_line), AttributedChar::default());
         }
+        (start_line..end_line).for_each(|y| move_line_size(layer, y + 1, y));
+        set_line_size(layer, end_line, LineSize::Single);
+    }
+
+    /// Moves a line that's about to scroll off the screen into the scrollback, together with its hyperlinks and sixels.
+    fn push_scrollback_line(&mut self, layer: usize, y: i32) {
+        let width = self.get_width();
+        let layer = &mut self.layers[layer];
+        let mut line = Line::with_capacity(width);
+        for x in 0..width {
+            line.set_char(x, layer.get_char((x, y)));
+        }
+        line.line_size = layer.lines.get(y as usize).map_or(LineSize::Single, |line| line.line_size);
+
+        let mut hyperlinks = Vec::new();
+        layer.hyperlinks.retain(|link| {
+            if link.position.y != y {
+                return true;
+            }
+            let mut hyperlink = link.clone();
+            hyperlink.position.y = 0;
+            hyperlinks.push(hyperlink);
+            false
+        });
+
+        let mut sixels = Vec::new();
+        layer.sixels.retain(|sixel| {
+            if sixel.position.y != y {
+                return true;
+            }
+            let mut sixel = sixel.clone();
+            sixel.position.y = 0;
+            sixels.push(sixel);
+            false
+        });
+
+        self.scrollback.push(crate::ScrollbackLine { line, hyperlinks, sixels });
     }
 
     fn scroll_down(&mut self, layer: usize) {
@@ -289,6 +437,8 @@ impl Buffer {
             });
             layer.set_char((x, start_line), AttributedChar::default());
         }
+        ((start_line + 1)..=end_line).rev().for_each(|y| move_line_size(layer, y - 1, y));
+        set_line_size(layer, start_line, LineSize::Single);
     }
 
     fn scroll_left(&mut self, layer: usize) {
@@ -385,6 +535,9 @@ impl Buffer {
             pos.x = x;
             self.layers[layer].set_char(pos, ch);
         }
+        if let Some(line) = self.layers[layer].lines.get_mut(pos.y as usize) {
+            line.repair_wide_chars();
+        }
     }
 
     fn clear_line_start(&mut self, layer: usize, caret: &Caret) {
@@ -397,6 +550,9 @@ impl Buffer {
             pos.x = x;
             self.layers[layer].set_char(pos, ch);
         }
+        if let Some(line) = self.layers[layer].lines.get_mut(pos.y as usize) {
+            line.repair_wide_chars();
+        }
     }
 
     fn remove_terminal_line(&mut self, layer: usize, line: i32) {
@@ -421,6 +577,18 @@ impl Buffer {
     }
 }
 
+/// Moves the line attribute (DECDWL/DECDHL) of a line along with its scrolled contents.
+fn move_line_size(layer: &mut Layer, from: i32, to: i32) {
+    let line_size = layer.lines.get(from as usize).map_or(LineSize::Single, |line| line.line_size);
+    set_line_size(layer, to, line_size);
+}
+
+fn set_line_size(layer: &mut Layer, y: i32, line_size: LineSize) {
+    if let Some(line) = layer.lines.get_mut(y as usize) {
+        line.line_size = line_size;
+    }
+}
+
 #[cfg(test)]
 fn create_buffer<T: BufferParser>(parser: &mut T, input: &[u8]) -> (Buffer, Caret) {
     let mut buf: Buffer = Buffer::create((80, 25));
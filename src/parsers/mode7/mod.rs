@@ -165,6 +165,10 @@ impl UnicodeConverter for CharConverter {
 }
 
 impl BufferParser for Parser {
+    fn encode_key(&self, _buffer: &Buffer, key: super::Key, _modifiers: crate::KeyModifiers) -> Option<Vec<u8>> {
+        super::encode_viewdata_key(key, true)
+    }
+
     fn print_char(&mut self, buf: &mut Buffer, current_layer: usize, caret: &mut Caret, ch: char) -> EngineResult<CallbackAction> {
         let ch = ch as u8;
         match ch {
@@ -7,6 +7,7 @@ pub enum ParserError {
     UnsupportedEscapeSequence(String),
     UnsupportedDCSSequence(String),
     UnsupportedOSCSequence(String),
+    UnsupportedAPCSequence(String),
     UnsupportedCustomCommand(i32),
     Description(&'static str),
     UnsupportedControlCode(u32),
@@ -38,6 +39,9 @@ impl std::fmt::Display for ParserError {
             ParserError::UnsupportedOSCSequence(seq) => {
                 write!(f, "unsupported OSC sequence {seq}")
             }
+            ParserError::UnsupportedAPCSequence(seq) => {
+                write!(f, "unsupported APC sequence {seq}")
+            }
             ParserError::Description(str) => write!(f, "{str}"),
             ParserError::UnsupportedControlCode(code) => {
                 write!(f, "unsupported control code {}", *code)
@@ -1,5 +1,5 @@
 #![allow(clippy::match_same_arms)]
-use super::BufferParser;
+use super::{BufferParser, CharacterSet, CharacterSets};
 use crate::{AttributedChar, Buffer, CallbackAction, Caret, EngineResult, Position, TextPane, UnicodeConverter};
 
 mod constants;
@@ -20,6 +20,9 @@ pub struct Parser {
 
     graphics_bg: u32,
     alpha_bg: u32,
+
+    /// G1 is the mosaic set, the other sets are alphanumeric.
+    charsets: CharacterSets,
 }
 
 impl Default for Parser {
@@ -32,11 +35,16 @@ impl Default for Parser {
             is_in_graphic_mode: false,
             graphics_bg: 0,
             alpha_bg: 0,
+            charsets: Parser::default_charsets(),
         }
     }
 }
 
 impl Parser {
+    fn default_charsets() -> CharacterSets {
+        CharacterSets::new([CharacterSet::Ascii, CharacterSet::Mosaic, CharacterSet::Ascii, CharacterSet::Ascii])
+    }
+
     fn reset_screen(&mut self) {
         self.got_esc = false;
 
@@ -157,9 +165,11 @@ impl Parser {
         }
 
         let mut print_ch = ch;
+        // a single shift applies to the next printable char
+        let is_mosaic_set = !self.got_esc && ch >= 0x20 && self.charsets.next_set() == CharacterSet::Mosaic;
         if self.got_esc || ch < 0x20 {
             print_ch = if self.hold_graphics { self.held_graphics_character as u8 } else { b' ' };
-        } else if self.is_in_graphic_mode {
+        } else if self.is_in_graphic_mode || is_mosaic_set {
             if (0x20..0x40).contains(&ch) || (0x60..0x80).contains(&ch) {
                 if print_ch < 0x40 {
                     print_ch -= 0x20;
@@ -243,6 +253,10 @@ impl UnicodeConverter for CharConverter {
 }
 
 impl BufferParser for Parser {
+    fn encode_key(&self, _buffer: &Buffer, key: super::Key, _modifiers: crate::KeyModifiers) -> Option<Vec<u8>> {
+        super::encode_viewdata_key(key, false)
+    }
+
     fn print_char(&mut self, buf: &mut Buffer, current_layer: usize, caret: &mut Caret, ch: char) -> EngineResult<CallbackAction> {
         let ch = ch as u8;
         match ch {
@@ -279,17 +293,22 @@ impl BufferParser for Parser {
                 caret.reset_color_attribute();
 
                 self.reset_screen();
+                self.charsets = Parser::default_charsets();
             }
             0b000_1101 => {
                 // 13 / 0x0D
                 caret.cr(buf);
             }
             0b000_1110 => {
+                // SO - switch to G1 char set
+                self.charsets.locking_shift(1);
                 return Ok(CallbackAction::NoUpdate);
-            } // TODO: SO - switch to G1 char set
+            }
             0b000_1111 => {
+                // SI - switch to G0 char set
+                self.charsets.locking_shift(0);
                 return Ok(CallbackAction::NoUpdate);
-            } // TODO: SI - switch to G0 char set
+            }
 
             // control codes 1
             0b001_0000 => {} // ignore
@@ -308,11 +327,15 @@ impl BufferParser for Parser {
                 return Ok(CallbackAction::NoUpdate);
             } // 0x1B ESC
             0b001_1100 => {
+                // SS2 - switch to G2 char set for the next char
+                self.charsets.single_shift(2);
                 return Ok(CallbackAction::NoUpdate);
-            } // TODO: SS2 - switch to G2 char set
+            }
             0b001_1101 => {
+                // SS3 - switch to G3 char set for the next char
+                self.charsets.single_shift(3);
                 return Ok(CallbackAction::NoUpdate);
-            } // TODO: SS3 - switch to G3 char set
+            }
             0b001_1110 => {
                 // 28 / 0x1E
                 caret.home(buf);
@@ -185,3 +185,17 @@ fn test_caret_visibility() {
     let (_, caret) = create_viewdata_buffer(&mut Parser::default(), b"\x14\x0C");
     assert!(!caret.is_visible());
 }
+
+#[test]
+fn test_shift_out_mosaics() {
+    // SO invokes the mosaic set, SI switches back to alphanumerics
+    let (buf, _) = create_viewdata_buffer(&mut Parser::default(), b"a\x0Ea\x0Fa");
+    assert_eq!('a', buf.get_char(Position::new(0, 0)).ch);
+    assert_eq!(0x80 + 0x21, buf.get_char(Position::new(1, 0)).ch as u32);
+    assert_eq!('a', buf.get_char(Position::new(2, 0)).ch);
+
+    // SS2 & SS3 only apply to the next char
+    let (buf, _) = create_viewdata_buffer(&mut Parser::default(), b"\x0E\x1Ca\x1Da");
+    assert_eq!('a', buf.get_char(Position::new(0, 0)).ch);
+    assert_eq!('a', buf.get_char(Position::new(1, 0)).ch);
+}
@@ -0,0 +1,398 @@
+use std::path::Path;
+
+use crate::{AttributedChar, BitFont, Buffer, EngineResult, Glyph, IceMode, LineSize, Rectangle, Size, TextAttribute, TextPane};
+
+/// Fonts without a sauce entry are stretched like the VGA text mode.
+const DEFAULT_SAUCE_FONT: &str = "IBM VGA";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderOptions {
+    /// Renders 8 pixel wide fonts with 9 pixel columns like the VGA text mode.
+    /// The line graphics chars 0xC0-0xDF repeat their last column, all others get an empty one.
+    pub use_letter_spacing: bool,
+
+    /// Stretches the output vertically to the aspect ratio of the legacy display.
+    pub use_aspect_ratio: bool,
+
+    /// When not set blinking chars are shown in their off phase.
+    pub show_blinking: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            use_letter_spacing: false,
+            use_aspect_ratio: false,
+            show_blinking: true,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Takes the letter spacing and aspect ratio flags from the sauce data of the buffer.
+    pub fn from_buffer(buf: &Buffer) -> Self {
+        Self {
+            use_letter_spacing: buf.use_letter_spacing(),
+            use_aspect_ratio: buf.use_aspect_ratio(),
+            show_blinking: true,
+        }
+    }
+}
+
+/// Glyph rows take as many bytes as needed for the font width, the leftmost pixel is the high bit.
+fn is_glyph_pixel_set(glyph: &Glyph, font_size: Size, x: i32, y: i32) -> bool {
+    if x < 0 || y < 0 || x >= font_size.width || y >= font_size.height {
+        return false;
+    }
+    let bytes_per_row = (font_size.width + 7) / 8;
+    glyph
+        .data
+        .get((y * bytes_per_row + x / 8) as usize)
+        .is_some_and(|row| row & (128 >> (x % 8)) != 0)
+}
+
+fn is_line_graphics_char(ch: char) -> bool {
+    (0xC0..=0xDF).contains(&(ch as u32))
+}
+
+type Rgb = (u8, u8, u8);
+
+fn get_color(buf: &Buffer, color: u32) -> Option<Rgb> {
+    if color == TextAttribute::TRANSPARENT_COLOR {
+        None
+    } else {
+        Some(buf.palette.get_rgb(color))
+    }
+}
+
+impl Buffer {
+    /// The font chars are rendered with - font 0 or the first available font if slot 0 is empty.
+    fn get_render_font(&self) -> Option<&BitFont> {
+        self.get_font(0)
+            .or_else(|| self.font_iter().min_by_key(|(slot, _)| **slot).map(|(_, font)| font))
+    }
+
+    /// Vertical stretch for a 4:3 display - taken from the sauce font entry and the column width.
+    fn get_aspect_ratio_stretch(&self, font_name: &str, use_letter_spacing: bool) -> f32 {
+        let sauce_font = self.get_sauce().as_ref().and_then(|sauce| sauce.font_opt.as_deref());
+        sauce_font
+            .and_then(|name| BitFont::get_sauce_aspect_ratio_stretch(name, use_letter_spacing))
+            .or_else(|| BitFont::get_sauce_aspect_ratio_stretch(font_name, use_letter_spacing))
+            .or_else(|| BitFont::get_sauce_aspect_ratio_stretch(DEFAULT_SAUCE_FONT, use_letter_spacing))
+            .unwrap_or(1.0)
+    }
+
+    /// Renders all visible layers of the rectangle to RGBA pixels. Transparent and empty cells stay transparent.
+    pub fn render_with_options(&self, rect: Rectangle, options: &RenderOptions) -> (Size, Vec<u8>) {
+        let Some(render_font) = self.get_render_font() else {
+            return (Size::new(0, 0), Vec::new());
+        };
+        let font_size = render_font.size;
+        let cell_width = if options.use_letter_spacing && font_size.width == 8 {
+            9
+        } else {
+            font_size.width
+        };
+        let cell_size = Size::new(cell_width, font_size.height);
+
+        let px_width = rect.get_width() * cell_size.width;
+        let px_height = rect.get_height() * cell_size.height;
+        let line_bytes = px_width * 4;
+        let mut pixels = vec![0; (line_bytes * px_height) as usize];
+
+        for y in 0..rect.get_height() {
+            // double width/height lines show the left half of the line scaled
+            let line_size = self.get_line_size(y + rect.start.y);
+            for x in 0..rect.get_width() {
+                let column = x + rect.start.x;
+                let ch = if line_size.is_double_width() {
+                    self.get_char((column / 2, y + rect.start.y))
+                } else {
+                    self.get_char((column, y + rect.start.y))
+                };
+                if !ch.is_visible() {
+                    continue;
+                }
+                let font = self.get_font(ch.get_font_page()).unwrap_or(render_font);
+                let cell_pixel = |cx: i32, cy: i32| {
+                    // pixel position inside of the scaled cell
+                    let sx = if line_size.is_double_width() {
+                        column % 2 * cell_size.width + cx
+                    } else {
+                        2 * cx
+                    };
+                    let sy = match line_size {
+                        LineSize::DoubleHeightTop => cy,
+                        LineSize::DoubleHeightBottom => cell_size.height + cy,
+                        _ => 2 * cy,
+                    };
+                    (sx / 2, sy / 2)
+                };
+
+                // combining marks of a grapheme cluster are drawn on top of the base char
+                let cluster = crate::get_grapheme_cluster(ch.ch);
+                let base_char = cluster.as_ref().and_then(|cluster| cluster.chars().next()).unwrap_or(ch.ch);
+                let is_set = |glyph: &Glyph, gx: i32, gy: i32| {
+                    if gx >= font.size.width && gx < cell_size.width {
+                        // letter spacing column
+                        return is_line_graphics_char(base_char) && is_glyph_pixel_set(glyph, font.size, font.size.width - 1, gy);
+                    }
+                    is_glyph_pixel_set(glyph, font.size, gx, gy)
+                };
+
+                let (fg, bg) = self.get_render_colors(ch, options.show_blinking);
+                let mut glyphs: Vec<&Glyph> = font.get_glyph(base_char).into_iter().collect();
+                if let Some(cluster) = &cluster {
+                    glyphs.extend(cluster.chars().skip(1).filter_map(|mark| font.get_glyph(mark)));
+                }
+
+                for cy in 0..cell_size.height {
+                    for cx in 0..cell_size.width {
+                        let (gx, gy) = cell_pixel(cx, cy);
+                        let color = if glyphs.iter().any(|glyph| is_set(glyph, gx, gy)) { fg } else { bg };
+                        if let Some((r, g, b)) = color {
+                            let offset = ((x * cell_size.width + cx) * 4 + (y * cell_size.height + cy) * line_bytes) as usize;
+                            pixels[offset] = r;
+                            pixels[offset + 1] = g;
+                            pixels[offset + 2] = b;
+                            pixels[offset + 3] = 0xFF;
+                        }
+                    }
+                }
+            }
+        }
+
+        for layer in self.layers.iter().filter(|layer| layer.properties.is_visible) {
+            for sixel in &layer.sixels {
+                let sx = layer.get_offset().x + sixel.position.x - rect.start.x;
+                let sx_px = sx * cell_size.width;
+                let sy = layer.get_offset().y + sixel.position.y - rect.start.y;
+                let sy_pix = sy * cell_size.height;
+                if sx_px < 0 || sx_px >= px_width {
+                    continue;
+                }
+                let sixel_line_bytes = (sixel.get_width().min(px_width - sx_px) * 4) as usize;
+                let sixel_stride = (sixel.get_width() * 4) as usize;
+
+                for (sixel_line, y) in (sy_pix..(sy_pix + sixel.get_height())).enumerate() {
+                    if y < 0 {
+                        continue;
+                    }
+                    let offset = y as usize * line_bytes as usize + sx_px as usize * 4;
+                    let o = sixel_line * sixel_stride;
+                    if offset + sixel_line_bytes > pixels.len() {
+                        break;
+                    }
+                    pixels[offset..(offset + sixel_line_bytes)].copy_from_slice(&sixel.picture_data[o..(o + sixel_line_bytes)]);
+                }
+            }
+        }
+
+        if options.use_aspect_ratio {
+            let use_letter_spacing = cell_size.width != font_size.width;
+            let stretch = self.get_aspect_ratio_stretch(&render_font.name, use_letter_spacing);
+            return stretch_vertically(Size::new(px_width, px_height), &pixels, stretch);
+        }
+        (Size::new(px_width, px_height), pixels)
+    }
+
+    /// Foreground and background color, `None` is transparent.
+    fn get_render_colors(&self, ch: AttributedChar, show_blinking: bool) -> (Option<Rgb>, Option<Rgb>) {
+        let fg = if ch.attribute.is_bold() && ch.attribute.get_foreground() < 8 {
+            ch.attribute.get_foreground() + 8
+        } else {
+            ch.attribute.get_foreground()
+        };
+        let bg = get_color(self, ch.attribute.get_background());
+        if !show_blinking && ch.attribute.is_blinking() && self.ice_mode != IceMode::Ice {
+            return (bg, bg);
+        }
+        (get_color(self, fg), bg)
+    }
+
+    /// Renders the rectangle and encodes it as png.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the png encoding fails.
+    pub fn render_to_png(&self, rect: Rectangle, options: &RenderOptions) -> EngineResult<Vec<u8>> {
+        let (size, pixels) = self.render_with_options(rect, options);
+        let mut result = Vec::new();
+        let mut encoder = png::Encoder::new(&mut result, size.width as u32, size.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&pixels)?;
+        writer.finish()?;
+        Ok(result)
+    }
+
+    /// Renders the whole buffer with the sauce settings and writes it as png file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the png encoding or writing the file fails.
+    pub fn save_png(&self, file_name: &Path) -> EngineResult<()> {
+        let rect = Rectangle::from(0, 0, self.get_width(), self.get_height());
+        let data = self.render_to_png(rect, &RenderOptions::from_buffer(self))?;
+        std::fs::write(file_name, data)?;
+        Ok(())
+    }
+}
+
+/// Nearest neighbour scaling of the image height.
+fn stretch_vertically(size: Size, pixels: &[u8], factor: f32) -> (Size, Vec<u8>) {
+    let height = (size.height as f32 * factor).round() as i32;
+    let line_bytes = (size.width * 4) as usize;
+    let mut result = Vec::with_capacity(line_bytes * height as usize);
+    for y in 0..height {
+        let src_y = ((y as f32 / factor) as usize).min(size.height as usize - 1);
+        result.extend_from_slice(&pixels[src_y * line_bytes..(src_y + 1) * line_bytes]);
+    }
+    (Size::new(size.width, height), result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AttributedChar, BitFont, Buffer, IceMode, Rectangle, RenderOptions, TextAttribute};
+
+    fn pixel(size: crate::Size, pixels: &[u8], x: i32, y: i32) -> [u8; 4] {
+        let o = ((y * size.width + x) * 4) as usize;
+        pixels[o..o + 4].try_into().unwrap()
+    }
+
+    fn create_buffer(ch: char) -> Buffer {
+        let mut buf = Buffer::new((2, 1));
+        buf.layers[0].set_char((0, 0), AttributedChar::new(ch, TextAttribute::default()));
+        buf
+    }
+
+    #[test]
+    fn test_letter_spacing() {
+        let buf = create_buffer('\u{C4}');
+        let options = RenderOptions {
+            use_letter_spacing: true,
+            ..Default::default()
+        };
+        let (size, pixels) = buf.render_with_options(Rectangle::from(0, 0, 2, 1), &options);
+        assert_eq!(18, size.width);
+        // the horizontal line graphic continues in the 9th column
+        for y in 0..size.height {
+            assert_eq!(pixel(size, &pixels, 7, y), pixel(size, &pixels, 8, y));
+        }
+
+        let buf = create_buffer('A');
+        let (size, pixels) = buf.render_with_options(Rectangle::from(0, 0, 2, 1), &options);
+        for y in 0..size.height {
+            assert_eq!([0, 0, 0, 0xFF], pixel(size, &pixels, 8, y));
+        }
+    }
+
+    #[test]
+    fn test_aspect_ratio() {
+        let buf = create_buffer('A');
+        let options = RenderOptions {
+            use_aspect_ratio: true,
+            ..Default::default()
+        };
+        let (size, pixels) = buf.render_with_options(Rectangle::from(0, 0, 2, 1), &options);
+        // 640x400 -> 640x480
+        assert_eq!(19, size.height);
+        assert_eq!((size.width * size.height * 4) as usize, pixels.len());
+
+        let letter_spacing = RenderOptions {
+            use_letter_spacing: true,
+            ..options
+        };
+        let (size, _) = buf.render_with_options(Rectangle::from(0, 0, 2, 1), &letter_spacing);
+        // 720x400 -> 720x540
+        assert_eq!(22, size.height);
+
+        let mut buf = create_buffer('A');
+        buf.set_font(0, BitFont::from_sauce_name("C64 PETSCII shifted").unwrap());
+        let (size, _) = buf.render_with_options(Rectangle::from(0, 0, 2, 1), &options);
+        assert_eq!((buf.get_font(0).unwrap().size.height as f32 * 1.2).round() as i32, size.height);
+
+        buf.set_font(0, BitFont::from_sauce_name("IBM VGA25G").unwrap());
+        let (size, _) = buf.render_with_options(Rectangle::from(0, 0, 2, 1), &letter_spacing);
+        assert_eq!(buf.get_font(0).unwrap().size.height, size.height);
+    }
+
+    #[test]
+    fn test_transparent_cells() {
+        let mut buf = create_buffer('A');
+        buf.layers[0].properties.has_alpha_channel = true;
+        buf.layers[0].set_char((1, 0), AttributedChar::invisible());
+        let (size, pixels) = buf.render_with_options(Rectangle::from(0, 0, 2, 1), &RenderOptions::default());
+        assert_eq!(0xFF, pixel(size, &pixels, 0, 0)[3]);
+        assert_eq!(0, pixel(size, &pixels, 8, 0)[3]);
+    }
+
+    #[test]
+    fn test_layer_offset() {
+        let mut buf = Buffer::new((2, 1));
+        buf.layers[0].set_char((0, 0), AttributedChar::new('\u{DB}', TextAttribute::default()));
+        let mut layer = crate::Layer::new("top", (1, 1));
+        layer.set_offset((1, 0));
+        layer.set_char((0, 0), AttributedChar::new('\u{DB}', TextAttribute::default()));
+        buf.layers.push(layer);
+        let (size, pixels) = buf.render_with_options(Rectangle::from(0, 0, 2, 1), &RenderOptions::default());
+        assert_eq!(pixel(size, &pixels, 0, 0), pixel(size, &pixels, 8, 0));
+
+        buf.layers[1].properties.is_visible = false;
+        let (size, pixels) = buf.render_with_options(Rectangle::from(0, 0, 2, 1), &RenderOptions::default());
+        assert_ne!(pixel(size, &pixels, 0, 0), pixel(size, &pixels, 8, 0));
+    }
+
+    #[test]
+    fn test_blink_phase() {
+        let mut buf = Buffer::new((1, 1));
+        buf.ice_mode = IceMode::Blink;
+        let mut attr = TextAttribute::default();
+        attr.set_is_blinking(true);
+        buf.layers[0].set_char((0, 0), AttributedChar::new('\u{DB}', attr));
+        let options = RenderOptions {
+            show_blinking: false,
+            ..Default::default()
+        };
+        let (size, pixels) = buf.render_with_options(Rectangle::from(0, 0, 1, 1), &options);
+        assert_eq!([0, 0, 0, 0xFF], pixel(size, &pixels, 4, 4));
+    }
+
+    #[test]
+    fn test_missing_font_zero() {
+        let mut buf = create_buffer('A');
+        buf.clear_font_table();
+        buf.set_font(1, BitFont::default());
+        let (size, _) = buf.render_with_options(Rectangle::from(0, 0, 2, 1), &RenderOptions::default());
+        assert_eq!(16, size.width);
+    }
+
+    #[test]
+    fn test_wide_glyphs() {
+        let mut font = BitFont::default();
+        font.size = crate::Size::new(10, 16);
+        for glyph in font.glyphs.values_mut() {
+            glyph.data = [0xFF, 0xC0].repeat(16);
+        }
+        let mut buf = create_buffer('A');
+        buf.set_font(0, font);
+        let (size, pixels) = buf.render_with_options(Rectangle::from(0, 0, 1, 1), &RenderOptions::default());
+        assert_eq!(10, size.width);
+        assert_eq!([0xAA, 0xAA, 0xAA, 0xFF], pixel(size, &pixels, 9, 0));
+    }
+
+    #[test]
+    fn test_wide_font_psf2() {
+        let mut font = BitFont::default();
+        font.size = crate::Size::new(10, 16);
+        for glyph in font.glyphs.values_mut() {
+            glyph.data = [0xFF, 0xC0].repeat(16);
+        }
+        let data = font.to_psf2_bytes().unwrap();
+        assert_eq!(32, u32::from_le_bytes(data[20..24].try_into().unwrap()));
+        let loaded = BitFont::from_bytes("wide", &data).unwrap();
+        assert_eq!(font.size, loaded.size);
+        assert_eq!(font.glyphs[&'A'], loaded.glyphs[&'A']);
+    }
+}
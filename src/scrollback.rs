@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+
+use crate::{AttributedChar, HyperLink, Line, Sixel};
+
+/// Default amount of lines kept in the scrollback of a terminal buffer.
+pub const DEFAULT_SCROLLBACK_LINES: usize = 2000;
+
+/// A line that scrolled off the top of a terminal screen.
+///
+/// Hyperlinks and sixels are stored relative to the line - their `y` position is always 0.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ScrollbackLine {
+    pub line: Line,
+    pub hyperlinks: Vec<HyperLink>,
+    pub sixels: Vec<Sixel>,
+}
+
+impl ScrollbackLine {
+    pub fn get_char(&self, x: i32) -> AttributedChar {
+        if x < 0 || x as usize >= self.line.chars.len() {
+            return AttributedChar::default();
+        }
+        self.line.chars[x as usize]
+    }
+}
+
+/// Bounded history of lines lost by scrolling a terminal buffer.
+/// Index 0 is the oldest line, the last index the line directly above the screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scrollback {
+    lines: VecDeque<ScrollbackLine>,
+    max_lines: usize,
+}
+
+impl Default for Scrollback {
+    fn default() -> Self {
+        Self::new(DEFAULT_SCROLLBACK_LINES)
+    }
+}
+
+impl Scrollback {
+    pub fn new(max_lines: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            max_lines,
+        }
+    }
+
+    pub fn get_max_lines(&self) -> usize {
+        self.max_lines
+    }
+
+    /// Changes the line limit, dropping the oldest lines if the history is too long.
+    /// A limit of 0 disables the scrollback.
+    pub fn set_max_lines(&mut self, max_lines: usize) {
+        self.max_lines = max_lines;
+        self.truncate();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    pub fn push(&mut self, line: ScrollbackLine) {
+        if self.max_lines == 0 {
+            return;
+        }
+        self.lines.push_back(line);
+        self.truncate();
+    }
+
+    pub fn get_line(&self, index: usize) -> Option<&ScrollbackLine> {
+        self.lines.get(index)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &ScrollbackLine> + ExactSizeIterator {
+        self.lines.iter()
+    }
+
+    fn truncate(&mut self) {
+        while self.lines.len() > self.max_lines {
+            self.lines.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AttributedChar, Line, Scrollback, ScrollbackLine, TextAttribute};
+
+    fn line_with(ch: char) -> ScrollbackLine {
+        let mut line = Line::new();
+        line.set_char(0, AttributedChar::new(ch, TextAttribute::default()));
+        ScrollbackLine { line, ..Default::default() }
+    }
+
+    #[test]
+    fn test_bounded_history() {
+        let mut scrollback = Scrollback::new(2);
+        scrollback.push(line_with('a'));
+        scrollback.push(line_with('b'));
+        scrollback.push(line_with('c'));
+        assert_eq!(2, scrollback.len());
+        assert_eq!('b', scrollback.get_line(0).unwrap().get_char(0).ch);
+        assert_eq!('c', scrollback.iter().last().unwrap().get_char(0).ch);
+
+        scrollback.set_max_lines(1);
+        assert_eq!(1, scrollback.len());
+        assert_eq!('c', scrollback.get_line(0).unwrap().get_char(0).ch);
+
+        scrollback.clear();
+        assert!(scrollback.is_empty());
+    }
+
+    #[test]
+    fn test_disabled_history() {
+        let mut scrollback = Scrollback::new(0);
+        scrollback.push(line_with('a'));
+        assert!(scrollback.is_empty());
+    }
+}
@@ -1,5 +1,13 @@
 use crate::{ansi::parse_next_number, EngineResult, Palette, ParserError, Position, Rectangle, Size};
 
+/// Largest picture that is decoded from png or scaled to - 64 MiB of RGBA data.
+const MAX_PICTURE_DATA_SIZE: usize = 64 * 1024 * 1024;
+
+/// Gives back the RGBA data size of a picture, `None` if it exceeds the limit.
+fn get_picture_data_size(width: usize, height: usize) -> Option<usize> {
+    width.checked_mul(height)?.checked_mul(4).filter(|size| *size <= MAX_PICTURE_DATA_SIZE)
+}
+
 #[derive(Clone, Debug, Copy)]
 pub enum SixelState {
     Read,
@@ -15,6 +23,8 @@ pub struct Sixel {
     pub vertical_scale: i32,
     pub horizontal_scale: i32,
     pub picture_data: Vec<u8>,
+    /// Id of the kitty graphics protocol image this picture was placed from (0 for anonymous images), `None` for sixels.
+    pub image_id: Option<u32>,
 
     size: Size,
 }
@@ -66,6 +76,7 @@ impl SixelParser {
             vertical_scale: self.vertical_scale,
             horizontal_scale: self.horizontal_scale,
             picture_data,
+            image_id: None,
             size: (self.width(), self.height()).into(),
         })
     }
@@ -275,6 +286,7 @@ impl Sixel {
             vertical_scale: 1,
             horizontal_scale: 1,
             picture_data: Vec::new(),
+            image_id: None,
             size: Size::default(),
         }
     }
@@ -285,6 +297,7 @@ impl Sixel {
             vertical_scale,
             horizontal_scale,
             picture_data: data,
+            image_id: None,
             size: size.into(),
         }
     }
@@ -327,6 +340,54 @@ impl Sixel {
         parser.parse_from(default_bg_color, data)
     }
 
+    /// Decodes a PNG image into a picture.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the PNG data is invalid.
+    pub fn from_png(data: &[u8]) -> EngineResult<Self> {
+        let mut decoder = png::Decoder::new(data);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
+        let (width, height) = (reader.info().width, reader.info().height);
+        if get_picture_data_size(width as usize, height as usize).is_none() {
+            return Err(ParserError::Error(format!("png too large: {width}x{height}")).into());
+        }
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        let pixels = &buffer[..info.buffer_size()];
+        let picture_data = match info.color_type {
+            png::ColorType::Rgba => pixels.to_vec(),
+            png::ColorType::Rgb => pixels.chunks_exact(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xFF]).collect(),
+            png::ColorType::GrayscaleAlpha => pixels.chunks_exact(2).flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]]).collect(),
+            png::ColorType::Grayscale => pixels.iter().flat_map(|g| [*g, *g, *g, 0xFF]).collect(),
+            png::ColorType::Indexed => return Err(ParserError::Error("unexpanded palette png".to_string()).into()),
+        };
+        Ok(Self::from_data((info.width as i32, info.height as i32), 1, 1, picture_data))
+    }
+
+    /// Scales the picture to a new size (nearest neighbor).
+    pub fn resize(&mut self, size: Size) {
+        if size == self.size || size.width <= 0 || size.height <= 0 || self.size.width <= 0 || self.size.height <= 0 {
+            return;
+        }
+        let Some(data_size) = get_picture_data_size(size.width as usize, size.height as usize) else {
+            log::warn!("can't scale picture to {}x{}, it would get too large", size.width, size.height);
+            return;
+        };
+        let mut picture_data = Vec::with_capacity(data_size);
+        for y in 0..size.height {
+            let src_y = (y as i64 * self.size.height as i64 / size.height as i64) as usize;
+            for x in 0..size.width {
+                let src_x = (x as i64 * self.size.width as i64 / size.width as i64) as usize;
+                let offset = (src_y * self.size.width as usize + src_x) * 4;
+                picture_data.extend_from_slice(&self.picture_data[offset..offset + 4]);
+            }
+        }
+        self.picture_data = picture_data;
+        self.size = size;
+    }
+
     pub fn get_width(&self) -> i32 {
         self.size.width
     }
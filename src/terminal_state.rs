@@ -1,4 +1,6 @@
-use crate::{ansi::BaudEmulation, Buffer, Caret, Rectangle, Size};
+use base64::{engine::general_purpose, Engine};
+
+use crate::{ansi::BaudEmulation, Buffer, Caret, Color, Rectangle, Size};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TerminalScrolling {
@@ -18,6 +20,13 @@ pub enum AutoWrapMode {
     AutoWrap,
 }
 
+/// Area DECCARA and DECRARA apply to, selected with DECSACE
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttributeChangeExtent {
+    Stream,
+    Rectangle,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FontSelectionState {
     NoRequest,
@@ -25,6 +34,9 @@ pub enum FontSelectionState {
     Failure,
 }
 
+pub const BRACKETED_PASTE_START: &str = "\x1B[200~";
+pub const BRACKETED_PASTE_END: &str = "\x1B[201~";
+
 #[derive(Debug, Clone)]
 pub struct TerminalState {
     size: Size,
@@ -32,10 +44,29 @@ pub struct TerminalState {
     pub origin_mode: OriginMode,
     pub scroll_state: TerminalScrolling,
     pub auto_wrap_mode: AutoWrapMode,
+    pub attribute_change_extent: AttributeChangeExtent,
     margins_top_bottom: Option<(i32, i32)>,
     margins_left_right: Option<(i32, i32)>,
     pub mouse_mode: MouseMode,
+    pub mouse_encoding: MouseEncoding,
+    /// Wheel events are sent as cursor keys on the alternate screen (DECSET 1007)
+    pub alternate_scroll_mode: bool,
     pub dec_margin_mode_left_right: bool,
+    /// Cursor keys send application sequences (DECCKM)
+    pub application_cursor_keys: bool,
+    /// Bracketed paste mode (DECSET 2004)
+    pub bracketed_paste_mode: bool,
+    /// Send focus in/out events (DECSET 1004)
+    pub focus_event_mode: bool,
+
+    /// Window title (OSC 0/2)
+    pub title: String,
+    /// Icon name (OSC 0/1)
+    pub icon_name: String,
+    /// Working directory of the remote shell as file url (OSC 7)
+    pub working_directory: Option<String>,
+    /// Cursor color set with OSC 12, `None` draws the cursor in the default foreground color
+    pub cursor_color: Option<Color>,
 
     pub font_selection_state: FontSelectionState,
 
@@ -62,16 +93,54 @@ pub enum MouseMode {
     #[allow(non_camel_case_types)]
     VT200_Highlight,
 
+    /// Button event tracking (1002)
     ButtonEvents,
+    /// Any event tracking (1003)
     AnyEvents,
+
+    // The modes below aren't tracking modes and are never set by the parser anymore.
+    #[deprecated(note = "focus events are reported with `TerminalState::focus_event_mode`")]
     FocusEvent,
+    #[deprecated(note = "alternate scroll mode is `TerminalState::alternate_scroll_mode`")]
     AlternateScroll,
+    #[deprecated(note = "use `MouseEncoding::Utf8`")]
     ExtendedMode,
+    #[deprecated(note = "use `MouseEncoding::Sgr`")]
     SGRExtendedMode,
+    #[deprecated(note = "use `MouseEncoding::Urxvt`")]
     URXVTExtendedMode,
+    #[deprecated(note = "use `MouseEncoding::SgrPixels`")]
     PixelPosition,
 }
 
+/// The coordinate encoding of the mouse reports, set independently of the tracking `MouseMode`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MouseEncoding {
+    /// `CSI M Cb Cx Cy` with byte values
+    Default,
+    /// Extended UTF-8 mode (1005)
+    Utf8,
+    /// SGR mode (1006)
+    Sgr,
+    /// URXVT mode (1015)
+    Urxvt,
+    /// SGR mode reporting pixel positions (1016)
+    SgrPixels,
+}
+
+impl MouseEncoding {
+    /// The encoding set by the DEC private mode `mode`.
+    pub fn from_mode(mode: i32) -> Option<Self> {
+        match mode {
+            1005 => Some(MouseEncoding::Utf8),
+            1006 => Some(MouseEncoding::Sgr),
+            1015 => Some(MouseEncoding::Urxvt),
+            1016 => Some(MouseEncoding::SgrPixels),
+            _ => None,
+        }
+    }
+}
+
 impl TerminalState {
     pub fn from(size: impl Into<Size>) -> Self {
         let mut ret = Self {
@@ -79,10 +148,20 @@ impl TerminalState {
             scroll_state: TerminalScrolling::Smooth,
             origin_mode: OriginMode::UpperLeftCorner,
             auto_wrap_mode: AutoWrapMode::AutoWrap,
+            attribute_change_extent: AttributeChangeExtent::Stream,
             mouse_mode: MouseMode::Default,
+            mouse_encoding: MouseEncoding::Default,
+            alternate_scroll_mode: false,
             margins_top_bottom: None,
             margins_left_right: None,
             dec_margin_mode_left_right: false,
+            application_cursor_keys: false,
+            bracketed_paste_mode: false,
+            focus_event_mode: false,
+            title: String::new(),
+            icon_name: String::new(),
+            working_directory: None,
+            cursor_color: None,
             baud_rate: BaudEmulation::Off,
             tab_stops: vec![],
             font_selection_state: FontSelectionState::NoRequest,
@@ -187,20 +266,25 @@ impl TerminalState {
     }
 
     pub fn limit_caret_pos(&self, buf: &Buffer, caret: &mut Caret) {
+        let width = if buf.get_line_size(caret.pos.y).is_double_width() {
+            self.get_width() / 2
+        } else {
+            self.get_width()
+        };
         match self.origin_mode {
             crate::OriginMode::UpperLeftCorner => {
                 if buf.is_terminal_buffer {
                     let first = buf.get_first_visible_line();
                     caret.pos.y = caret.pos.y.clamp(first, first + self.get_height() - 1);
                 }
-                caret.pos.x = caret.pos.x.clamp(0, (self.get_width() - 1).max(0));
+                caret.pos.x = caret.pos.x.clamp(0, (width - 1).max(0));
             }
             crate::OriginMode::WithinMargins => {
                 let first = buf.get_first_editable_line();
                 let height = buf.get_last_editable_line() - first;
                 let n = caret.pos.y.clamp(first, (first + height - 1).max(first));
                 caret.pos.y = n;
-                caret.pos.x = caret.pos.x.clamp(0, (self.get_width() - 1).max(0));
+                caret.pos.x = caret.pos.x.clamp(0, (width - 1).max(0));
             }
         }
     }
@@ -240,4 +324,29 @@ impl TerminalState {
         self.clear_margins_top_bottom();
         self.clear_margins_left_right();
     }
+
+    /// Encodes pasted text (clipboard contents or a copied selection) for sending it to the remote side.
+    /// Line breaks are sent as CR. In bracketed paste mode the text is wrapped in `ESC[200~` and `ESC[201~`,
+    /// all ESC chars of the text are removed (like xterm does) so the pasted data can't end the paste.
+    pub fn encode_paste(&self, text: &str) -> String {
+        let text = text.replace("\r\n", "\r").replace('\n', "\r");
+        if self.bracketed_paste_mode {
+            format!("{BRACKETED_PASTE_START}{}{BRACKETED_PASTE_END}", text.replace('\x1B', ""))
+        } else {
+            text
+        }
+    }
+
+    /// Gives back the sequence to send when the terminal window gains or loses the focus, if the remote side requested focus events.
+    pub fn encode_focus_event(&self, has_focus: bool) -> Option<String> {
+        if !self.focus_event_mode {
+            return None;
+        }
+        Some(if has_focus { "\x1B[I" } else { "\x1B[O" }.to_string())
+    }
+
+    /// Encodes the answer to an OSC 52 clipboard query for the selection given by [`crate::CallbackAction::QueryClipboard`].
+    pub fn encode_clipboard_response(selection: &str, text: &str) -> String {
+        format!("\x1B]52;{selection};{}\x1B\\", general_purpose::STANDARD.encode(text))
+    }
 }
@@ -13,6 +13,12 @@ pub mod attribute {
     pub const CROSSED_OUT: u16 = 0b0000_0000_1000_0000;
     pub const DOUBLE_HEIGHT: u16 = 0b0000_0001_0000_0000;
     pub const OVERLINE: u16 = 0b0000_0010_0000_0000;
+    /// Marks the right half cell of a double width (CJK/emoji) character.
+    pub const WIDE_CONTINUATION: u16 = 0b0000_0100_0000_0000;
+    /// Set by DECSCA, protected chars aren't erased by the selective erase functions.
+    pub const PROTECTED: u16 = 0b0000_1000_0000_0000;
+    /// Curly/dotted/dashed underline style, only used together with UNDERLINE (see [`super::UnderlineStyle`]).
+    pub const UNDERLINE_STYLE: u16 = 0b0011_0000_0000_0000;
     pub const INVISIBLE: u16 = 0b1000_0000_0000_0000;
 
     /// This is a special attribute that is used to indicate that the character data
@@ -23,11 +29,50 @@ pub mod attribute {
     pub const INVISIBLE_SHORT: u16 = 0b1100_0000_0000_0000;
 }
 
+/// Underline styles as set by `SGR 4:Ps`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UnderlineStyle {
+    #[default]
+    None,
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+impl UnderlineStyle {
+    /// Gives back the style for the `Ps` of `SGR 4:Ps`.
+    pub fn from_sgr(ps: i32) -> Option<Self> {
+        match ps {
+            0 => Some(UnderlineStyle::None),
+            1 => Some(UnderlineStyle::Single),
+            2 => Some(UnderlineStyle::Double),
+            3 => Some(UnderlineStyle::Curly),
+            4 => Some(UnderlineStyle::Dotted),
+            5 => Some(UnderlineStyle::Dashed),
+            _ => None,
+        }
+    }
+
+    pub fn to_sgr(self) -> u8 {
+        match self {
+            UnderlineStyle::None => 0,
+            UnderlineStyle::Single => 1,
+            UnderlineStyle::Double => 2,
+            UnderlineStyle::Curly => 3,
+            UnderlineStyle::Dotted => 4,
+            UnderlineStyle::Dashed => 5,
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct TextAttribute {
     pub(super) font_page: usize,
     pub(super) foreground_color: u32,
     pub(super) background_color: u32,
+    pub(super) underline_color: Option<u32>,
     pub attr: u16,
 }
 
@@ -36,6 +81,7 @@ impl std::fmt::Debug for TextAttribute {
         f.debug_struct("TextAttribute")
             .field("foreground_color", &self.foreground_color)
             .field("background_color", &self.background_color)
+            .field("underline_color", &self.underline_color)
             .field("attr", &format!("{:08b}", self.attr))
             .field("font_page", &self.font_page)
             .finish()
@@ -47,6 +93,7 @@ impl Default for TextAttribute {
         Self {
             foreground_color: 7,
             background_color: 0,
+            underline_color: None,
             attr: attribute::NONE,
             font_page: 0,
         }
@@ -213,7 +260,9 @@ impl TextAttribute {
         (self.attr & attribute::UNDERLINE) == attribute::UNDERLINE
     }
 
+    /// Sets or clears a single (straight) underline.
     pub fn set_is_underlined(&mut self, is_underline: bool) {
+        self.attr &= !attribute::UNDERLINE_STYLE;
         if is_underline {
             self.attr |= attribute::UNDERLINE;
         } else {
@@ -233,6 +282,42 @@ impl TextAttribute {
         }
     }
 
+    pub fn get_underline_style(self) -> UnderlineStyle {
+        if self.is_double_underlined() {
+            return UnderlineStyle::Double;
+        }
+        if !self.is_underlined() {
+            return UnderlineStyle::None;
+        }
+        match (self.attr & attribute::UNDERLINE_STYLE) >> 12 {
+            1 => UnderlineStyle::Curly,
+            2 => UnderlineStyle::Dotted,
+            3 => UnderlineStyle::Dashed,
+            _ => UnderlineStyle::Single,
+        }
+    }
+
+    pub fn set_underline_style(&mut self, style: UnderlineStyle) {
+        self.attr &= !(attribute::UNDERLINE | attribute::DOUBLE_UNDERLINE | attribute::UNDERLINE_STYLE);
+        self.attr |= match style {
+            UnderlineStyle::None => 0,
+            UnderlineStyle::Single => attribute::UNDERLINE,
+            UnderlineStyle::Double => attribute::DOUBLE_UNDERLINE,
+            UnderlineStyle::Curly => attribute::UNDERLINE | 1 << 12,
+            UnderlineStyle::Dotted => attribute::UNDERLINE | 2 << 12,
+            UnderlineStyle::Dashed => attribute::UNDERLINE | 3 << 12,
+        };
+    }
+
+    /// The underline color, `None` if the underline is drawn in the foreground color.
+    pub fn get_underline_color(self) -> Option<u32> {
+        self.underline_color
+    }
+
+    pub fn set_underline_color(&mut self, color: Option<u32>) {
+        self.underline_color = color;
+    }
+
     pub fn is_concealed(self) -> bool {
         (self.attr & attribute::CONCEAL) == attribute::CONCEAL
     }
@@ -249,6 +334,18 @@ impl TextAttribute {
         self.attr = 0;
     }
 
+    pub fn is_protected(self) -> bool {
+        (self.attr & attribute::PROTECTED) == attribute::PROTECTED
+    }
+
+    pub fn set_is_protected(&mut self, is_protected: bool) {
+        if is_protected {
+            self.attr |= attribute::PROTECTED;
+        } else {
+            self.attr &= !attribute::PROTECTED;
+        }
+    }
+
     pub fn is_overlined(self) -> bool {
         (self.attr & attribute::OVERLINE) == attribute::OVERLINE
     }
@@ -277,6 +374,9 @@ impl TextAttribute {
 
 impl PartialEq for TextAttribute {
     fn eq(&self, other: &TextAttribute) -> bool {
-        self.foreground_color == other.foreground_color && self.background_color == other.background_color && self.attr == other.attr
+        self.foreground_color == other.foreground_color
+            && self.background_color == other.background_color
+            && self.underline_color == other.underline_color
+            && self.attr == other.attr
     }
 }